@@ -1,16 +1,186 @@
-use std::process;
+use std::{
+    fs, io,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::process::CommandExt,
+    },
+    process, ptr, thread, time,
+};
 
-pub struct DroppableChild(pub process::Child);
+// how long a program gets to exit after SIGTERM before it is killed
+pub const DEFAULT_KILL_GRACE: time::Duration = time::Duration::from_millis(100);
+const EXIT_POLL_INTERVAL: time::Duration = time::Duration::from_millis(5);
+
+pub struct DroppableChild(pub process::Child, time::Duration);
 
 impl DroppableChild {
     pub fn new(child: process::Child) -> Self {
-        Self(child)
+        Self::with_grace(child, DEFAULT_KILL_GRACE)
+    }
+
+    pub fn with_grace(child: process::Child, grace: time::Duration) -> Self {
+        Self(child, grace)
+    }
+
+    // the grace is waited out on a thread of its own, so the next run does not wait for it
+    pub fn kill_in_background(self) {
+        thread::spawn(move || drop(self));
+    }
+}
+
+// in a process group of its own, so whatever the program runs is signalled along with it,
+// and the terminal's ctrl-c is not
+pub fn own_group(command: &mut process::Command) -> &mut process::Command {
+    command.process_group(0)
+}
+
+// the whole group when the program leads one, see own_group
+fn signal(pid: i32, signal: i32) {
+    if unsafe { libc::kill(-pid, signal) } != 0 {
+        unsafe { libc::kill(pid, signal) };
     }
 }
 
 impl Drop for DroppableChild {
+    // asks first so the program can clean up after itself, killed when it does not exit in time
     fn drop(&mut self) {
-        let _ = self.0.kill();
+        if !matches!(self.0.try_wait(), Ok(None)) {
+            return;
+        }
+
+        let pid = self.0.id() as i32;
+        signal(pid, libc::SIGTERM);
+        let started = time::Instant::now();
+        while started.elapsed() < self.1 {
+            if !matches!(self.0.try_wait(), Ok(None)) {
+                return;
+            }
+            thread::sleep(EXIT_POLL_INTERVAL);
+        }
+
+        signal(pid, libc::SIGKILL);
         let _ = self.0.wait();
     }
 }
+
+// a pseudo-terminal for the program to write to, so it behaves like it would on a terminal,
+// the master end is read like a pipe. openpty is not posix, glibc before 2.34 and the bsds
+// have it in libutil, which the libc crate links, and there is none on windows
+pub fn open_pty(winsize: libc::winsize) -> io::Result<(fs::File, OwnedFd)> {
+    let (mut master, mut slave) = (0, 0);
+    let opened = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            ptr::null_mut(),
+            ptr::null(),
+            &winsize,
+        )
+    };
+    if opened != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let (master, slave) = unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+
+    // only the program's stdout and stderr should hold the terminal open, not whatever else it runs
+    for fd in [&master, &slave] {
+        if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok((fs::File::from(master), slave))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, io::Read};
+
+    fn spawn_ready(script: &str) -> process::Child {
+        let mut child = process::Command::new("sh")
+            .args([
+                "-c",
+                &format!("{}; echo ready; while :; do sleep 0.01; done", script),
+            ])
+            .stdout(process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut buf = [0; 6];
+        child.stdout.as_mut().unwrap().read_exact(&mut buf).unwrap();
+        child
+    }
+
+    #[test]
+    fn droppable_child_terminates() {
+        let dir = env::temp_dir().join(format!("tip-child-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cleaned = dir.join("cleaned");
+
+        // exits cleanly on sigterm within the grace
+        let child = spawn_ready(&format!("trap 'touch {}; exit 0' TERM", cleaned.display()));
+        drop(DroppableChild::with_grace(
+            child,
+            time::Duration::from_secs(5),
+        ));
+        assert!(cleaned.exists());
+
+        // killed once the grace is over
+        let child = spawn_ready("trap '' TERM");
+        let started = time::Instant::now();
+        drop(DroppableChild::with_grace(
+            child,
+            time::Duration::from_millis(50),
+        ));
+        assert!(started.elapsed() < time::Duration::from_secs(1));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn droppable_child_signals_its_group() {
+        // the subshell holds stdout open too, and would outlive its parent otherwise
+        let mut command = process::Command::new("sh");
+        command
+            .args(["-c", "sh -c 'echo ready; exec sleep 10' & wait"])
+            .stdout(process::Stdio::piped());
+        let mut child = own_group(&mut command).spawn().unwrap();
+        let mut stdout = child.stdout.take().unwrap();
+        let mut buf = [0; 6];
+        stdout.read_exact(&mut buf).unwrap();
+
+        let started = time::Instant::now();
+        DroppableChild::with_grace(child, time::Duration::from_secs(5)).kill_in_background();
+        // the grace is waited out elsewhere
+        assert!(started.elapsed() < time::Duration::from_millis(500));
+        // closed once both are gone
+        stdout.read_to_end(&mut Vec::new()).unwrap();
+        assert!(started.elapsed() < time::Duration::from_secs(5));
+    }
+
+    #[test]
+    fn open_pty_is_a_terminal() {
+        let winsize = libc::winsize {
+            ws_row: 10,
+            ws_col: 33,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let (mut master, slave) = open_pty(winsize).unwrap();
+        let mut command = process::Command::new("sh");
+        command
+            .args(["-c", "test -t 1 && stty size <&1"])
+            .stdout(slave);
+        let mut child = command.spawn().unwrap();
+        drop(command);
+        assert!(child.wait().unwrap().success());
+
+        let mut output = Vec::new();
+        let mut buf = [0; 64];
+        // eio once the program closed the terminal
+        while let Ok(size @ 1..) = master.read(&mut buf) {
+            output.extend_from_slice(&buf[..size]);
+        }
+        assert_eq!(output, b"10 33\r\n");
+    }
+}
@@ -0,0 +1,1011 @@
+use anyhow::{Context, Result, anyhow};
+use std::{env, fs, path, str, time};
+
+use crate::{child, data, fields, keymap, terminal};
+
+pub static HELP: &str = "Usage: tip [OPTIONS] <program> [ARGUMENTS]
+
+The query is the last argument of the program, or replaces every {} in the arguments.
+
+Options:
+  --query <query>        Start with this query instead of the one from the last time
+  --no-restore           Neither restore the last query of the program nor remember this one
+  --query-literal        Pass the query as typed as the last argument, {} in the
+                         arguments stays as is and --query-first, --query-stdin are ignored
+  --query-first          Pass the query as the first argument instead of the last
+  --query-stdin <order>  Write the query as a line to the program's stdin instead of passing it
+                         as an argument, before or after the piped input: first, last
+  --print-command <fmt>  Print the command that would run for the starting query and exit:
+                         lines: one argument per line
+                         shell: quoted for a shell on one line
+  --buffer-size <bytes>  Read buffer size for child output [env: TIP_BUFFER_SIZE] [default: 8192]
+  --line-buffered        Show output only up to its last newline, the partial line after it
+                         waits for the rest of the line or the end of the output
+  --max-lines <n>        Keep at most n lines of the output
+  --keep <lines>         Which lines --max-lines keeps: head, tail [default: head]
+  --max-height <n>       Show at most n lines of output even on taller terminals
+  --initial-scroll <pos> Where the output of a new query starts: top, bottom [default: top]
+                         bottom: stays there while output comes in until scrolled
+  --follow               Keep the newest output in view, paused while scrolled up
+  --cursor-line          Highlight a current line, moved with the scroll keys
+  --paging-threshold <n> Once the output has more than n records, show which line is at the
+                         top and how many there are [default: 1000]
+  --header <text>        Show text above the separator, lines are cut off at the terminal width
+  --header-style <style> Style of the header, like --prompt-style
+  --header-command <cmd> Show the output of a shell command above the separator, run once
+                         at the start, e.g 'git branch --show-current'
+  --redraw <mode>        How the screen is redrawn: screen, lines [default: screen]
+                         lines: overwrite line by line, flickers less on some terminals
+  --separator <glyph>    Separator below the prompt, repeated to its width [default: ─]
+  --separator-width <n>  Width of the separator in columns [default: terminal width]
+  --wrap-prompt          Wrap a long query onto more rows instead of scrolling it
+  --border               Draw a box around the output, its long lines are cut instead of wrapped
+  --ruler                Number the columns above the output, for lining up tables,
+                         alt-r toggles it
+  --no-alt-screen        Draw over the normal screen after scrolling its lines into the
+                         scrollback, the default when TERM is dumb, linux or unset
+  --words <mode>         What ctrl-w, alt-d, alt-u/l/c and ctrl-arrows take as a word [default: word]
+                         word: letters and digits, punctuation ends a word too
+                         whitespace: anything between whitespace, like vim's WORD
+  --prompt-style <style> Style of the prompt before the query, e.g 'bold,blue'
+                         bold, dim, italic, underline and a color: black, red, green,
+                         yellow, blue, magenta, cyan, white
+  --query-style <style>  Style of the typed query
+  --placeholder <text>   Show text after the prompt while the query is empty, e.g 'type to filter…'
+  --placeholder-style <style>
+                         Style of the placeholder, like --prompt-style [default: dim]
+  --highlight            Highlight occurrences of the query in the output
+  --color-rule <rule>    Style what a regex matches in the output as regex=style, e.g
+                         'ERROR=bold,red', repeatable, later rules layer over earlier ones
+  --filter <mode>        Dim or hide output lines not containing the query instead of
+                         re-running the program: dim, hide
+  --case <mode>          Case matching for highlights and filters: smart, sensitive, insensitive [default: smart]
+  --validate-regex       Do not run queries that are not a valid regex
+  --diff                 Color lines added and removed since the previous run
+  --delimiter <string>   Records of the output are separated by this literal string instead
+                         of newlines, not a regex, nul for the NUL byte. shown one per line
+                         with their newlines as spaces, accepting prints them as written
+  --nth <fields>         Show only these whitespace separated fields of each record, counting
+                         from 1 or from -1 at the end, e.g '1,3..' or '-1'
+  --tac                  Show the records last first, what accepting prints keeps its order
+  --tac-output           Print the captured output last first too, implies --tac
+  --select               Enter prints the record under the cursor line instead of running
+                         the program again, the whole record even with --nth, implies --cursor-line
+  --select-1             Accept right away without showing tip when the output of the query
+                         has a single record
+  --auto-select          Accept as soon as a finished run of the query leaves a single record,
+                         checked again as the query changes, implies --select
+  --exit-0               Exit with 1 right away without showing tip when the output of the
+                         query is empty
+  --json                 The output is json lines, the line at the top of the pane is pretty
+                         printed in a preview below
+  --preview <cmd>        Show the output of a shell command for the record under the cursor
+                         line, or the top line, in a pane below, the record is $1,
+                         e.g 'git show \"$1\"'
+  --preview-window <position:size>
+                         Where the preview goes, up, down, left or right, and its lines or
+                         columns, or a percentage of them, e.g right:40% [default: down:50%]
+                         a size the terminal has no room for gets half of it, an invalid one
+                         the default
+  --json-key <path>      Show lines as this field of their json, e.g 'metadata.name', implies --json
+  --control <mode>       How control characters of the output show: pass, caret, strip [default: pass]
+                         caret: like cat -v, e.g ^G, color sequences are kept
+                         strip: left out, color sequences are kept
+  --binary <mode>        How output is shown: auto, text, hex [default: auto]
+                         auto: as hex when the start of a run looks binary
+  --pty                  Run the program on a pseudo-terminal sized to the output pane,
+                         for programs that only color or stream their output on a terminal,
+                         its stderr is shown as part of the output. needs openpty, on
+                         glibc before 2.34 and the BSDs from libutil
+  --capture <streams>    Which output of the program is shown: stdout, stderr, both [default: both]
+  --uncaptured <mode>    Where the stderr --capture leaves out goes: null, inherit [default: null]
+                         inherit: to tip's own unless it is the terminal, e.g with
+                         2>progress.log. stdout left out is dropped, tip's own is the result
+  --kill-grace <ms>      How long a program gets to exit after SIGTERM before it is killed,
+                         when a new query replaces it or tip exits, along with whatever
+                         it runs [default: 100]
+  --arg-max <bytes>      Do not run queries whose arguments and environment take more than
+                         this, the footer says so instead [default: ARG_MAX of the system]
+  --size-poll <ms>       Also read the terminal size this often, for terminals or multiplexers
+                         that do not signal resizes, ctrl-l reads it once
+  --transform <cmd>      Pipe the displayed output through a shell command, e.g 'sort -u'
+  --tab <cmd>            Run a shell command with the query as $1 too, its output in a tab of
+                         its own next to the program's, repeatable
+  --query-hook <cmd>     Run a shell command with the query as $1 before every run, of every
+                         tab and of accepting or --review too, what it prints is run instead
+                         unless it prints nothing, exiting non-zero skips the run,
+                         e.g 'echo \"$1\" >> queries.log'
+  --max-query-length <n> Further typing is ignored once the query has n characters [default: 4096]
+  --min-query-len <n>    Do not run the program until the query has n characters, the output
+                         of the last run stays meanwhile
+  --lazy                 Do not run the program until the query is typed or run is pressed
+  --no-input             Do not read piped stdin, the program gets no stdin
+  --input <file>         Feed the program this file instead of piped stdin
+  --empty-accept <mode>  What accepting an empty query does [default: run]
+                         run: run the program without a query and print its output
+                         cancel: print nothing and exit with 1
+                         none: print nothing and exit with 0
+                         ignore: do nothing and keep editing, with --select while the
+                         output is empty
+  --accept-output <mode> What accepting prints [env: TIP_ACCEPT_OUTPUT] [default: run]
+                         run: run the program once more with the query, its stdout is printed
+                         whatever --capture shows, for programs that are not safe to run
+                         twice, e.g curl -X POST, use captured
+                         captured: the output tip shows, unfinished if the run still is
+  --accept-command <cmd> Run this shell command on accept instead of the program, the query
+                         is $1 and with --select the record is $2, e.g 'vim \"$2\"'. it gets
+                         the terminal, the output captured with --accept-output captured is
+                         its stdin instead, and its stdout is tip's or --output
+  --review               Enter shows the accepted output in tip first, enter again prints its
+                         stdout without running the program again, esc goes back to editing
+                         the query
+  --keep-output          Leave the last output in the terminal on exit, unless accepted
+                         output is printed to it
+  --output <file>        Write the output to a file instead of stdout, replaced only once
+                         the program succeeds
+  --bind <key:action,..> Bind keys to actions, e.g 'ctrl-y:accept,ctrl-q:abort'
+  --expect <key,..>      Keys that also accept, the pressed key is printed as the first line,
+                         an empty line when accepted with another key
+
+Keys:
+  enter                  accept
+  ctrl-c, esc            abort, or clear-or-abort to clear the query first, e.g
+                         --bind esc:clear-or-abort, ignore does nothing
+  ctrl-s                 toggle-case, cycle the case matching mode
+  ctrl-r                 run, run the program with the current query again
+  ctrl-x                 cancel, stop the current run and run nothing until the query changes
+  ctrl-f                 toggle-follow, keep the newest output in view
+  ctrl-l                 refresh, read the terminal size again and redraw
+  alt-r                  toggle-ruler, show or hide the column ruler above the output
+  alt-t                  next-tab, show the output of the next --tab
+  up, down               scroll-up, scroll-down
+  btab                   scroll-up
+  pgup, pgdn             page-up, page-down
+  tab                    complete the word before the cursor from words in the output,
+                         cycles when pressed again, or insert-tab to type a literal tab
+                         shown as ⇥, e.g --bind tab:insert-tab
+  insert                 toggle overwrite mode in the prompt
+  home, end              move the cursor to the start or end of the query
+  ctrl-w, alt-backspace  delete the word before the cursor
+  alt-d                  delete the word after the cursor
+  ctrl-t                 transpose the characters around the cursor
+  alt-u, alt-l, alt-c    uppercase, lowercase or capitalize to the end of the word
+  paste                  not bound by default, insert the clipboard, asked from the terminal
+                         with OSC 52 which not all terminals allow, e.g --bind ctrl-v:paste
+  alt-<digits>           repeat the next deletion or cursor motion, e.g alt-3 ctrl-w
+  execute(<cmd>)         not bound by default, run a shell command with the query as $1 and
+                         the terminal handed to it, e.g --bind 'ctrl-g:execute(git add \"$1\")'
+
+Environment:
+  COLUMNS, LINES         Override the terminal size reported by the terminal
+  XDG_STATE_HOME         Where the last query of each program is kept [default: ~/.local/state]
+  NO_COLOR               Leave colors out of the styles and errors
+  TIP_TEMPLATE           The program and arguments when none are given, split on whitespace,
+                         {} or {query} is the query, e.g 'rg {} --json'. a program given
+                         on the command line replaces all of it, options still apply";
+
+// pipes on linux hold 64KiB by default, so a single read rarely fills more than that,
+// 8KiB keeps most of the throughput while still surfacing partial output quickly
+const DEFAULT_BUFFER_SIZE: usize = 1 << 13;
+const MIN_BUFFER_SIZE: usize = 1 << 6;
+const MAX_BUFFER_SIZE: usize = 1 << 20;
+// the query is a single argument, linux limits those to 128KiB,
+// this stays below it even when every character takes 4 bytes
+pub const DEFAULT_MAX_QUERY_LENGTH: usize = 1 << 12;
+
+// below it the output is short enough to page through without a counter
+const DEFAULT_PAGING_THRESHOLD: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputSource {
+    // piped stdin, if stdin is not a tty
+    Stdin,
+    None,
+    File(path::PathBuf),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmptyAccept {
+    Run,
+    Cancel,
+    None,
+    Ignore,
+}
+
+impl str::FromStr for EmptyAccept {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "run" => Self::Run,
+            "cancel" => Self::Cancel,
+            "none" => Self::None,
+            "ignore" => Self::Ignore,
+            _ => return Err(anyhow!("unknown empty accept mode: {}", s)),
+        })
+    }
+}
+
+// what accepting prints, the program runs twice with run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcceptOutput {
+    Run,
+    Captured,
+}
+
+impl str::FromStr for AcceptOutput {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "run" => Self::Run,
+            "captured" => Self::Captured,
+            _ => return Err(anyhow!("unknown accept output: {}", s)),
+        })
+    }
+}
+
+// what the word motions and deletions of the prompt take as a word
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordMode {
+    // letters and digits, punctuation separates words too
+    Word,
+    // anything but whitespace, like vim's WORD
+    Whitespace,
+}
+
+impl str::FromStr for WordMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "word" => Self::Word,
+            "whitespace" => Self::Whitespace,
+            _ => return Err(anyhow!("unknown word mode: {}", s)),
+        })
+    }
+}
+
+// where the query goes in the arguments of the program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryMode {
+    // the last argument, left out when empty
+    Append,
+    // the first argument, before the others, left out when empty
+    Prepend,
+    // replaces {} in the arguments
+    Placeholder,
+    // a line of its own on stdin, before or after the piped input
+    Stdin(StdinOrder),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StdinOrder {
+    First,
+    Last,
+}
+
+impl str::FromStr for StdinOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "first" => Self::First,
+            "last" => Self::Last,
+            _ => return Err(anyhow!("unknown stdin order: {}", s)),
+        })
+    }
+}
+
+// how --print-command prints the arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrintCommand {
+    Lines,
+    Shell,
+}
+
+impl str::FromStr for PrintCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "lines" => Self::Lines,
+            "shell" => Self::Shell,
+            _ => return Err(anyhow!("unknown print format: {}", s)),
+        })
+    }
+}
+
+// where the output of a new query is scrolled to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitialScroll {
+    Top,
+    Bottom,
+}
+
+impl str::FromStr for InitialScroll {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "top" => Self::Top,
+            "bottom" => Self::Bottom,
+            _ => return Err(anyhow!("unknown initial scroll: {}", s)),
+        })
+    }
+}
+
+// which output of the program is read into the pane
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capture {
+    Stdout,
+    Stderr,
+    Both,
+}
+
+impl Capture {
+    pub fn stdout(self) -> bool {
+        self != Self::Stderr
+    }
+
+    pub fn stderr(self) -> bool {
+        self != Self::Stdout
+    }
+}
+
+impl str::FromStr for Capture {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "stdout" => Self::Stdout,
+            "stderr" => Self::Stderr,
+            "both" => Self::Both,
+            _ => return Err(anyhow!("unknown capture mode: {}", s)),
+        })
+    }
+}
+
+// where output that is not captured goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Uncaptured {
+    Null,
+    // stderr to tip's own, unless that is the terminal
+    Inherit,
+}
+
+impl str::FromStr for Uncaptured {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "null" => Self::Null,
+            "inherit" => Self::Inherit,
+            _ => return Err(anyhow!("unknown uncaptured mode: {}", s)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryMode {
+    Auto,
+    Text,
+    Hex,
+}
+
+impl str::FromStr for BinaryMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "auto" => Self::Auto,
+            "text" => Self::Text,
+            "hex" => Self::Hex,
+            _ => return Err(anyhow!("unknown binary mode: {}", s)),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub bin: String,
+    pub bin_args: Vec<String>,
+    pub query_mode: QueryMode,
+    pub query_literal: bool,
+    pub query: Option<String>,
+    pub restore: bool,
+    pub print_command: Option<PrintCommand>,
+    pub buffer_size: usize,
+    pub line_buffered: bool,
+    pub max_lines: Option<usize>,
+    pub max_height: Option<usize>,
+    pub keep: data::Keep,
+    pub header: Option<String>,
+    pub header_style: terminal::Style,
+    pub placeholder: Option<String>,
+    pub placeholder_style: terminal::Style,
+    pub header_command: Option<String>,
+    pub redraw: terminal::Redraw,
+    pub separator: terminal::Separator,
+    pub cursor_line: bool,
+    pub initial_scroll: InitialScroll,
+    pub follow: bool,
+    pub wrap_prompt: bool,
+    pub border: bool,
+    pub ruler: bool,
+    pub alternate_screen: bool,
+    pub word_mode: WordMode,
+    pub prompt_style: terminal::Style,
+    pub query_style: terminal::Style,
+    // false with NO_COLOR
+    pub color: bool,
+    pub highlight: bool,
+    pub color_rules: Vec<terminal::ColorRule>,
+    pub control: terminal::ControlMode,
+    pub filter: Option<terminal::FilterMode>,
+    pub case: terminal::CaseMode,
+    pub validate_regex: bool,
+    pub transform: Option<String>,
+    pub query_hook: Option<String>,
+    pub tabs: Vec<String>,
+    pub pty: bool,
+    pub kill_grace: time::Duration,
+    pub arg_max: Option<usize>,
+    pub size_poll: Option<time::Duration>,
+    // typed before the keyboard, for tests and demos, not in the help
+    pub keys: Vec<keymap::Key>,
+    pub diff: bool,
+    // none for newlines
+    pub delimiter: Option<Vec<u8>>,
+    pub nth: Option<fields::Fields>,
+    pub tac: bool,
+    // reverses the output of --accept-output captured as well
+    pub tac_output: bool,
+    pub select: bool,
+    pub select_1: bool,
+    pub auto_select: bool,
+    pub exit_0: bool,
+    pub json: bool,
+    pub json_key: Option<String>,
+    pub preview: Option<String>,
+    pub preview_window: terminal::PreviewWindow,
+    pub binary: BinaryMode,
+    pub capture: Capture,
+    pub uncaptured: Uncaptured,
+    pub max_query_length: usize,
+    pub min_query_length: usize,
+    pub paging_threshold: usize,
+    pub lazy: bool,
+    pub input: InputSource,
+    pub keymap: keymap::Keymap,
+    pub expect: Vec<keymap::Key>,
+    pub empty_accept: EmptyAccept,
+    pub accept_output: AcceptOutput,
+    // a shell command accept runs instead of the program
+    pub accept_command: Option<String>,
+    pub output: Option<path::PathBuf>,
+    pub keep_output: bool,
+    pub review: bool,
+    // about values that were replaced by their default, printed before the terminal is taken
+    pub warnings: Vec<String>,
+}
+
+fn parse_value<T: str::FromStr>(name: &str, value: Option<String>) -> Result<T> {
+    let Some(value) = value else {
+        return Err(anyhow!("{} requires a value", name));
+    };
+    value
+        .parse()
+        .map_err(|_| anyhow!("invalid value for {}: '{}'", name, value))
+}
+
+fn parse_buffer_size(name: &str, value: Option<String>) -> Result<usize> {
+    let size = parse_value(name, value)?;
+    if !(MIN_BUFFER_SIZE..=MAX_BUFFER_SIZE).contains(&size) {
+        return Err(anyhow!(
+            "{} must be between {} and {}",
+            name,
+            MIN_BUFFER_SIZE,
+            MAX_BUFFER_SIZE
+        ));
+    }
+    Ok(size)
+}
+
+// terminals known to ignore or mishandle switching to the alternate screen
+fn has_alternate_screen(term: &str) -> bool {
+    !matches!(
+        term,
+        "" | "dumb" | "linux" | "cons25" | "vt52" | "vt100" | "vt102" | "ansi"
+    )
+}
+
+// the program and its arguments of TIP_TEMPLATE, {query} is the same as {}
+fn parse_template(template: &str) -> Result<(String, Vec<String>)> {
+    let mut argv = template
+        .split_whitespace()
+        .map(|v| v.replace("{query}", "{}"));
+    let Some(bin) = argv.next() else {
+        return Err(anyhow!("TIP_TEMPLATE has no program"));
+    };
+    Ok((bin, argv.collect()))
+}
+
+impl Config {
+    pub fn line_limit(&self) -> Option<data::LineLimit> {
+        self.max_lines.map(|max| data::LineLimit {
+            max,
+            keep: self.keep,
+        })
+    }
+
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let buffer_size = match env::var("TIP_BUFFER_SIZE") {
+            Ok(v) => parse_buffer_size("TIP_BUFFER_SIZE", Some(v))?,
+            Err(_) => DEFAULT_BUFFER_SIZE,
+        };
+        let accept_output = match env::var("TIP_ACCEPT_OUTPUT") {
+            Ok(v) => parse_value("TIP_ACCEPT_OUTPUT", Some(v))?,
+            Err(_) => AcceptOutput::Run,
+        };
+
+        let mut config = Self {
+            bin: String::new(),
+            bin_args: Vec::new(),
+            query_mode: QueryMode::Append,
+            query_literal: false,
+            query: None,
+            restore: true,
+            print_command: None,
+            buffer_size,
+            line_buffered: false,
+            max_lines: None,
+            max_height: None,
+            keep: data::Keep::Head,
+            header: None,
+            header_style: terminal::Style::default(),
+            placeholder: None,
+            placeholder_style: "dim".parse().unwrap(),
+            header_command: None,
+            redraw: terminal::Redraw::Screen,
+            separator: terminal::Separator::default(),
+            cursor_line: false,
+            initial_scroll: InitialScroll::Top,
+            follow: false,
+            wrap_prompt: false,
+            border: false,
+            ruler: false,
+            alternate_screen: env::var("TERM").is_ok_and(|v| has_alternate_screen(&v)),
+            word_mode: WordMode::Word,
+            prompt_style: terminal::Style::default(),
+            query_style: terminal::Style::default(),
+            color: true,
+            highlight: false,
+            color_rules: Vec::new(),
+            control: terminal::ControlMode::Pass,
+            filter: None,
+            case: terminal::CaseMode::Smart,
+            validate_regex: false,
+            transform: None,
+            query_hook: None,
+            tabs: Vec::new(),
+            pty: false,
+            kill_grace: child::DEFAULT_KILL_GRACE,
+            arg_max: None,
+            size_poll: None,
+            keys: Vec::new(),
+            diff: false,
+            delimiter: None,
+            nth: None,
+            tac: false,
+            tac_output: false,
+            select: false,
+            select_1: false,
+            auto_select: false,
+            exit_0: false,
+            json: false,
+            json_key: None,
+            preview: None,
+            preview_window: terminal::PreviewWindow::default(),
+            binary: BinaryMode::Auto,
+            capture: Capture::Both,
+            uncaptured: Uncaptured::Null,
+            max_query_length: DEFAULT_MAX_QUERY_LENGTH,
+            min_query_length: 0,
+            paging_threshold: DEFAULT_PAGING_THRESHOLD,
+            lazy: false,
+            input: InputSource::Stdin,
+            keymap: keymap::Keymap::default(),
+            expect: Vec::new(),
+            empty_accept: EmptyAccept::Run,
+            accept_output,
+            accept_command: None,
+            output: None,
+            keep_output: false,
+            review: false,
+            warnings: Vec::new(),
+        };
+
+        let mut args = args.into_iter();
+        let mut bin = None;
+        while let Some(arg) = args.next() {
+            // support both `--flag value` and `--flag=value`
+            let (name, mut value) = match arg.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => {
+                    (name.to_string(), Some(value.to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+            let mut next_value = || value.take().or_else(|| args.next());
+
+            match name.as_str() {
+                "--query" => config.query = Some(parse_value(&name, next_value())?),
+                "--no-restore" => config.restore = false,
+                "--query-literal" => config.query_literal = true,
+                "--query-first" => config.query_mode = QueryMode::Prepend,
+                "--query-stdin" => {
+                    config.query_mode = QueryMode::Stdin(parse_value(&name, next_value())?)
+                }
+                "--print-command" => config.print_command = Some(parse_value(&name, next_value())?),
+                "--buffer-size" => config.buffer_size = parse_buffer_size(&name, next_value())?,
+                "--line-buffered" => config.line_buffered = true,
+                "--max-lines" => config.max_lines = Some(parse_value(&name, next_value())?),
+                "--max-height" => {
+                    let max_height = parse_value(&name, next_value())?;
+                    if max_height == 0 {
+                        return Err(anyhow!("--max-height must be at least 1"));
+                    }
+                    config.max_height = Some(max_height);
+                }
+                "--keep" => config.keep = parse_value(&name, next_value())?,
+                "--header" => config.header = Some(parse_value(&name, next_value())?),
+                "--header-style" => config.header_style = parse_value(&name, next_value())?,
+                "--header-command" => {
+                    config.header_command = Some(parse_value(&name, next_value())?)
+                }
+                "--redraw" => config.redraw = parse_value(&name, next_value())?,
+                "--separator" => {
+                    config.separator.glyph = parse_value(&name, next_value())?;
+                    if config.separator.glyph.is_empty() {
+                        return Err(anyhow!("--separator can not be empty"));
+                    }
+                }
+                "--separator-width" => {
+                    config.separator.width = Some(parse_value(&name, next_value())?)
+                }
+                "--wrap-prompt" => config.wrap_prompt = true,
+                "--border" => config.border = true,
+                "--ruler" => config.ruler = true,
+                "--no-alt-screen" => config.alternate_screen = false,
+                "--words" => config.word_mode = parse_value(&name, next_value())?,
+                "--prompt-style" => config.prompt_style = parse_value(&name, next_value())?,
+                "--query-style" => config.query_style = parse_value(&name, next_value())?,
+                "--placeholder" => config.placeholder = Some(parse_value(&name, next_value())?),
+                "--placeholder-style" => {
+                    config.placeholder_style = parse_value(&name, next_value())?
+                }
+                "--initial-scroll" => config.initial_scroll = parse_value(&name, next_value())?,
+                "--follow" => config.follow = true,
+                "--cursor-line" => config.cursor_line = true,
+                "--highlight" => config.highlight = true,
+                "--color-rule" => config.color_rules.push(parse_value(&name, next_value())?),
+                "--filter" => config.filter = Some(parse_value(&name, next_value())?),
+                "--case" => config.case = parse_value(&name, next_value())?,
+                "--validate-regex" => config.validate_regex = true,
+                "--diff" => config.diff = true,
+                "--delimiter" => {
+                    config.delimiter = match parse_value::<String>(&name, next_value())?.as_str() {
+                        "" => return Err(anyhow!("--delimiter can not be empty")),
+                        "\n" => None,
+                        "nul" => Some(vec![0]),
+                        v => Some(v.as_bytes().to_vec()),
+                    }
+                }
+                "--nth" => config.nth = Some(parse_value(&name, next_value())?),
+                "--tac" => config.tac = true,
+                "--tac-output" => {
+                    config.tac = true;
+                    config.tac_output = true;
+                }
+                "--select" => {
+                    config.select = true;
+                    config.cursor_line = true;
+                }
+                "--select-1" => config.select_1 = true,
+                "--auto-select" => {
+                    config.auto_select = true;
+                    config.select = true;
+                    config.cursor_line = true;
+                }
+                "--exit-0" => config.exit_0 = true,
+                "--json" => config.json = true,
+                "--preview" => config.preview = Some(parse_value(&name, next_value())?),
+                // a layout is not worth refusing to start over
+                "--preview-window" => {
+                    let value = parse_value::<String>(&name, next_value())?;
+                    config.preview_window = value.parse().unwrap_or_else(|_| {
+                        config.warnings.push(format!(
+                            "invalid value for {}: '{}', using down:50%",
+                            name, value
+                        ));
+                        terminal::PreviewWindow::default()
+                    });
+                }
+                "--json-key" => {
+                    config.json = true;
+                    config.json_key = Some(parse_value(&name, next_value())?);
+                }
+                "--control" => config.control = parse_value(&name, next_value())?,
+                "--binary" => config.binary = parse_value(&name, next_value())?,
+                "--capture" => config.capture = parse_value(&name, next_value())?,
+                "--uncaptured" => config.uncaptured = parse_value(&name, next_value())?,
+                "--pty" => config.pty = true,
+                "--arg-max" => config.arg_max = Some(parse_value(&name, next_value())?),
+                "--kill-grace" => {
+                    config.kill_grace =
+                        time::Duration::from_millis(parse_value(&name, next_value())?)
+                }
+                // a file with @file
+                "--keys" => {
+                    let keys = parse_value::<String>(&name, next_value())?;
+                    let keys = match keys.strip_prefix('@') {
+                        Some(path) => fs::read_to_string(path)
+                            .with_context(|| format!("failed reading {}", path))?
+                            .trim_end_matches('\n')
+                            .to_string(),
+                        None => keys,
+                    };
+                    config.keys.extend(keymap::parse_keys(&keys)?);
+                }
+                "--size-poll" => {
+                    config.size_poll = Some(time::Duration::from_millis(parse_value(
+                        &name,
+                        next_value(),
+                    )?))
+                }
+                "--transform" => config.transform = Some(parse_value(&name, next_value())?),
+                "--query-hook" => config.query_hook = Some(parse_value(&name, next_value())?),
+                "--tab" => config.tabs.push(parse_value(&name, next_value())?),
+                "--paging-threshold" => config.paging_threshold = parse_value(&name, next_value())?,
+                "--max-query-length" => config.max_query_length = parse_value(&name, next_value())?,
+                "--min-query-len" => config.min_query_length = parse_value(&name, next_value())?,
+                "--lazy" => config.lazy = true,
+                "--empty-accept" => config.empty_accept = parse_value(&name, next_value())?,
+                "--accept-output" => config.accept_output = parse_value(&name, next_value())?,
+                "--accept-command" => {
+                    config.accept_command = Some(parse_value(&name, next_value())?)
+                }
+                "--review" => config.review = true,
+                "--keep-output" => config.keep_output = true,
+                "--output" => config.output = Some(parse_value(&name, next_value())?),
+                "--bind" => config
+                    .keymap
+                    .bind(&parse_value::<String>(&name, next_value())?)?,
+                "--expect" => {
+                    for key in parse_value::<String>(&name, next_value())?.split(',') {
+                        let key = key.parse()?;
+                        config.keymap.insert(key, keymap::Action::Accept);
+                        config.expect.push(key);
+                    }
+                }
+                "--no-input" | "--input" => {
+                    if config.input != InputSource::Stdin {
+                        return Err(anyhow!("--no-input and --input are mutually exclusive"));
+                    }
+                    config.input = match name.as_str() {
+                        "--input" => InputSource::File(parse_value(&name, next_value())?),
+                        _ => InputSource::None,
+                    };
+                }
+                "--" => {
+                    bin = args.next();
+                    break;
+                }
+                x if x.starts_with("--") => return Err(anyhow!("unknown option: {}\n{}", x, HELP)),
+                _ => {
+                    bin = Some(arg);
+                    break;
+                }
+            }
+        }
+
+        // a diff colors lines, which would no longer be json
+        if config.diff && config.json {
+            return Err(anyhow!("--diff and --json are mutually exclusive"));
+        }
+
+        // the review is of the program given on the command line only
+        if !config.tabs.is_empty() && config.review {
+            return Err(anyhow!("--tab and --review are mutually exclusive"));
+        }
+
+        // the review shows the output of the program, which the accept command is run instead of
+        if config.accept_command.is_some() && config.review {
+            return Err(anyhow!(
+                "--accept-command and --review are mutually exclusive"
+            ));
+        }
+
+        // removed lines of a diff are not records to select
+        if config.diff && config.select {
+            return Err(anyhow!("--diff and --select are mutually exclusive"));
+        }
+
+        if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            config.prompt_style = config.prompt_style.without_color();
+            config.query_style = config.query_style.without_color();
+            config.header_style = config.header_style.without_color();
+            config.placeholder_style = config.placeholder_style.without_color();
+            config.color_rules = config
+                .color_rules
+                .into_iter()
+                .map(terminal::ColorRule::without_color)
+                .collect();
+            config.color = false;
+        }
+
+        let (bin, bin_args) = match (bin, env::var("TIP_TEMPLATE")) {
+            (Some(bin), _) => (bin, args.collect()),
+            (None, Ok(template)) => parse_template(&template)?,
+            (None, Err(_)) => return Err(anyhow!(HELP)),
+        };
+        config.bin = bin;
+        config.bin_args = bin_args;
+        if config.query_literal {
+            config.query_mode = QueryMode::Append;
+        } else if config.query_mode == QueryMode::Append
+            && config.bin_args.iter().any(|v| v.contains("{}"))
+        {
+            config.query_mode = QueryMode::Placeholder;
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Config> {
+        Config::parse(args.iter().map(|v| v.to_string()))
+    }
+
+    #[test]
+    fn parse_buffer_size() {
+        let config = parse(&["--buffer-size", "1024", "rg", "--json"]).unwrap();
+        assert_eq!(config.buffer_size, 1024);
+        assert_eq!(config.bin, "rg");
+        assert_eq!(config.bin_args, vec!["--json"]);
+
+        let config = parse(&["--buffer-size=65536", "jq"]).unwrap();
+        assert_eq!(config.buffer_size, 65536);
+
+        assert!(parse(&["--buffer-size", "0", "jq"]).is_err());
+        assert!(parse(&["--buffer-size", "abc", "jq"]).is_err());
+        assert!(parse(&["--buffer-size"]).is_err());
+    }
+
+    #[test]
+    fn parse_expect() {
+        let config = parse(&["--expect", "ctrl-y,ctrl-o", "ls"]).unwrap();
+        assert_eq!(
+            config.expect,
+            vec![keymap::Key::Ctrl('y'), keymap::Key::Ctrl('o')]
+        );
+        assert_eq!(
+            config.keymap.action(&terminal::TerminalInput::Ctrl(b'o')),
+            Some(keymap::Action::Accept)
+        );
+    }
+
+    #[test]
+    fn parse_mutually_exclusive() {
+        assert!(parse(&["--review", "ls"]).is_ok());
+        let err = parse(&["--review", "--accept-command", "vim \"$2\"", "ls"]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "--accept-command and --review are mutually exclusive"
+        );
+        assert!(parse(&["--review", "--tab", "ps", "ls"]).is_err());
+    }
+
+    #[test]
+    fn parse_preview_window() {
+        let config = parse(&["--preview-window", "right:40%", "ls"]).unwrap();
+        assert_eq!(
+            config.preview_window,
+            terminal::PreviewWindow {
+                position: terminal::PreviewPosition::Right,
+                size: terminal::PreviewSize::Percent(40),
+            }
+        );
+        assert!(config.warnings.is_empty());
+
+        // the default instead, with a warning
+        let config = parse(&["--preview-window", "sideways", "ls"]).unwrap();
+        assert_eq!(config.preview_window, terminal::PreviewWindow::default());
+        assert_eq!(
+            config.warnings,
+            ["invalid value for --preview-window: 'sideways', using down:50%"]
+        );
+    }
+
+    #[test]
+    fn alternate_screen_from_term() {
+        assert!(has_alternate_screen("xterm-256color"));
+        assert!(has_alternate_screen("screen"));
+        assert!(!has_alternate_screen("dumb"));
+        assert!(!has_alternate_screen("linux"));
+        assert!(!has_alternate_screen(""));
+        assert!(!parse(&["--no-alt-screen", "jq"]).unwrap().alternate_screen);
+    }
+
+    #[test]
+    fn parse_template_placeholders() {
+        let (bin, bin_args) = parse_template("rg  {query} --json").unwrap();
+        assert_eq!(
+            (bin.as_str(), bin_args.clone()),
+            ("rg", vec!["{}".to_string(), "--json".to_string()])
+        );
+
+        // as if given on the command line
+        let mut args = vec!["--".to_string(), bin];
+        args.extend(bin_args);
+        let config = Config::parse(args).unwrap();
+        assert_eq!(config.query_mode, QueryMode::Placeholder);
+        assert_eq!(config.bin_args, ["{}", "--json"]);
+
+        assert!(parse_template(" ").is_err());
+    }
+
+    #[test]
+    fn parse_delimiter() {
+        assert_eq!(parse(&["ls"]).unwrap().delimiter, None);
+        assert_eq!(
+            parse(&["--delimiter", ",", "ls"]).unwrap().delimiter,
+            Some(b",".to_vec())
+        );
+        assert_eq!(
+            parse(&["--delimiter=nul", "ls"]).unwrap().delimiter,
+            Some(vec![0])
+        );
+        assert!(parse(&["--delimiter=", "ls"]).is_err());
+    }
+
+    #[test]
+    fn parse_query_mode() {
+        assert_eq!(parse(&["rg", "-i"]).unwrap().query_mode, QueryMode::Append);
+        assert_eq!(
+            parse(&["rg", "--glob={}", "src"]).unwrap().query_mode,
+            QueryMode::Placeholder
+        );
+        // {} is left alone when the query goes first
+        assert_eq!(
+            parse(&["--query-first", "rg", "{}"]).unwrap().query_mode,
+            QueryMode::Prepend
+        );
+        assert_eq!(
+            parse(&["--query-literal", "--query-stdin", "last", "rg", "{}"])
+                .unwrap()
+                .query_mode,
+            QueryMode::Append
+        );
+    }
+
+    #[test]
+    fn parse_input() {
+        assert_eq!(parse(&["jq"]).unwrap().input, InputSource::Stdin);
+        assert_eq!(
+            parse(&["--no-input", "jq"]).unwrap().input,
+            InputSource::None
+        );
+        assert_eq!(
+            parse(&["--input", "data.json", "jq"]).unwrap().input,
+            InputSource::File("data.json".into())
+        );
+
+        assert!(parse(&["--no-input", "--input", "data.json", "jq"]).is_err());
+        assert!(parse(&["--input", "a", "--input", "b", "jq"]).is_err());
+    }
+}
@@ -0,0 +1,441 @@
+use anyhow::{Result, anyhow};
+use arc_swap::{ArcSwap, ArcSwapOption};
+use std::{collections, mem, str, sync, time};
+
+// publishing copies the whole buffer, so it is limited to about once per rendered frame
+const PUBLISH_INTERVAL: time::Duration = time::Duration::from_millis(16);
+// for large outputs the interval grows so copying takes at most a fifth of the time
+const PUBLISH_COST_FACTOR: u32 = 4;
+
+// output of a command shared with the renderer,
+// readers of the published snapshot never wait on the writer
+pub struct SharedData {
+    published: ArcSwap<Snapshot>,
+    // output of the run before, only kept when asked for as it doubles the memory
+    previous: ArcSwapOption<Vec<u8>>,
+    keep_previous: bool,
+    has_run: sync::atomic::AtomicBool,
+}
+
+// swapped in as one, so a version always goes with the output it was bumped for
+struct Snapshot {
+    version: u64,
+    // of the last reset, only the writer that did it publishes
+    run: u64,
+    // bumped whenever the output changes other than by appending to it,
+    // so by every reset, as a writer's first publish is of a run of its own
+    generation: u64,
+    data: sync::Arc<Vec<u8>>,
+}
+
+impl SharedData {
+    pub fn new(keep_previous: bool) -> Self {
+        Self {
+            published: ArcSwap::from_pointee(Snapshot {
+                version: 0,
+                run: 0,
+                generation: 0,
+                data: sync::Arc::new(Vec::new()),
+            }),
+            previous: ArcSwapOption::empty(),
+            keep_previous,
+            has_run: sync::atomic::AtomicBool::new(false),
+        }
+    }
+
+    pub fn load(&self) -> sync::Arc<Vec<u8>> {
+        self.published.load().data.clone()
+    }
+
+    // the output with its version, bumped on every publish and reset, so a reader holding
+    // an older version knows its copy is stale without comparing the output
+    pub fn snapshot(&self) -> (u64, sync::Arc<Vec<u8>>) {
+        let published = self.published.load();
+        (published.version, published.data.clone())
+    }
+
+    // none on the first run
+    pub fn load_previous(&self) -> Option<sync::Arc<Vec<u8>>> {
+        self.previous.load_full()
+    }
+
+    // the output with its generation, an earlier load with the same generation is a prefix of it
+    pub fn generational(&self) -> (u64, sync::Arc<Vec<u8>>) {
+        let published = self.published.load();
+        (published.generation, published.data.clone())
+    }
+
+    // empties the output for a new run, returns the token its publishes go with
+    pub fn reset(&self) -> u64 {
+        let previous = self.published.rcu(|published| Snapshot {
+            version: published.version + 1,
+            run: published.run + 1,
+            generation: published.generation + 1,
+            data: sync::Arc::new(Vec::new()),
+        });
+        if self.keep_previous && self.has_run.swap(true, sync::atomic::Ordering::Relaxed) {
+            self.previous.store(Some(previous.data.clone()));
+        }
+        previous.run + 1
+    }
+
+    // dropped when another run reset the output since, a superseded run is still read to
+    // its end but must not replace the output of the next one. returns whether it was published
+    fn publish(&self, run: u64, data: Vec<u8>, appended: bool) -> bool {
+        let data = sync::Arc::new(data);
+        // the version goes up by one even when another run swaps at the same time
+        let previous = self.published.rcu(|published| {
+            if published.run != run {
+                return published.clone();
+            }
+            sync::Arc::new(Snapshot {
+                version: published.version + 1,
+                run,
+                generation: published.generation + !appended as u64,
+                data: data.clone(),
+            })
+        });
+        previous.run == run
+    }
+}
+
+// which lines stay when the output has more than --max-lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keep {
+    Head,
+    Tail,
+}
+
+impl str::FromStr for Keep {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "head" => Self::Head,
+            "tail" => Self::Tail,
+            _ => return Err(anyhow!("unknown keep mode: {}", s)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineLimit {
+    pub max: usize,
+    pub keep: Keep,
+}
+
+// appends to a private buffer and swaps in a copy of it
+pub struct DataWriter {
+    shared: sync::Arc<SharedData>,
+    buf: Vec<u8>,
+    // everything before it was dropped by the line limit, compacted lazily
+    start: usize,
+    // offsets of the newlines after start
+    newlines: collections::VecDeque<usize>,
+    line_limit: Option<LineLimit>,
+    // lines were dropped from the top since the last publish
+    dropped: bool,
+    // taken when the first output resets the shared one
+    run: Option<u64>,
+    pending: bool,
+    published_at: Option<time::Instant>,
+    min_interval: time::Duration,
+    interval: time::Duration,
+}
+
+impl DataWriter {
+    pub fn new(shared: sync::Arc<SharedData>) -> Self {
+        Self {
+            shared,
+            buf: Vec::new(),
+            start: 0,
+            newlines: collections::VecDeque::new(),
+            line_limit: None,
+            dropped: false,
+            run: None,
+            pending: false,
+            published_at: None,
+            min_interval: PUBLISH_INTERVAL,
+            interval: PUBLISH_INTERVAL,
+        }
+    }
+
+    pub fn set_line_limit(&mut self, line_limit: Option<LineLimit>) {
+        self.line_limit = line_limit;
+    }
+
+    // how long until pending data should be flushed, none if nothing is pending
+    pub fn flush_timeout(&self) -> Option<time::Duration> {
+        if !self.pending {
+            return None;
+        }
+        Some(match self.published_at {
+            Some(published_at) => self.interval.saturating_sub(published_at.elapsed()),
+            None => time::Duration::ZERO,
+        })
+    }
+
+    // returns whether the snapshot was published
+    pub fn push(&mut self, buf: &[u8]) -> bool {
+        // a run replaces the previous output once it writes something
+        if self.run.is_none() {
+            self.run = Some(self.shared.reset());
+        }
+
+        match self.line_limit {
+            None => self.buf.extend_from_slice(buf),
+            Some(line_limit) => self.push_limited(buf, line_limit),
+        }
+        self.pending = true;
+
+        if self.flush_timeout() == Some(time::Duration::ZERO) {
+            return self.flush();
+        }
+        false
+    }
+
+    fn push_limited(&mut self, buf: &[u8], line_limit: LineLimit) {
+        match line_limit.keep {
+            // the rest of the output is read but dropped
+            Keep::Head => {
+                for v in buf {
+                    if self.newlines.len() >= line_limit.max {
+                        break;
+                    }
+                    if *v == b'\n' {
+                        self.newlines.push_back(self.buf.len());
+                    }
+                    self.buf.push(*v);
+                }
+            }
+            Keep::Tail => {
+                for (i, v) in buf.iter().enumerate() {
+                    if *v == b'\n' {
+                        self.newlines.push_back(self.buf.len() + i);
+                    }
+                }
+                self.buf.extend_from_slice(buf);
+
+                while self.line_count() > line_limit.max {
+                    self.start = self.newlines.pop_front().unwrap() + 1;
+                    self.dropped = true;
+                }
+                // dropped lines are only moved out once they are half of the buffer
+                if self.start > self.buf.len() / 2 {
+                    self.buf.drain(..self.start);
+                    for v in &mut self.newlines {
+                        *v -= self.start;
+                    }
+                    self.start = 0;
+                }
+            }
+        }
+    }
+
+    // a trailing line without a newline counts too
+    fn line_count(&self) -> usize {
+        let partial = match self.newlines.back() {
+            Some(v) => *v + 1 < self.buf.len(),
+            None => self.start < self.buf.len(),
+        };
+        self.newlines.len() + partial as usize
+    }
+
+    // at the end of a run, one that wrote nothing replaces the previous output with nothing,
+    // returns whether the snapshot was published
+    pub fn finish(&mut self) -> bool {
+        if self.run.is_none() {
+            self.run = Some(self.shared.reset());
+            return true;
+        }
+        self.flush()
+    }
+
+    // returns whether the snapshot was published
+    pub fn flush(&mut self) -> bool {
+        let Some(run) = self.run.filter(|_| self.pending) else {
+            return false;
+        };
+
+        let start = time::Instant::now();
+        let appended = !mem::take(&mut self.dropped);
+        let published = self
+            .shared
+            .publish(run, self.buf[self.start..].to_vec(), appended);
+        self.interval = self.min_interval.max(start.elapsed() * PUBLISH_COST_FACTOR);
+
+        self.pending = false;
+        self.published_at = Some(time::Instant::now());
+        published
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn data_writer_publishes() {
+        let shared = sync::Arc::new(SharedData::new(false));
+        shared.publish(0, b"previous".to_vec(), false);
+
+        let mut writer = DataWriter::new(shared.clone());
+        writer.min_interval = time::Duration::from_secs(60);
+        assert!(writer.push(b"foo"));
+        assert_eq!(*shared.load(), b"foo");
+
+        // within the publish interval the data stays pending
+        assert!(!writer.push(b"bar"));
+        assert_eq!(*shared.load(), b"foo");
+        assert!(writer.flush_timeout().is_some());
+
+        assert!(writer.flush());
+        assert_eq!(*shared.load(), b"foobar");
+        assert_eq!(writer.flush_timeout(), None);
+        assert!(!writer.flush());
+        assert_eq!(shared.load_previous(), None);
+    }
+
+    #[test]
+    fn data_writer_line_limit() {
+        let output = (0..10000)
+            .map(|v| format!("line {}\n", v))
+            .collect::<String>();
+        let limited = |keep| {
+            let shared = sync::Arc::new(SharedData::new(false));
+            let mut writer = DataWriter::new(shared.clone());
+            writer.set_line_limit(Some(LineLimit { max: 3, keep }));
+            for chunk in output.as_bytes().chunks(7) {
+                writer.push(chunk);
+            }
+            writer.flush();
+            String::from_utf8(shared.load().to_vec()).unwrap()
+        };
+
+        assert_eq!(limited(Keep::Head), "line 0\nline 1\nline 2\n");
+        assert_eq!(limited(Keep::Tail), "line 9997\nline 9998\nline 9999\n");
+    }
+
+    #[test]
+    fn data_writer_finish_without_output() {
+        let shared = sync::Arc::new(SharedData::new(false));
+        DataWriter::new(shared.clone()).push(b"previous");
+
+        let mut writer = DataWriter::new(shared.clone());
+        assert!(writer.finish());
+        assert_eq!(*shared.load(), b"");
+        assert!(!writer.finish());
+    }
+
+    #[test]
+    fn data_writer_generation() {
+        let shared = sync::Arc::new(SharedData::new(false));
+        let mut writer = DataWriter::new(shared.clone());
+        writer.set_line_limit(Some(LineLimit {
+            max: 2,
+            keep: Keep::Tail,
+        }));
+
+        writer.push(b"a\n");
+        let (generation, _) = shared.generational();
+        writer.push(b"b\n");
+        writer.flush();
+        assert_eq!(
+            shared.generational(),
+            (generation, sync::Arc::new(b"a\nb\n".to_vec()))
+        );
+
+        // dropping the first line is not an append
+        writer.push(b"c\n");
+        writer.flush();
+        let dropped = shared.generational();
+        assert_eq!(*dropped.1, b"b\nc\n");
+        assert_ne!(dropped.0, generation);
+
+        // nor is another writer's output, a superseded one's is dropped
+        let mut next = DataWriter::new(shared.clone());
+        next.push(b"d\n");
+        let (generation, _) = shared.generational();
+        assert_ne!(generation, dropped.0);
+        writer.push(b"e\n");
+        writer.flush();
+        assert_eq!(
+            shared.generational(),
+            (generation, sync::Arc::new(b"d\n".to_vec()))
+        );
+    }
+
+    #[test]
+    fn shared_data_versions() {
+        let shared = sync::Arc::new(SharedData::new(false));
+        assert_eq!(shared.snapshot().0, 0);
+        DataWriter::new(shared.clone()).push(b"a");
+        assert_eq!(shared.snapshot(), (2, sync::Arc::new(b"a".to_vec())));
+
+        let done = sync::Arc::new(sync::atomic::AtomicBool::new(false));
+        let writers = (0..4)
+            .map(|_| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    for _ in 0..250 {
+                        let run = shared.reset();
+                        shared.publish(run, b"b".to_vec(), true);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        let reader = thread::spawn({
+            let shared = shared.clone();
+            let done = done.clone();
+            move || {
+                let mut last = 0;
+                while !done.load(sync::atomic::Ordering::Relaxed) {
+                    let (version, _) = shared.snapshot();
+                    assert!(version >= last);
+                    last = version;
+                }
+            }
+        });
+        for v in writers {
+            v.join().unwrap();
+        }
+        done.store(true, sync::atomic::Ordering::Relaxed);
+        reader.join().unwrap();
+        // every reset counts, publishes of runs reset over in between do not
+        let (version, data) = shared.snapshot();
+        assert!((1002..=2002).contains(&version));
+        assert_eq!(*data, b"b");
+    }
+
+    #[test]
+    fn superseded_writer_does_not_publish() {
+        let shared = sync::Arc::new(SharedData::new(false));
+        let mut old = DataWriter::new(shared.clone());
+        old.min_interval = time::Duration::from_secs(60);
+        assert!(old.push(b"old\n"));
+
+        let mut new = DataWriter::new(shared.clone());
+        new.min_interval = time::Duration::from_secs(60);
+        assert!(new.push(b"new\n"));
+        old.push(b"more\n");
+        assert!(!old.flush());
+        assert_eq!(*shared.load(), b"new\n");
+
+        new.push(b"b");
+        assert!(new.flush());
+        assert!(!old.finish());
+        assert_eq!(*shared.load(), b"new\nb");
+    }
+
+    #[test]
+    fn shared_data_keeps_previous() {
+        let shared = sync::Arc::new(SharedData::new(true));
+        DataWriter::new(shared.clone()).push(b"first");
+        assert_eq!(shared.load_previous(), None);
+
+        DataWriter::new(shared.clone()).push(b"second");
+        assert_eq!(*shared.load_previous().unwrap(), b"first");
+        assert_eq!(*shared.load(), b"second");
+    }
+}
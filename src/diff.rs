@@ -0,0 +1,103 @@
+// past this many compared line pairs the changed middle is shown as replaced wholesale,
+// the table costs a byte per pair
+const MAX_TABLE_SIZE: usize = 1 << 22;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLine<'a> {
+    Same(&'a [u8]),
+    Added(&'a [u8]),
+    Removed(&'a [u8]),
+}
+
+// line level diff from the longest common subsequence,
+// common prefixes and suffixes are skipped first as re-runs mostly share them
+pub fn diff_lines<'a>(old: &'a [u8], new: &'a [u8]) -> Vec<DiffLine<'a>> {
+    let old = old.split(|v| *v == b'\n').collect::<Vec<_>>();
+    let new = new.split(|v| *v == b'\n').collect::<Vec<_>>();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut lines = old[..prefix]
+        .iter()
+        .map(|v| DiffLine::Same(v))
+        .collect::<Vec<_>>();
+
+    if old_middle.len().saturating_mul(new_middle.len()) > MAX_TABLE_SIZE {
+        lines.extend(old_middle.iter().map(|v| DiffLine::Removed(v)));
+        lines.extend(new_middle.iter().map(|v| DiffLine::Added(v)));
+    } else {
+        lines.extend(diff_middle(old_middle, new_middle));
+    }
+
+    lines.extend(old[old.len() - suffix..].iter().map(|v| DiffLine::Same(v)));
+    lines
+}
+
+fn diff_middle<'a>(old: &[&'a [u8]], new: &[&'a [u8]]) -> Vec<DiffLine<'a>> {
+    // lcs[i][j] is the common subsequence length of old[i..] and new[j..]
+    let width = new.len() + 1;
+    let mut lcs = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i * width + j] = if old[i] == new[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::with_capacity(old.len() + new.len());
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(DiffLine::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[(i + 1) * width + j] >= lcs[i * width + j + 1] {
+            lines.push(DiffLine::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old[i..].iter().map(|v| DiffLine::Removed(v)));
+    lines.extend(new[j..].iter().map(|v| DiffLine::Added(v)));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_lines_added_removed() {
+        assert_eq!(
+            diff_lines(b"a\nb\nc\nd", b"a\nc\nx\nd"),
+            vec![
+                DiffLine::Same(b"a"),
+                DiffLine::Removed(b"b"),
+                DiffLine::Same(b"c"),
+                DiffLine::Added(b"x"),
+                DiffLine::Same(b"d"),
+            ]
+        );
+        assert_eq!(
+            diff_lines(b"", b"a"),
+            vec![DiffLine::Removed(b""), DiffLine::Added(b"a")]
+        );
+        assert_eq!(
+            diff_lines(b"a\n", b"a\n"),
+            vec![DiffLine::Same(b"a"), DiffLine::Same(b"")]
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use std::{error, fmt, io};
+
+// failures worth telling apart, they travel inside anyhow errors and are matched on with
+// downcast_ref::<TipError>(). everything else stays an anyhow message
+#[derive(Debug)]
+pub enum TipError {
+    // /dev/tty could not be opened, e.g without a controlling terminal
+    TtyUnavailable(io::Error),
+    // reading or setting the terminal attributes, with what was being done
+    TermiosError(&'static str, io::Error),
+    // the program could not be started
+    SpawnFailed(String, io::Error),
+    // writing a frame to the terminal
+    RenderError(io::Error),
+}
+
+impl fmt::Display for TipError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TtyUnavailable(err) => write!(f, "no terminal to draw on: {}", err),
+            Self::TermiosError(doing, err) => write!(f, "failed {}: {}", doing, err),
+            Self::SpawnFailed(bin, err) => write!(f, "failed running {}: {}", bin, err),
+            Self::RenderError(err) => write!(f, "failed drawing to the terminal: {}", err),
+        }
+    }
+}
+
+impl error::Error for TipError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::TtyUnavailable(err)
+            | Self::TermiosError(_, err)
+            | Self::SpawnFailed(_, err)
+            | Self::RenderError(err) => Some(err),
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use anyhow::{Result, anyhow};
+use std::str;
+
+// inclusive, counting from 1 or from -1 at the end, none is open
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FieldRange {
+    start: Option<isize>,
+    end: Option<isize>,
+}
+
+// which whitespace separated fields of a record are shown, e.g `1,3..` or `-1`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fields(Vec<FieldRange>);
+
+impl str::FromStr for Fields {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parse_index = |v: &str| -> Result<Option<isize>> {
+            match v {
+                "" => Ok(None),
+                _ => match v.parse() {
+                    Ok(0) | Err(_) => Err(anyhow!("invalid field index: {}", v)),
+                    Ok(v) => Ok(Some(v)),
+                },
+            }
+        };
+
+        let mut ranges = Vec::new();
+        for range in s.split(',') {
+            ranges.push(match range.split_once("..") {
+                Some((start, end)) => FieldRange {
+                    start: parse_index(start)?,
+                    end: parse_index(end)?,
+                },
+                None => {
+                    let Some(index) = parse_index(range)? else {
+                        return Err(anyhow!("invalid field index: {}", range));
+                    };
+                    FieldRange {
+                        start: Some(index),
+                        end: Some(index),
+                    }
+                }
+            });
+        }
+        Ok(Self(ranges))
+    }
+}
+
+impl Fields {
+    // joined by a space, fields out of range are left out
+    pub fn select(&self, record: &[u8]) -> Vec<u8> {
+        let fields = record
+            .split(|v| v.is_ascii_whitespace())
+            .filter(|v| !v.is_empty())
+            .collect::<Vec<_>>();
+        let len = fields.len() as isize;
+        let resolve = |index: isize| if index > 0 { index - 1 } else { len + index };
+
+        let mut selected = Vec::new();
+        for range in &self.0 {
+            let start = range.start.map_or(0, resolve).max(0);
+            let end = range.end.map_or(len - 1, resolve).min(len - 1);
+            for field in start..=end {
+                if !selected.is_empty() {
+                    selected.push(b' ');
+                }
+                selected.extend_from_slice(fields[field as usize]);
+            }
+        }
+        selected
+    }
+}
+
+// every line shows only the selected fields
+pub fn render_fields(data: &[u8], fields: &Fields) -> Vec<u8> {
+    let mut rendered = Vec::with_capacity(data.len());
+    for (i, line) in data.split(|v| *v == b'\n').enumerate() {
+        if i > 0 {
+            rendered.push(b'\n');
+        }
+        rendered.extend(fields.select(line));
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_select() {
+        let select = |fields: &str, record: &str| {
+            let fields = fields.parse::<Fields>().unwrap();
+            String::from_utf8(fields.select(record.as_bytes())).unwrap()
+        };
+
+        assert_eq!(select("2", "  a  b\tc"), "b");
+        assert_eq!(select("1,3..", "a b c d"), "a c d");
+        assert_eq!(select("-1", "a b c"), "c");
+        assert_eq!(select("..-2", "a b c"), "a b");
+        assert_eq!(select("..", "a b c"), "a b c");
+        // out of range fields are left out
+        assert_eq!(select("2,5,-9", "a b"), "b");
+        assert_eq!(select("3..", "a b"), "");
+
+        for invalid in ["0", "", "x", "1..y", "1,"] {
+            assert!(invalid.parse::<Fields>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn render_fields_lines() {
+        let fields = "2".parse().unwrap();
+        assert_eq!(render_fields(b"1 web\n2 db\n\n", &fields), b"web\ndb\n\n");
+    }
+}
@@ -0,0 +1,81 @@
+use std::fmt::Write;
+
+const BYTES_PER_LINE: usize = 16;
+// share of bytes that can not be displayed as text past which output is treated as binary
+const MAX_BINARY_RATIO: f64 = 0.1;
+
+// guessed from a chunk of output, a nul byte alone is enough,
+// escape sequences and whitespace are text
+pub fn looks_binary(chunk: &[u8]) -> bool {
+    if chunk.contains(&0) {
+        return true;
+    }
+
+    let mut binary = 0;
+    let mut utf8_chunks = chunk.utf8_chunks().peekable();
+    while let Some(utf8_chunk) = utf8_chunks.next() {
+        binary += utf8_chunk
+            .valid()
+            .chars()
+            .filter(|ch| ch.is_control() && !matches!(ch, '\t' | '\n' | '\r' | '\x0c' | '\x1b'))
+            .count();
+        // the chunk may end in the middle of a character
+        if utf8_chunks.peek().is_some() {
+            binary += utf8_chunk.invalid().len();
+        }
+    }
+    binary as f64 > chunk.len() as f64 * MAX_BINARY_RATIO
+}
+
+// like `xxd`, the offset, 16 bytes in hex and the same bytes as ascii
+pub fn render_hex(data: &[u8]) -> Vec<u8> {
+    let mut rendered = String::with_capacity(data.len() * 4 + 16);
+    for (i, line) in data.chunks(BYTES_PER_LINE).enumerate() {
+        if i > 0 {
+            rendered.push('\n');
+        }
+        let _ = write!(rendered, "{:08x}:", i * BYTES_PER_LINE);
+        for column in 0..BYTES_PER_LINE {
+            if column % 2 == 0 {
+                rendered.push(' ');
+            }
+            match line.get(column) {
+                Some(v) => {
+                    let _ = write!(rendered, "{:02x}", v);
+                }
+                None => rendered.push_str("  "),
+            }
+        }
+        rendered.push_str("  ");
+        rendered.extend(line.iter().map(|v| match v {
+            0x20..=0x7e => *v as char,
+            _ => '.',
+        }));
+    }
+    rendered.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn looks_binary_chunks() {
+        assert!(!looks_binary(b"hello\tworld\r\n\x1b[31mred\x1b[0m\n"));
+        assert!(!looks_binary("žodis ąčę\n".as_bytes()));
+        // cut in the middle of a character
+        assert!(!looks_binary(&"ž".as_bytes()[..1]));
+        assert!(looks_binary(b"ELF\x00\x01"));
+        assert!(looks_binary(b"\x89PNG\r\n\x1a\n\xff\xd8\xff\xe0"));
+    }
+
+    #[test]
+    fn render_hex_lines() {
+        assert_eq!(
+            String::from_utf8(render_hex(b"0123456789abcdef\x00\xffz")).unwrap(),
+            "00000000: 3031 3233 3435 3637 3839 6162 6364 6566  0123456789abcdef\n\
+             00000010: 00ff 7a                                  ..z"
+        );
+        assert_eq!(render_hex(b""), b"");
+    }
+}
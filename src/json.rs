@@ -0,0 +1,352 @@
+use std::{fmt::Write, str};
+
+// enough of json to pretty print a line and look up fields in it. parsed here and not with
+// serde_json, which sorts object keys unless preserve_order pulls in indexmap and rewrites
+// numbers unless arbitrary_precision, while the preview shows the line as it was written
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    // kept as written, it is only displayed
+    Number(String),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    // none if the line is not a single json value
+    pub fn parse(input: &[u8]) -> Option<Self> {
+        let mut parser = Parser {
+            input,
+            index: 0,
+            depth: 0,
+        };
+        let value = parser.value()?;
+        parser.whitespace();
+        (parser.index == input.len()).then_some(value)
+    }
+
+    // dot separated path of object keys, e.g `metadata.name`
+    pub fn field(&self, path: &str) -> Option<&Self> {
+        path.split('.').try_fold(self, |value, key| match value {
+            Self::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        })
+    }
+
+    // strings without quotes, everything else as json
+    pub fn display(&self) -> String {
+        match self {
+            Self::String(v) => v.clone(),
+            _ => self.pretty(),
+        }
+    }
+
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.write_pretty(&mut out, 0);
+        out
+    }
+
+    fn write_pretty(&self, out: &mut String, indent: usize) {
+        match self {
+            Self::Null => out.push_str("null"),
+            Self::Bool(v) => out.push_str(if *v { "true" } else { "false" }),
+            Self::Number(v) => out.push_str(v),
+            Self::String(v) => write_string(out, v),
+            Self::Array(values) if values.is_empty() => out.push_str("[]"),
+            Self::Object(fields) if fields.is_empty() => out.push_str("{}"),
+            Self::Array(values) => {
+                out.push('[');
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    newline(out, indent + 1);
+                    value.write_pretty(out, indent + 1);
+                }
+                newline(out, indent);
+                out.push(']');
+            }
+            Self::Object(fields) => {
+                out.push('{');
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    newline(out, indent + 1);
+                    write_string(out, key);
+                    out.push_str(": ");
+                    value.write_pretty(out, indent + 1);
+                }
+                newline(out, indent);
+                out.push('}');
+            }
+        }
+    }
+}
+
+fn newline(out: &mut String, indent: usize) {
+    out.push('\n');
+    out.push_str(&"  ".repeat(indent));
+}
+
+fn write_string(out: &mut String, v: &str) {
+    out.push('"');
+    for ch in v.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ch if ch.is_control() => {
+                let _ = write!(out, "\\u{:04x}", ch as u32);
+            }
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+}
+
+// as deep as serde_json goes, a line of brackets must not run the stack out
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    input: &'a [u8],
+    index: usize,
+    // arrays and objects the parser is in
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.index).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let v = self.peek()?;
+        self.index += 1;
+        Some(v)
+    }
+
+    fn whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.index += 1;
+        }
+    }
+
+    fn literal(&mut self, literal: &[u8], value: Value) -> Option<Value> {
+        let end = self.index + literal.len();
+        if self.input.get(self.index..end)? != literal {
+            return None;
+        }
+        self.index = end;
+        Some(value)
+    }
+
+    fn value(&mut self) -> Option<Value> {
+        self.whitespace();
+        match self.peek()? {
+            b'n' => self.literal(b"null", Value::Null),
+            b't' => self.literal(b"true", Value::Bool(true)),
+            b'f' => self.literal(b"false", Value::Bool(false)),
+            b'"' => self.string().map(Value::String),
+            b'[' | b'{' if self.depth >= MAX_DEPTH => None,
+            b'[' => {
+                self.depth += 1;
+                let value = self.array();
+                self.depth -= 1;
+                value
+            }
+            b'{' => {
+                self.depth += 1;
+                let value = self.object();
+                self.depth -= 1;
+                value
+            }
+            b'-' | b'0'..=b'9' => self.number(),
+            _ => None,
+        }
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.index;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.index += 1;
+        }
+        self.index - start
+    }
+
+    fn number(&mut self) -> Option<Value> {
+        let start = self.index;
+        if self.peek() == Some(b'-') {
+            self.index += 1;
+        }
+        match self.peek()? {
+            b'0' => self.index += 1,
+            b'1'..=b'9' => {
+                self.digits();
+            }
+            _ => return None,
+        }
+        if self.peek() == Some(b'.') {
+            self.index += 1;
+            if self.digits() == 0 {
+                return None;
+            }
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            self.index += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.index += 1;
+            }
+            if self.digits() == 0 {
+                return None;
+            }
+        }
+        let number = str::from_utf8(&self.input[start..self.index]).ok()?;
+        Some(Value::Number(number.to_string()))
+    }
+
+    fn hex4(&mut self) -> Option<u32> {
+        let hex = self.input.get(self.index..self.index + 4)?;
+        let v = u32::from_str_radix(str::from_utf8(hex).ok()?, 16).ok()?;
+        self.index += 4;
+        Some(v)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.next()?;
+        let mut out = Vec::new();
+        loop {
+            match self.next()? {
+                b'"' => break,
+                b'\\' => {
+                    let ch = match self.next()? {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let high = self.hex4()?;
+                            let code = if (0xd800..0xdc00).contains(&high) {
+                                // surrogate pair
+                                if self.next()? != b'\\' || self.next()? != b'u' {
+                                    return None;
+                                }
+                                let low = self.hex4()?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return None;
+                                }
+                                0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                            } else {
+                                high
+                            };
+                            char::from_u32(code)?
+                        }
+                        _ => return None,
+                    };
+                    let mut buf = [0; 4];
+                    out.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+                }
+                0x00..=0x1f => return None,
+                v => out.push(v),
+            }
+        }
+        String::from_utf8(out).ok()
+    }
+
+    fn array(&mut self) -> Option<Value> {
+        self.next()?;
+        let mut values = Vec::new();
+        self.whitespace();
+        if self.peek() == Some(b']') {
+            self.index += 1;
+            return Some(Value::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            self.whitespace();
+            match self.next()? {
+                b',' => {}
+                b']' => return Some(Value::Array(values)),
+                _ => return None,
+            }
+        }
+    }
+
+    fn object(&mut self) -> Option<Value> {
+        self.next()?;
+        let mut fields = Vec::new();
+        self.whitespace();
+        if self.peek() == Some(b'}') {
+            self.index += 1;
+            return Some(Value::Object(fields));
+        }
+        loop {
+            self.whitespace();
+            if self.peek()? != b'"' {
+                return None;
+            }
+            let key = self.string()?;
+            self.whitespace();
+            if self.next()? != b':' {
+                return None;
+            }
+            fields.push((key, self.value()?));
+            self.whitespace();
+            match self.next()? {
+                b',' => {}
+                b'}' => return Some(Value::Object(fields)),
+                _ => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_pretty() {
+        let value =
+            Value::parse(br#" {"name":"a\"b\u00e9","n":[1,-2.5e3,true,null],"e":{}} "#).unwrap();
+        assert_eq!(
+            value.pretty(),
+            "{\n  \"name\": \"a\\\"bé\",\n  \"n\": [\n    1,\n    -2.5e3,\n    true,\n    null\n  ],\n  \"e\": {}\n}"
+        );
+
+        for malformed in [
+            &b"{"[..],
+            b"{\"a\" 1}",
+            b"[1,]",
+            b"01",
+            b"\"\\x\"",
+            b"1 2",
+            b"",
+        ] {
+            assert_eq!(Value::parse(malformed), None);
+        }
+
+        // too deep is not json to tip, rather than a stack overflow
+        let nested = |depth| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(Value::parse(nested(MAX_DEPTH).as_bytes()).is_some());
+        assert_eq!(Value::parse(nested(100_000).as_bytes()), None);
+    }
+
+    #[test]
+    fn field_path() {
+        let value = Value::parse(br#"{"metadata":{"name":"web","replicas":3}}"#).unwrap();
+        assert_eq!(value.field("metadata.name").unwrap().display(), "web");
+        assert_eq!(value.field("metadata.replicas").unwrap().display(), "3");
+        assert_eq!(value.field("metadata.missing"), None);
+        assert_eq!(value.field("metadata.name.deeper"), None);
+    }
+}
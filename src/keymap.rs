@@ -0,0 +1,446 @@
+use anyhow::{Result, anyhow};
+use std::{collections, fmt, str};
+
+use crate::terminal;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    Enter,
+    Tab,
+    BackTab,
+    Esc,
+    Backspace,
+    Left,
+    Right,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Insert,
+    CtrlLeft,
+    CtrlRight,
+    Ctrl(char),
+    Alt(char),
+    AltBackspace,
+    Char(char),
+}
+
+impl Key {
+    pub fn from_input(input: &terminal::TerminalInput) -> Option<Self> {
+        Some(match input {
+            terminal::TerminalInput::Ctrl(b'm') => Self::Enter,
+            terminal::TerminalInput::Ctrl(b'i') => Self::Tab,
+            terminal::TerminalInput::Ctrl(ch) => Self::Ctrl(*ch as char),
+            terminal::TerminalInput::Printable(ch) => Self::Char(*ch),
+            terminal::TerminalInput::Delete => Self::Backspace,
+            terminal::TerminalInput::Alt(ch) => Self::Alt(*ch),
+            terminal::TerminalInput::AltDelete => Self::AltBackspace,
+            terminal::TerminalInput::Paste(_) => return None,
+            terminal::TerminalInput::Escape(escape) => match escape {
+                terminal::TerminalEscape::Timeout => Self::Esc,
+                terminal::TerminalEscape::LeftArrow => Self::Left,
+                terminal::TerminalEscape::RightArrow => Self::Right,
+                terminal::TerminalEscape::UpArrow => Self::Up,
+                terminal::TerminalEscape::DownArrow => Self::Down,
+                terminal::TerminalEscape::PageUp => Self::PageUp,
+                terminal::TerminalEscape::PageDown => Self::PageDown,
+                terminal::TerminalEscape::Home => Self::Home,
+                terminal::TerminalEscape::End => Self::End,
+                terminal::TerminalEscape::Insert => Self::Insert,
+                terminal::TerminalEscape::BackTab => Self::BackTab,
+                terminal::TerminalEscape::CtrlLeftArrow => Self::CtrlLeft,
+                terminal::TerminalEscape::CtrlRightArrow => Self::CtrlRight,
+            },
+        })
+    }
+}
+
+impl Key {
+    // what reading the key from the terminal gives
+    pub fn to_input(self) -> terminal::TerminalInput {
+        match self {
+            Self::Enter => terminal::TerminalInput::Ctrl(b'm'),
+            Self::Tab => terminal::TerminalInput::Ctrl(b'i'),
+            Self::BackTab => terminal::TerminalInput::Escape(terminal::TerminalEscape::BackTab),
+            Self::Esc => terminal::TerminalInput::Escape(terminal::TerminalEscape::Timeout),
+            Self::Backspace => terminal::TerminalInput::Delete,
+            Self::Left => terminal::TerminalInput::Escape(terminal::TerminalEscape::LeftArrow),
+            Self::Right => terminal::TerminalInput::Escape(terminal::TerminalEscape::RightArrow),
+            Self::Up => terminal::TerminalInput::Escape(terminal::TerminalEscape::UpArrow),
+            Self::Down => terminal::TerminalInput::Escape(terminal::TerminalEscape::DownArrow),
+            Self::PageUp => terminal::TerminalInput::Escape(terminal::TerminalEscape::PageUp),
+            Self::PageDown => terminal::TerminalInput::Escape(terminal::TerminalEscape::PageDown),
+            Self::Home => terminal::TerminalInput::Escape(terminal::TerminalEscape::Home),
+            Self::End => terminal::TerminalInput::Escape(terminal::TerminalEscape::End),
+            Self::Insert => terminal::TerminalInput::Escape(terminal::TerminalEscape::Insert),
+            Self::CtrlLeft => {
+                terminal::TerminalInput::Escape(terminal::TerminalEscape::CtrlLeftArrow)
+            }
+            Self::CtrlRight => {
+                terminal::TerminalInput::Escape(terminal::TerminalEscape::CtrlRightArrow)
+            }
+            Self::Ctrl(ch) => terminal::TerminalInput::Ctrl(ch as u8),
+            Self::Alt(ch) => terminal::TerminalInput::Alt(ch),
+            Self::AltBackspace => terminal::TerminalInput::AltDelete,
+            Self::Char(ch) => terminal::TerminalInput::Printable(ch),
+        }
+    }
+}
+
+// keys written out, e.g `foo^W<left><enter>`: text is typed as is, <key> is a key named
+// like in --bind and ^x is ctrl-x, <lt> types a < and ^^ a ^
+pub fn parse_keys(s: &str) -> Result<Vec<Key>> {
+    let mut keys = Vec::new();
+    let mut chars = s.chars();
+    while let Some(ch) = chars.next() {
+        keys.push(match ch {
+            '<' => {
+                let name = chars.by_ref().take_while(|v| *v != '>').collect::<String>();
+                match name.to_lowercase().as_str() {
+                    "lt" => Key::Char('<'),
+                    name => name.parse()?,
+                }
+            }
+            '^' => match chars.next() {
+                Some('^') => Key::Char('^'),
+                Some(ch) => format!("ctrl-{}", ch.to_ascii_lowercase()).parse()?,
+                None => return Err(anyhow!("expected a key after ^")),
+            },
+            ch => Key::Char(ch),
+        });
+    }
+    Ok(keys)
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Enter => write!(f, "enter"),
+            Self::Tab => write!(f, "tab"),
+            Self::BackTab => write!(f, "btab"),
+            Self::Esc => write!(f, "esc"),
+            Self::Backspace => write!(f, "backspace"),
+            Self::Left => write!(f, "left"),
+            Self::Right => write!(f, "right"),
+            Self::Up => write!(f, "up"),
+            Self::Down => write!(f, "down"),
+            Self::PageUp => write!(f, "pgup"),
+            Self::PageDown => write!(f, "pgdn"),
+            Self::Home => write!(f, "home"),
+            Self::End => write!(f, "end"),
+            Self::Insert => write!(f, "insert"),
+            Self::CtrlLeft => write!(f, "ctrl-left"),
+            Self::CtrlRight => write!(f, "ctrl-right"),
+            Self::Ctrl(ch) => write!(f, "ctrl-{}", ch),
+            Self::Alt(ch) => write!(f, "alt-{}", ch),
+            Self::AltBackspace => write!(f, "alt-backspace"),
+            Self::Char(ch) => write!(f, "{}", ch),
+        }
+    }
+}
+
+impl str::FromStr for Key {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "enter" => Self::Enter,
+            "tab" => Self::Tab,
+            "btab" | "shift-tab" => Self::BackTab,
+            "esc" => Self::Esc,
+            "backspace" | "bspace" => Self::Backspace,
+            "left" => Self::Left,
+            "right" => Self::Right,
+            "up" => Self::Up,
+            "down" => Self::Down,
+            "pgup" => Self::PageUp,
+            "pgdn" => Self::PageDown,
+            "home" => Self::Home,
+            "end" => Self::End,
+            "insert" => Self::Insert,
+            "ctrl-left" => Self::CtrlLeft,
+            "ctrl-right" => Self::CtrlRight,
+            "alt-backspace" | "alt-bspace" => Self::AltBackspace,
+            // ctrl-m and ctrl-i are the same bytes as enter and tab
+            "ctrl-m" => Self::Enter,
+            "ctrl-i" => Self::Tab,
+            _ => {
+                let mut chars = s.chars();
+                match (s.strip_prefix("ctrl-"), chars.next(), chars.next()) {
+                    (Some(ch), _, _) if ch.len() == 1 && ch.as_bytes()[0].is_ascii_lowercase() => {
+                        Self::Ctrl(ch.as_bytes()[0] as char)
+                    }
+                    // alt only combines with ascii, that is what read_escape produces
+                    _ if s.starts_with("alt-")
+                        && s.len() == "alt-".len() + 1
+                        && s.as_bytes()[4].is_ascii_graphic() =>
+                    {
+                        Self::Alt(s.as_bytes()[4] as char)
+                    }
+                    (None, Some(ch), None) => Self::Char(ch),
+                    _ => return Err(anyhow!("unknown key: {}", s)),
+                }
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Accept,
+    Abort,
+    // clears the query, aborts once it is empty
+    ClearOrAbort,
+    // does nothing, unbinds a default key
+    Ignore,
+    ToggleCase,
+    Run,
+    // kills the current run, nothing runs again until the next query
+    Cancel,
+    ScrollUp,
+    ScrollDown,
+    PageUp,
+    PageDown,
+    Paste,
+    // a literal tab in the query
+    InsertTab,
+    // the word before the cursor from words in the output
+    Complete,
+    ToggleFollow,
+    // reads the terminal size again and redraws
+    Refresh,
+    // shows or hides the column numbers above the data
+    ToggleRuler,
+    // runs the command of the keymap at the index, written as execute(<cmd>)
+    Execute(usize),
+    // shows the output of the next --tab, the first after the last
+    NextTab,
+}
+
+impl str::FromStr for Action {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "accept" => Self::Accept,
+            "abort" => Self::Abort,
+            "clear-or-abort" => Self::ClearOrAbort,
+            "ignore" => Self::Ignore,
+            "toggle-case" => Self::ToggleCase,
+            "run" => Self::Run,
+            "cancel" => Self::Cancel,
+            "scroll-up" => Self::ScrollUp,
+            "scroll-down" => Self::ScrollDown,
+            "page-up" => Self::PageUp,
+            "page-down" => Self::PageDown,
+            "paste" => Self::Paste,
+            "insert-tab" => Self::InsertTab,
+            "complete" => Self::Complete,
+            "toggle-follow" => Self::ToggleFollow,
+            "refresh" => Self::Refresh,
+            "toggle-ruler" => Self::ToggleRuler,
+            "next-tab" => Self::NextTab,
+            _ => return Err(anyhow!("unknown action: {}", s)),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: collections::HashMap<Key, Action>,
+    // shell commands of the execute actions
+    commands: Vec<String>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            bindings: collections::HashMap::from([
+                (Key::Enter, Action::Accept),
+                (Key::Ctrl('c'), Action::Abort),
+                (Key::Esc, Action::Abort),
+                (Key::Ctrl('s'), Action::ToggleCase),
+                (Key::Ctrl('r'), Action::Run),
+                (Key::Ctrl('x'), Action::Cancel),
+                (Key::Up, Action::ScrollUp),
+                (Key::BackTab, Action::ScrollUp),
+                (Key::Down, Action::ScrollDown),
+                (Key::PageUp, Action::PageUp),
+                (Key::PageDown, Action::PageDown),
+                (Key::Tab, Action::Complete),
+                (Key::Ctrl('f'), Action::ToggleFollow),
+                (Key::Ctrl('l'), Action::Refresh),
+                (Key::Alt('r'), Action::ToggleRuler),
+                (Key::Alt('t'), Action::NextTab),
+            ]),
+            commands: Vec::new(),
+        }
+    }
+}
+
+impl Keymap {
+    pub fn insert(&mut self, key: Key, action: Action) {
+        self.bindings.insert(key, action);
+    }
+
+    // comma separated list of key:action, e.g `ctrl-y:accept,esc:abort`
+    pub fn bind(&mut self, spec: &str) -> Result<()> {
+        for binding in split_bindings(spec) {
+            let Some((key, action)) = binding.split_once(':') else {
+                return Err(anyhow!("invalid binding, expected key:action: {}", binding));
+            };
+            let action = match action
+                .strip_prefix("execute(")
+                .and_then(|v| v.strip_suffix(')'))
+            {
+                Some(command) => {
+                    self.commands.push(command.to_string());
+                    Action::Execute(self.commands.len() - 1)
+                }
+                None => action.parse()?,
+            };
+            self.insert(key.parse()?, action);
+        }
+        Ok(())
+    }
+
+    pub fn command(&self, index: usize) -> &str {
+        &self.commands[index]
+    }
+
+    pub fn action(&self, input: &terminal::TerminalInput) -> Option<Action> {
+        Key::from_input(input).and_then(|key| self.bindings.get(&key).copied())
+    }
+}
+
+// a comma after the key separates bindings, unless within the parentheses of execute(a, b)
+fn split_bindings(spec: &str) -> Vec<&str> {
+    let mut bindings = Vec::new();
+    let mut start = 0;
+    let mut in_action = false;
+    let mut depth = 0usize;
+    for (i, ch) in spec.char_indices() {
+        match ch {
+            ':' if !in_action && i > start => in_action = true,
+            '(' if in_action => depth += 1,
+            ')' if in_action => depth = depth.saturating_sub(1),
+            ',' if in_action && depth == 0 => {
+                bindings.push(&spec[start..i]);
+                start = i + 1;
+                in_action = false;
+            }
+            _ => {}
+        }
+    }
+    bindings.push(&spec[start..]);
+    bindings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_keys_notation() {
+        assert_eq!(
+            parse_keys("a^W<Left><ctrl-right><lt>^^<enter>").unwrap(),
+            vec![
+                Key::Char('a'),
+                Key::Ctrl('w'),
+                Key::Left,
+                Key::CtrlRight,
+                Key::Char('<'),
+                Key::Char('^'),
+                Key::Enter,
+            ]
+        );
+        // what the keymap reads back is the same key
+        for key in parse_keys("x^R<tab><btab><esc><alt-d>").unwrap() {
+            assert_eq!(Key::from_input(&key.to_input()), Some(key));
+        }
+
+        assert!(parse_keys("<nope>").is_err());
+        assert!(parse_keys("^").is_err());
+        assert!(parse_keys("^1").is_err());
+    }
+
+    #[test]
+    fn keymap_bind_execute() {
+        let mut keymap = Keymap::default();
+        keymap
+            .bind("ctrl-g:execute(git add \"$1\"),,:execute(printf '%s,%s' (a) \"$1\"),y:accept")
+            .unwrap();
+
+        let Some(Action::Execute(index)) = keymap.action(&terminal::TerminalInput::Ctrl(b'g'))
+        else {
+            panic!("ctrl-g is not an execute action");
+        };
+        assert_eq!(keymap.command(index), "git add \"$1\"");
+        let Some(Action::Execute(index)) = keymap.action(&terminal::TerminalInput::Printable(','))
+        else {
+            panic!(", is not an execute action");
+        };
+        assert_eq!(keymap.command(index), "printf '%s,%s' (a) \"$1\"");
+        assert_eq!(
+            keymap.action(&terminal::TerminalInput::Printable('y')),
+            Some(Action::Accept)
+        );
+
+        assert!(keymap.bind("x:execute").is_err());
+    }
+
+    #[test]
+    fn keymap_bind() {
+        let mut keymap = Keymap::default();
+        keymap.bind("ctrl-y:accept,x:toggle-case").unwrap();
+
+        assert_eq!(
+            keymap.action(&terminal::TerminalInput::Ctrl(b'y')),
+            Some(Action::Accept)
+        );
+        assert_eq!(
+            keymap.action(&terminal::TerminalInput::Printable('x')),
+            Some(Action::ToggleCase)
+        );
+        assert_eq!(
+            keymap.action(&terminal::TerminalInput::Ctrl(b'm')),
+            Some(Action::Accept)
+        );
+        assert_eq!(
+            keymap.action(&terminal::TerminalInput::Printable('y')),
+            None
+        );
+
+        // tab arrives as ctrl-i
+        assert_eq!(
+            keymap.action(&terminal::TerminalInput::Ctrl(b'i')),
+            Some(Action::Complete)
+        );
+        keymap.bind("tab:insert-tab").unwrap();
+        assert_eq!(
+            keymap.action(&terminal::TerminalInput::Ctrl(b'i')),
+            Some(Action::InsertTab)
+        );
+
+        assert!(keymap.bind("ctrl-y").is_err());
+        assert!(keymap.bind("ctrl-y:explode").is_err());
+        assert!(keymap.bind("hyper-y:accept").is_err());
+    }
+
+    #[test]
+    fn key_display_roundtrip() {
+        for name in [
+            "enter",
+            "esc",
+            "ctrl-left",
+            "ctrl-y",
+            "alt-d",
+            "alt-backspace",
+            "x",
+        ] {
+            assert_eq!(name.parse::<Key>().unwrap().to_string(), name);
+        }
+    }
+}
@@ -1,15 +1,26 @@
 use anyhow::{Context, Result, anyhow};
 use std::{
-    env,
+    env, fs,
     io::{self, Read, Write},
-    process, sync, thread,
+    iter, mem,
+    os::fd::{AsRawFd, OwnedFd},
+    process, sync, thread, time,
 };
+use terminal::ComponentData;
 
 mod child;
+mod config;
+mod data;
+mod diff;
+mod error;
+mod fields;
+mod hex;
+mod json;
+mod keymap;
+mod output;
+mod state;
 mod terminal;
 
-static HELP: &str = "Usage: tip <program> [ARGUMENTS]";
-
 macro_rules! onerr {
     ($e:expr, $s:block) => {{
         match $e {
@@ -19,21 +30,175 @@ macro_rules! onerr {
     }};
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
+enum WordCase {
+    Upper,
+    Lower,
+    // first letter upper, the rest lower
+    Capitalize,
+}
+
 struct UiPrompt {
     cursor_index: usize,
     query: Vec<char>,
     tx: sync::mpsc::Sender<String>,
     cmd: String,
+    keymap: keymap::Keymap,
+    overwrite: bool,
+    max_query_length: usize,
+    word_mode: config::WordMode,
+    // alt-<digits> typed so far, repeats the next edit
+    numeric_arg: Option<usize>,
+    completer: Option<Completer>,
+    // pressing complete again cycles through these
+    completion: Option<Completion>,
+    // the query is not edited while reviewing
+    review: sync::Arc<sync::Mutex<Option<Review>>>,
+}
+
+// words for completing the query come from the output
+// completes from the output of the tab shown
+struct Completer {
+    tabs: Tabs,
+}
+
+struct Completion {
+    candidates: Vec<String>,
+    // of the word being completed in the query
+    start: usize,
+    // none until the first cycle
+    index: Option<usize>,
+}
+
+// listed in the footer when completion is ambiguous
+const MAX_LISTED_CANDIDATES: usize = 20;
+
+// unique whitespace separated tokens of the output starting with prefix, sorted
+fn completion_candidates(data: &[u8], prefix: &str) -> Vec<String> {
+    let data = String::from_utf8_lossy(data);
+    let candidates = data
+        .split_whitespace()
+        .filter(|v| v.starts_with(prefix) && v.len() > prefix.len())
+        .collect::<std::collections::BTreeSet<_>>();
+    candidates.into_iter().map(|v| v.to_string()).collect()
+}
+
+fn common_prefix(candidates: &[String]) -> String {
+    let Some((first, rest)) = candidates.split_first() else {
+        return String::new();
+    };
+    let mut prefix = first.as_str();
+    for candidate in rest {
+        let len = prefix
+            .char_indices()
+            .zip(candidate.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((i, ch), _)| i + ch.len_utf8());
+        prefix = &prefix[..len];
+    }
+    prefix.to_string()
 }
 
 impl UiPrompt {
-    fn new(tx: sync::mpsc::Sender<String>, cmd: String) -> Self {
+    fn new(
+        tx: sync::mpsc::Sender<String>,
+        cmd: String,
+        keymap: keymap::Keymap,
+        max_query_length: usize,
+    ) -> Self {
         Self {
             cmd,
             cursor_index: 0,
             query: Vec::new(),
             tx,
+            keymap,
+            overwrite: false,
+            max_query_length,
+            word_mode: config::WordMode::Word,
+            numeric_arg: None,
+            completer: None,
+            completion: None,
+            review: sync::Arc::new(sync::Mutex::new(None)),
+        }
+    }
+
+    // typed before it started, nothing is sent for it
+    fn set_query(&mut self, query: &str) {
+        self.query = query.chars().take(self.max_query_length).collect();
+        self.cursor_index = self.query.len();
+    }
+
+    fn set_word_mode(&mut self, word_mode: config::WordMode) {
+        self.word_mode = word_mode;
+    }
+
+    fn set_completer(&mut self, completer: Completer) {
+        self.completer = Some(completer);
+    }
+
+    fn set_review(&mut self, review: sync::Arc<sync::Mutex<Option<Review>>>) {
+        self.review = review;
+    }
+
+    fn set_completion_list(&self, list: Option<String>) {
+        if let Some(completer) = &self.completer {
+            completer.tabs.active().status.lock().unwrap().completions = list;
+        }
+    }
+
+    fn replace_word(&mut self, start: usize, word: &str) {
+        self.query.splice(start..self.cursor_index, word.chars());
+        self.cursor_index = start + word.chars().count();
+        self.query.truncate(self.max_query_length);
+        self.cursor_index = self.cursor_index.min(self.query.len());
+    }
+
+    // the word before the cursor to the longest prefix all candidates share,
+    // once there it cycles through them
+    fn complete(&mut self) {
+        if let Some(completion) = &mut self.completion {
+            let index = completion
+                .index
+                .map_or(0, |v| (v + 1) % completion.candidates.len());
+            completion.index = Some(index);
+            let (start, word) = (completion.start, completion.candidates[index].clone());
+            self.replace_word(start, &word);
+            return;
+        }
+
+        let Some(completer) = &self.completer else {
+            return;
+        };
+        let start = self.query[..self.cursor_index]
+            .iter()
+            .rposition(|v| v.is_whitespace())
+            .map_or(0, |v| v + 1);
+        let prefix = self.query[start..self.cursor_index]
+            .iter()
+            .collect::<String>();
+        let candidates = completion_candidates(&completer.tabs.active().data.load(), &prefix);
+
+        match candidates.len() {
+            0 => {}
+            1 => self.replace_word(start, &candidates[0]),
+            _ => {
+                self.replace_word(start, &common_prefix(&candidates));
+                let mut list = candidates
+                    .iter()
+                    .take(MAX_LISTED_CANDIDATES)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if candidates.len() > MAX_LISTED_CANDIDATES {
+                    list.push("…".to_string());
+                }
+                self.set_completion_list(Some(list.join(" ")));
+                self.completion = Some(Completion {
+                    candidates,
+                    start,
+                    index: None,
+                });
+            }
         }
     }
 
@@ -47,8 +212,12 @@ impl UiPrompt {
         self.cursor_index = cursor_index.max(0).min(self.query.len() as isize) as usize;
     }
 
-    fn is_word_char(ch: char) -> bool {
-        ch.is_alphanumeric()
+    // every word motion and deletion goes by it
+    fn is_word_char(&self, ch: char) -> bool {
+        match self.word_mode {
+            config::WordMode::Word => ch.is_alphanumeric(),
+            config::WordMode::Whitespace => !ch.is_whitespace(),
+        }
     }
 
     fn move_cursor_word(&mut self, range: impl Iterator<Item = usize>) {
@@ -59,7 +228,7 @@ impl UiPrompt {
             let Some(ch) = self.query.get(i) else {
                 continue;
             };
-            let is_word = Self::is_word_char(*ch);
+            let is_word = self.is_word_char(*ch);
             seen_normal = if seen_normal { true } else { is_word };
 
             if !is_word && seen_normal {
@@ -76,68 +245,309 @@ impl UiPrompt {
         self.move_cursor_word((0..=self.cursor_index).rev());
     }
 
-    fn add_character(&mut self, ch: char) -> Result<()> {
-        self.query.insert(self.cursor_index, ch);
-        self.cursor_index += 1;
+    fn delete_word_backward(&mut self) {
+        let end = self.cursor_index;
+        self.move_cursor_word_backward();
+        // the word boundary lands on the separator, which stays
+        if self.cursor_index < end && !self.is_word_char(self.query[self.cursor_index]) {
+            self.cursor_index += 1;
+        }
+        if self.cursor_index == end {
+            return;
+        }
 
-        self.tx.send(self.get_string())?;
-        Ok(())
+        self.query.drain(self.cursor_index..end);
+    }
+
+    fn delete_word_forward(&mut self) {
+        let start = self.cursor_index;
+        self.move_cursor_word_forward();
+        let end = self.cursor_index;
+        self.cursor_index = start;
+        if start == end {
+            return;
+        }
+
+        self.query.drain(start..end);
+    }
+
+    // from the cursor to the end of the word, the cursor ends up after the word,
+    // full case mappings are used so a character can become several, e.g ß to SS
+    fn change_word_case(&mut self, case: WordCase) {
+        let start = self.cursor_index;
+        self.move_cursor_word_forward();
+        let end = self.cursor_index;
+        if start == end {
+            return;
+        }
+
+        let mut seen_word = false;
+        let mut changed = Vec::new();
+        for ch in self.query[start..end].iter().copied() {
+            let upper = match case {
+                WordCase::Upper => true,
+                WordCase::Lower => false,
+                WordCase::Capitalize => !seen_word && self.is_word_char(ch),
+            };
+            seen_word = seen_word || self.is_word_char(ch);
+
+            if upper {
+                changed.extend(ch.to_uppercase());
+            } else {
+                changed.extend(ch.to_lowercase());
+            }
+        }
+
+        self.cursor_index = start + changed.len();
+        self.query.splice(start..end, changed);
+    }
+
+    // like readline, at the end of the query the last two characters are swapped
+    fn transpose_characters(&mut self) {
+        if self.query.len() < 2 || self.cursor_index == 0 {
+            return;
+        }
+
+        let index = self.cursor_index.min(self.query.len() - 1);
+        self.query.swap(index - 1, index);
+        self.cursor_index = index + 1;
+    }
+
+    fn add_character(&mut self, ch: char) {
+        // at the end of the query there is nothing to overwrite
+        if self.overwrite && self.cursor_index < self.query.len() {
+            self.query[self.cursor_index] = ch;
+        } else if self.query.len() < self.max_query_length {
+            self.query.insert(self.cursor_index, ch);
+        } else {
+            // long pastes are cut off instead of building an argument that fails to exec
+            return;
+        }
+        self.cursor_index += 1;
     }
 
-    fn delete_character(&mut self) -> Result<()> {
+    fn delete_character(&mut self) {
         if self.cursor_index == 0 {
-            return Ok(());
+            return;
         }
 
         self.query.remove(self.cursor_index - 1);
         self.cursor_index -= 1;
-
-        self.tx.send(self.get_string())?;
-        Ok(())
     }
 }
 
+const TAB_GLYPH: char = '⇥';
+
 impl terminal::ComponentPrompt for UiPrompt {
     fn render(&self) -> terminal::ComponentPromptOut {
+        let mut query = format!("[{}]> ", self.cmd).chars().collect::<Vec<_>>();
+        let prompt_len = query.len();
+        let cursor_index = prompt_len + self.cursor_index;
+        // a tab would jump to the next tab stop, so it is shown as a single column
+        query.extend(self.query.iter().map(|ch| match ch {
+            '\t' => TAB_GLYPH,
+            ch => *ch,
+        }));
+
         terminal::ComponentPromptOut {
-            cursor_index: self.cursor_index + self.cmd.len() + 4,
-            query: format!("[{}]> {}", self.cmd, self.query.iter().collect::<String>())
-                .chars()
-                .collect(),
+            query,
+            prompt_len,
+            cursor_index,
+            overwrite: self.overwrite,
+            read_only: self.review.lock().unwrap().is_some(),
         }
     }
 
     fn input(&mut self, input: &terminal::TerminalInput) -> Result<()> {
+        let action = self.keymap.action(input);
+        if action != Some(keymap::Action::Complete) && self.completion.take().is_some() {
+            self.set_completion_list(None);
+        }
+
+        // bound keys are actions, not edits
+        match action {
+            Some(keymap::Action::Run) => {
+                self.tx.send(self.get_string())?;
+                return Ok(());
+            }
+            Some(keymap::Action::Complete) => {
+                self.numeric_arg = None;
+                let previous = self.query.clone();
+                self.complete();
+                if self.query != previous {
+                    self.tx.send(self.get_string())?;
+                }
+                return Ok(());
+            }
+            Some(keymap::Action::InsertTab) => {
+                self.numeric_arg = None;
+                let previous = self.query.clone();
+                self.add_character('\t');
+                if self.query != previous {
+                    self.tx.send(self.get_string())?;
+                }
+                return Ok(());
+            }
+            // with an empty query it aborts before getting here
+            Some(keymap::Action::ClearOrAbort) if !self.query.is_empty() => {
+                self.numeric_arg = None;
+                self.query.clear();
+                self.cursor_index = 0;
+                self.tx.send(self.get_string())?;
+                return Ok(());
+            }
+            Some(_) => {
+                self.numeric_arg = None;
+                return Ok(());
+            }
+            None => {}
+        }
+
+        // like readline, no edit repeats further than the query can ever reach
+        if let terminal::TerminalInput::Alt(ch @ '0'..='9') = input {
+            let digit = ch.to_digit(10).unwrap() as usize;
+            let numeric_arg = self.numeric_arg.unwrap_or(0).saturating_mul(10) + digit;
+            self.numeric_arg = Some(numeric_arg.min(self.max_query_length));
+            return Ok(());
+        }
+        let count = self.numeric_arg.take().unwrap_or(1);
+
+        let previous = self.query.clone();
+        for _ in 0..count {
+            match input {
+                terminal::TerminalInput::Delete => self.delete_character(),
+                terminal::TerminalInput::Ctrl(b'w') | terminal::TerminalInput::AltDelete => {
+                    self.delete_word_backward()
+                }
+                terminal::TerminalInput::Alt('d') => self.delete_word_forward(),
+                terminal::TerminalInput::Escape(terminal::TerminalEscape::LeftArrow) => {
+                    self.move_cursor(-1)
+                }
+                terminal::TerminalInput::Escape(terminal::TerminalEscape::RightArrow) => {
+                    self.move_cursor(1)
+                }
+                terminal::TerminalInput::Escape(terminal::TerminalEscape::CtrlLeftArrow) => {
+                    self.move_cursor_word_backward()
+                }
+                terminal::TerminalInput::Escape(terminal::TerminalEscape::CtrlRightArrow) => {
+                    self.move_cursor_word_forward()
+                }
+                _ => break,
+            }
+        }
+
         match input {
-            terminal::TerminalInput::Delete => {
-                self.delete_character()?;
-            }
-            terminal::TerminalInput::Printable(ch) => {
-                self.add_character(*ch as char)?;
-            }
-            terminal::TerminalInput::Escape(escape) => match escape {
-                terminal::TerminalEscape::LeftArrow => self.move_cursor(-1),
-                terminal::TerminalEscape::RightArrow => self.move_cursor(1),
-                terminal::TerminalEscape::CtrlLeftArrow => self.move_cursor_word_backward(),
-                terminal::TerminalEscape::CtrlRightArrow => self.move_cursor_word_forward(),
-                _ => {}
-            },
+            terminal::TerminalInput::Printable(ch) => self.add_character(*ch),
+            // the query is a single line
+            terminal::TerminalInput::Paste(text) => text
+                .chars()
+                .map(|ch| if ch == '\n' || ch == '\t' { ' ' } else { ch })
+                .filter(|ch| !ch.is_control())
+                .for_each(|ch| self.add_character(ch)),
+            terminal::TerminalInput::Ctrl(b't') => self.transpose_characters(),
+            terminal::TerminalInput::Alt('u') => self.change_word_case(WordCase::Upper),
+            terminal::TerminalInput::Alt('l') => self.change_word_case(WordCase::Lower),
+            terminal::TerminalInput::Alt('c') => self.change_word_case(WordCase::Capitalize),
+            terminal::TerminalInput::Escape(terminal::TerminalEscape::Insert) => {
+                self.overwrite = !self.overwrite
+            }
+            terminal::TerminalInput::Escape(terminal::TerminalEscape::Home) => {
+                self.cursor_index = 0
+            }
+            terminal::TerminalInput::Escape(terminal::TerminalEscape::End) => {
+                self.cursor_index = self.query.len()
+            }
             _ => {}
         }
 
+        // a repeated edit runs the program once
+        if self.query != previous {
+            self.tx.send(self.get_string())?;
+        }
         Ok(())
     }
 }
 
+// the program first, then its arguments with the query
+fn build_argv(cmd: &str, args: &[String], query: &str, mode: config::QueryMode) -> Vec<String> {
+    let mut argv = vec![cmd.to_string()];
+    match mode {
+        config::QueryMode::Append => {
+            argv.extend_from_slice(args);
+            if !query.is_empty() {
+                argv.push(query.to_string());
+            }
+        }
+        config::QueryMode::Prepend => {
+            if !query.is_empty() {
+                argv.push(query.to_string());
+            }
+            argv.extend_from_slice(args);
+        }
+        config::QueryMode::Placeholder => {
+            argv.extend(args.iter().map(|v| v.replace("{}", query)));
+        }
+        config::QueryMode::Stdin(_) => argv.extend_from_slice(args),
+    }
+    argv
+}
+
+// what the program reads, the query goes in with --query-stdin
+fn build_stdin(
+    input: &Option<sync::Arc<Vec<u8>>>,
+    query: &str,
+    mode: config::QueryMode,
+) -> Option<sync::Arc<Vec<u8>>> {
+    let config::QueryMode::Stdin(order) = mode else {
+        return input.clone();
+    };
+
+    let input = input.as_deref().map_or(&[][..], |v| v.as_slice());
+    let mut stdin = Vec::with_capacity(input.len() + query.len() + 2);
+    if order == config::StdinOrder::Last {
+        stdin.extend_from_slice(input);
+        if !input.is_empty() && !input.ends_with(b"\n") {
+            stdin.push(b'\n');
+        }
+    }
+    stdin.extend_from_slice(query.as_bytes());
+    stdin.push(b'\n');
+    if order == config::StdinOrder::First {
+        stdin.extend_from_slice(input);
+    }
+    Some(sync::Arc::new(stdin))
+}
+
+// single quoted unless it only has characters no shell treats specially
+fn shell_quote(arg: &str) -> String {
+    let plain = |ch: char| ch.is_ascii_alphanumeric() || "_@%+=:,./-".contains(ch);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+fn print_command(argv: &[String], format: config::PrintCommand) -> String {
+    match format {
+        config::PrintCommand::Lines => argv.iter().map(|v| format!("{}\n", v)).collect(),
+        config::PrintCommand::Shell => {
+            let quoted = argv.iter().map(|v| shell_quote(v)).collect::<Vec<_>>();
+            format!("{}\n", quoted.join(" "))
+        }
+    }
+}
+
 fn create_command(
     cmd: &str,
     args: &[String],
     query: &str,
+    mode: config::QueryMode,
     input: &Option<sync::Arc<Vec<u8>>>,
 ) -> process::Command {
-    let mut command = process::Command::new(cmd);
+    let argv = build_argv(cmd, args, query, mode);
+    let mut command = process::Command::new(&argv[0]);
     command
-        .args(args)
+        .args(&argv[1..])
         .stdin(if input.is_some() {
             process::Stdio::piped()
         } else {
@@ -145,262 +555,4240 @@ fn create_command(
         })
         .stdout(process::Stdio::piped())
         .stderr(process::Stdio::piped());
+    command
+}
 
-    if !query.is_empty() {
-        command.arg(&query);
+// linux limits a single argument to 32 pages besides the total
+const MAX_ARG_STRLEN: usize = 32 * 4096;
+// ARG_MAX before linux 2.6.23, when sysconf does not know it
+const DEFAULT_ARG_MAX: usize = 128 * 1024;
+
+fn system_arg_max() -> usize {
+    match unsafe { libc::sysconf(libc::_SC_ARG_MAX) } {
+        v if v > 0 => v as usize,
+        _ => DEFAULT_ARG_MAX,
     }
+}
 
-    command
+// why exec would fail with E2BIG, every string counts with its nul and pointer
+fn arg_length_error(argv: &[String], arg_max: usize) -> Option<String> {
+    let size = |v: usize| v + 1 + size_of::<usize>();
+    if let Some(arg) = argv.iter().find(|v| v.len() + 1 > MAX_ARG_STRLEN) {
+        return Some(format!(
+            "not run, an argument is too long: {} bytes, at most {}",
+            arg.len(),
+            MAX_ARG_STRLEN - 1
+        ));
+    }
+    let length = argv.iter().map(|v| size(v.len())).sum::<usize>()
+        + env::vars_os()
+            .map(|(k, v)| size(k.len() + 1 + v.len()))
+            .sum::<usize>();
+    (length > arg_max).then(|| {
+        format!(
+            "not run, the command is too long: {} bytes, at most {}",
+            length, arg_max
+        )
+    })
 }
 
-struct UiWaitingProcess {
-    data: sync::Arc<sync::Mutex<Vec<u8>>>,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RunStatus {
+    // when the run was spawned
+    Running(time::Instant),
+    // spawn to the end of its output
+    Finished(time::Duration),
+    // spawn to the cancel action
+    Cancelled(time::Duration),
 }
 
-impl UiWaitingProcess {
-    fn new(
-        cmd: String,
-        args: Vec<String>,
-        input: Option<sync::Arc<Vec<u8>>>,
-        redraw_tx: sync::mpsc::SyncSender<()>,
-        query_rx: sync::mpsc::Receiver<String>,
-    ) -> Self {
-        let data = sync::Arc::new(sync::Mutex::new(Vec::new()));
-        Self::start(cmd, args, input, redraw_tx, query_rx, data.clone());
-        Self { data }
+// keeping the newest output in view, like `tail -f`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Follow {
+    #[default]
+    Off,
+    Following,
+    // scrolled up, follows again once back at the bottom
+    Paused,
+    // new output with --initial-scroll bottom, kept at the bottom until scrolled
+    Bottom,
+}
+
+impl Follow {
+    // for the output of a new query, an explicit --follow is left as it is
+    fn reset(self, initial_scroll: config::InitialScroll) -> Self {
+        match (self, initial_scroll) {
+            (Self::Off | Self::Bottom, config::InitialScroll::Top) => Self::Off,
+            (Self::Off | Self::Bottom, config::InitialScroll::Bottom) => Self::Bottom,
+            (v, _) => v,
+        }
     }
 
-    fn start(
-        cmd: String,
-        args: Vec<String>,
-        input: Option<sync::Arc<Vec<u8>>>,
-        redraw_tx: sync::mpsc::SyncSender<()>,
-        query_rx: sync::mpsc::Receiver<String>,
-        data: sync::Arc<sync::Mutex<Vec<u8>>>,
-    ) -> thread::JoinHandle<()> {
-        thread::spawn({
-            move || {
-                let mut _child: Option<_> = None;
-                let mut query = String::new();
-                loop {
-                    let mut command = create_command(&cmd, &args, &query, &input);
-                    _child = Some(child::DroppableChild::new(onerr!(command.spawn(), {
-                        continue;
-                    })));
-                    let Some(child) = &mut _child else {
-                        unreachable!();
-                    };
+    // whether the view is pinned to the bottom
+    fn pinned(self) -> bool {
+        matches!(self, Self::Following | Self::Bottom)
+    }
+}
 
-                    let stdin = child.0.stdin.take();
-                    let stdout = child.0.stdout.take().unwrap();
-                    let stderr = child.0.stderr.take().unwrap();
+#[derive(Debug, Default)]
+struct UiStatus {
+    regex_error: Option<String>,
+    // the last query was not run as its command is longer than allowed
+    arg_error: Option<String>,
+    // the last query was not run as it is shorter than --min-query-len
+    short_query: Option<String>,
+    // the --query-hook skipped the last query
+    hook_error: Option<String>,
+    // how the last action command exited, and when
+    action: Option<(String, time::Instant)>,
+    run: Option<RunStatus>,
+    review: bool,
+    follow: Follow,
+    // candidates of an ambiguous completion
+    completions: Option<String>,
+}
 
-                    onerr!(Self::reset_data(data.clone(), redraw_tx.clone()), {
-                        return;
-                    });
+impl UiStatus {
+    // a superseded run can finish after the next one started, it must not overwrite it
+    fn finish_run(&mut self, started_at: time::Instant) {
+        if self.run == Some(RunStatus::Running(started_at)) {
+            self.run = Some(RunStatus::Finished(started_at.elapsed()));
+        }
+    }
+}
 
-                    thread::spawn({
-                        let input = input.clone();
-                        let data = data.clone();
-                        let redraw_tx = redraw_tx.clone();
-                        move || {
-                            let write_handle = input.map(|input| {
-                                thread::spawn(move || {
-                                    let _ = stdin.unwrap().write_all(&input);
-                                })
-                            });
+// how long the footer says how an action command exited
+const ACTION_SHOWN: time::Duration = time::Duration::from_secs(3);
 
-                            let _ =
-                                Self::read_child_stream(stdout, data.clone(), redraw_tx.clone());
-                            let _ = Self::read_child_stream(stderr, data, redraw_tx);
+// the command of an execute action, with the query as $1. it gets the terminal,
+// not the stdin and stdout of tip, which may be pipes
+fn execute_action(cmd: &str, query: &str) -> String {
+    let tty = || {
+        fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+    };
+    let status = tty().and_then(|stdin| {
+        process::Command::new("sh")
+            .args(["-c", cmd, "sh", query])
+            .stdin(stdin)
+            .stdout(tty()?)
+            .stderr(tty()?)
+            .status()
+    });
+    match status {
+        Ok(status) => match status.code() {
+            Some(code) => format!("action exited with {}", code),
+            None => format!("action {}", status),
+        },
+        Err(err) => format!("action failed: {}", err),
+    }
+}
 
-                            if let Some(write_handle) = write_handle {
-                                write_handle.join().unwrap();
-                            }
-                        }
-                    });
+// how often the elapsed time of a running program is redrawn
+const RUNNING_TICK: time::Duration = time::Duration::from_millis(100);
 
-                    query = onerr!(query_rx.recv(), { return });
-                }
-            }
-        })
+fn format_elapsed(elapsed: time::Duration) -> String {
+    if elapsed < time::Duration::from_secs(1) {
+        format!("{}ms", elapsed.as_millis())
+    } else {
+        format!("{:.1}s", elapsed.as_secs_f64())
     }
+}
 
-    fn read_child_stream(
-        mut stream: impl Read,
-        data: sync::Arc<sync::Mutex<Vec<u8>>>,
-        redraw_tx: sync::mpsc::SyncSender<()>,
-    ) -> Result<()> {
-        let mut has_read = false;
-        loop {
-            let mut buf = [0; 1 << 13];
-            let size = stream.read(&mut buf)?;
-            if size == 0 {
-                break;
-            }
-            if !has_read {
-                has_read = true;
-                Self::reset_data(data.clone(), redraw_tx.clone())?;
+// fixed lines above the separator
+struct UiHeader {
+    text: Option<String>,
+    // output of --header-command once it is done
+    output: sync::Arc<sync::Mutex<Vec<u8>>>,
+    tabs: Tabs,
+}
+
+impl terminal::ComponentHeader for UiHeader {
+    // the tab bar first, then the text, then the output
+    fn render(&self) -> terminal::ComponentHeaderOut {
+        let output = self.output.lock().unwrap();
+        let mut header = self.tabs.bar().unwrap_or_default();
+        for part in [self.text.as_deref().unwrap_or_default().as_bytes(), &output] {
+            if !header.is_empty() && !part.is_empty() {
+                header.push(b'\n');
             }
-            Self::push_to_data(data.clone(), &buf[..size], redraw_tx.clone())?
+            header.extend_from_slice(part);
         }
-
-        Ok(())
+        terminal::ComponentHeaderOut(header)
     }
+}
 
-    fn reset_data(
-        data: sync::Arc<sync::Mutex<Vec<u8>>>,
-        redraw_tx: sync::mpsc::SyncSender<()>,
-    ) -> Result<()> {
-        *data.lock().unwrap() = Vec::new();
-        redraw_tx.send(())?;
-        Ok(())
+// the query to run, or why there is none. it runs in the worker before each run, one at a
+// time, so a slow hook holds the run up
+fn run_query_hook(cmd: &str, query: &str) -> Result<String, String> {
+    let output = process::Command::new("sh")
+        .args(["-c", cmd, "sh", query])
+        .stdin(process::Stdio::null())
+        .stderr(process::Stdio::null())
+        .output()
+        .map_err(|err| format!("query hook failed: {}", err))?;
+    if !output.status.success() {
+        return Err(match output.status.code() {
+            Some(code) => format!("query hook exited with {}", code),
+            None => format!("query hook {}", output.status),
+        });
     }
 
-    fn push_to_data(
-        data: sync::Arc<sync::Mutex<Vec<u8>>>,
-        buf: &[u8],
-        redraw_tx: sync::mpsc::SyncSender<()>,
-    ) -> Result<()> {
-        {
-            let mut data = data.lock().unwrap();
-            buf.iter().for_each(|v| data.push(*v));
-            // mutex gets dropped here
-        }
-        redraw_tx.send(())?;
-        Ok(())
+    let printed = output.stdout.strip_suffix(b"\n").unwrap_or(&output.stdout);
+    match printed.is_empty() {
+        true => Ok(query.to_string()),
+        false => Ok(String::from_utf8_lossy(printed).into_owned()),
     }
 }
 
-impl terminal::ComponentData for UiWaitingProcess {
-    fn render(&self) -> terminal::ComponentDataOut {
-        let data = self.data.lock().unwrap().clone();
-        terminal::ComponentDataOut(data)
+// what accepting and --review run, the same query the worker would
+fn hooked_query(config: &config::Config, query: &str) -> Result<String, String> {
+    match &config.query_hook {
+        Some(hook) => run_query_hook(hook, query),
+        None => Ok(query.to_string()),
     }
 }
 
-fn pipe_cmd(
-    cmd: &str,
-    args: &[String],
-    query: &str,
-    input: Option<sync::Arc<Vec<u8>>>,
-) -> Result<process::ExitStatus> {
-    let mut command = create_command(cmd, args, query, &input);
-    let mut child = command.spawn()?;
-
-    let stdin_handle = input.map(|input| {
-        thread::spawn({
-            let mut stdin = child.stdin.take().unwrap();
-            move || stdin.write_all(&input)
-        })
-    });
+// stdout then stderr, so a failing command still says something
+fn run_header_command(cmd: &str) -> Vec<u8> {
+    let output = match process::Command::new("sh")
+        .args(["-c", cmd])
+        .stdin(process::Stdio::null())
+        .output()
+    {
+        Ok(v) => v,
+        Err(err) => return format_spawn_error("sh", &err, false),
+    };
 
-    let stdout_handle = thread::spawn({
-        let mut stdout = child.stdout.take().unwrap();
-        move || io::copy(&mut stdout, &mut io::stdout())
-    });
+    let mut header = output.stdout;
+    header.extend(output.stderr);
+    while header.last() == Some(&b'\n') {
+        header.pop();
+    }
+    header
+}
 
-    let stderr_handle = thread::spawn({
-        let mut stderr = child.stderr.take().unwrap();
-        move || io::copy(&mut stderr, &mut io::stderr())
-    });
+// the status of the tab shown
+struct UiFooter {
+    tabs: Tabs,
+}
 
-    let exit_status = child.wait()?;
+impl UiFooter {
+    fn new(tabs: Tabs) -> Self {
+        Self { tabs }
+    }
+}
 
-    if let Some(stdin_handle) = stdin_handle {
-        let _ = stdin_handle.join().unwrap();
+impl terminal::ComponentFooter for UiFooter {
+    fn render(&self) -> terminal::ComponentFooterOut {
+        let status = self.tabs.active().status.lock().unwrap();
+        let mut parts = Vec::new();
+        if let Some(completions) = &status.completions {
+            parts.push(completions.clone());
+        }
+        match status.follow {
+            Follow::Off | Follow::Bottom => {}
+            Follow::Following => parts.push("following".to_string()),
+            Follow::Paused => parts.push("follow paused".to_string()),
+        }
+        if status.review {
+            parts.push("review: enter accepts, esc edits the query".to_string());
+        }
+        if let Some(regex_error) = &status.regex_error {
+            parts.push(format!("invalid regex: {}", regex_error));
+        }
+        if let Some(short_query) = &status.short_query {
+            parts.push(short_query.clone());
+        }
+        if let Some(arg_error) = &status.arg_error {
+            parts.push(arg_error.clone());
+        }
+        if let Some(hook_error) = &status.hook_error {
+            parts.push(hook_error.clone());
+        }
+        if let Some((action, at)) = &status.action
+            && at.elapsed() < ACTION_SHOWN
+        {
+            parts.push(action.clone());
+        }
+        match status.run {
+            Some(RunStatus::Running(started_at)) => {
+                parts.push(format!("running… {}", format_elapsed(started_at.elapsed())))
+            }
+            Some(RunStatus::Finished(elapsed)) => {
+                parts.push(format!("ran in {}", format_elapsed(elapsed)))
+            }
+            Some(RunStatus::Cancelled(elapsed)) => {
+                parts.push(format!("cancelled after {}, idle", format_elapsed(elapsed)))
+            }
+            None => {}
+        }
+        terminal::ComponentFooterOut(parts.join("  "))
     }
-    let _ = stdout_handle.join().unwrap();
-    let _ = stderr_handle.join().unwrap();
+}
 
-    Ok(exit_status)
+// regex errors span multiple lines pointing at the query,
+// the footer only has room for the reason
+fn format_regex_error(err: &regex::Error) -> String {
+    let err = err.to_string();
+    let reason = err.lines().last().unwrap_or_default();
+    reason.strip_prefix("error: ").unwrap_or(reason).to_string()
 }
 
-fn main_err() -> Result<i32> {
-    let stdin_input = {
-        let mut stdin_input = None;
-        if !terminal::isatty(libc::STDIN_FILENO) {
-            let mut v = Vec::new();
-            io::stdin()
-                .read_to_end(&mut v)
-                .with_context(|| "failed reading stdin")?;
-            stdin_input = Some(sync::Arc::new(v));
-        }
-        stdin_input
+// shown in place of the output of a program that could not be started
+fn format_spawn_error(bin: &str, err: &io::Error, color: bool) -> Vec<u8> {
+    let reason = match err.kind() {
+        io::ErrorKind::NotFound => "command not found".to_string(),
+        io::ErrorKind::PermissionDenied => "permission denied".to_string(),
+        _ => err.to_string(),
     };
+    format_error_line(&format!("tip: {}: {}", bin, reason), color)
+}
 
-    let Some(bin) = env::args().skip(1).next() else {
-        return Err(anyhow!(HELP));
-    };
-    let bin_args = env::args().skip(2).collect::<Vec<_>>();
-
-    // todo: figure out how to do this sync
-    // there is a deadlock between query_rx, query_tx, redraw_tx
-    let (query_tx, query_rx) = sync::mpsc::channel();
-    let (redraw_tx, redraw_rx) = sync::mpsc::sync_channel(0);
-
-    let mut ui_waiting_process = UiWaitingProcess::new(
-        bin.clone(),
-        bin_args.clone(),
-        stdin_input.clone(),
-        redraw_tx.clone(),
-        query_rx,
-    );
-    let cmd = {
-        let mut cmd = vec![bin.clone()];
-        if !bin_args.is_empty() {
-            cmd.push(bin_args.join(" "))
-        };
-        cmd.join(" ")
-    };
-    let mut ui_prompt = UiPrompt::new(query_tx, cmd.clone());
-    let mut pressed_enter = false;
+fn format_error_line(line: &str, color: bool) -> Vec<u8> {
+    match color {
+        true => format!("\x1b[31m{}\x1b[0m\n", line),
+        false => format!("{}\n", line),
+    }
+    .into_bytes()
+}
 
-    terminal::TerminalRenderer::new(
-        vec![
-            terminal::Component::Prompt(&mut ui_prompt),
-            terminal::Component::Data(&mut ui_waiting_process),
-        ],
-        redraw_rx,
-    )?
-    .start(|input| match input {
-        terminal::TerminalInput::Ctrl(ch) => match ch {
-            // enter
-            b'm' => {
-                pressed_enter = true;
-                true
-            }
-            // c-c
-            b'c' => true,
-            _ => false,
-        },
-        terminal::TerminalInput::Escape(esc) => match esc {
-            terminal::TerminalEscape::Timeout => true,
-            _ => false,
-        },
-        _ => false,
-    })?;
+// a terminal stderr is the one tip draws on
+fn inherits_stderr(uncaptured: config::Uncaptured, stderr_tty: bool) -> bool {
+    uncaptured == config::Uncaptured::Inherit && !stderr_tty
+}
+
+// transforms are shell snippets, so they can be pipelines themselves
+fn create_transform_command(transform: &str) -> process::Command {
+    let mut command = process::Command::new("sh");
+    command
+        .args(["-c", transform])
+        .stdin(process::Stdio::piped())
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::piped());
+    child::own_group(&mut command);
+    command
+}
+
+struct UiWaitingProcess {
+    data: sync::Arc<data::SharedData>,
+    query: sync::Arc<sync::Mutex<String>>,
+    case: terminal::CaseMode,
+    highlight: bool,
+    filter: Option<terminal::FilterMode>,
+    keymap: keymap::Keymap,
+    // reset on every new query
+    viewport: sync::Arc<sync::Mutex<Viewport>>,
+    running: RunningChildren,
+    cursor_line: bool,
+    // more records than this show where the top line is, like the cursor line does
+    paging_threshold: usize,
+    auto_select: bool,
+    // false until the first run with --lazy
+    started: sync::Arc<sync::atomic::AtomicBool>,
+    diff: bool,
+    diff_cache: sync::Mutex<Option<DiffCache>>,
+    delimiter: Option<Vec<u8>>,
+    records_cache: sync::Mutex<Option<RenderCache>>,
+    nth: Option<fields::Fields>,
+    nth_cache: sync::Mutex<Option<RenderCache>>,
+    tac: bool,
+    json: bool,
+    json_key: Option<String>,
+    json_key_cache: sync::Mutex<Option<RenderCache>>,
+    status: sync::Arc<sync::Mutex<UiStatus>>,
+    // shown instead of the live output while reviewing
+    review: sync::Arc<sync::Mutex<Option<Review>>>,
+    // decided on the first output of every run with --binary auto
+    hex: sync::Arc<sync::atomic::AtomicBool>,
+    hex_cache: sync::Mutex<Option<RenderCache>>,
+    preview: Option<UiPreview>,
+    // what --pty sizes the terminal of the program to
+    pane: PaneSize,
+}
+
+// the focus has to stay on a record this long before its preview runs
+const PREVIEW_DEBOUNCE: time::Duration = time::Duration::from_millis(100);
 
-    if pressed_enter {
-        let ui_prompt_string = ui_prompt.get_string();
-        if !ui_prompt_string.is_empty() {
-            eprintln!("{} '{}'", &cmd, &ui_prompt_string);
+static PREVIEW_LOADING: &[u8] = b"\x1b[2mloading preview\xe2\x80\xa6\x1b[22m";
+
+#[derive(Default)]
+struct Preview {
+    // what the output is for, bumped with every new record
+    record: Option<Vec<u8>>,
+    run: u64,
+    output: Vec<u8>,
+    running: bool,
+    // of the run, killed once the focus moves on
+    child: Option<child::DroppableChild>,
+}
+
+// output of --preview for the focused record, only the record the focus settled on runs
+struct UiPreview {
+    preview: sync::Arc<sync::Mutex<Preview>>,
+    request_tx: sync::mpsc::Sender<(u64, Vec<u8>)>,
+}
+
+// appends to the run it was created for, once another one started the command gets a broken pipe
+#[derive(Clone)]
+struct PreviewWriter {
+    preview: sync::Arc<sync::Mutex<Preview>>,
+    run: u64,
+    redraw_tx: terminal::RedrawSender,
+}
+
+impl Write for PreviewWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut preview = self.preview.lock().unwrap();
+        if preview.run != self.run {
+            return Err(io::ErrorKind::BrokenPipe.into());
         }
+        preview.output.extend_from_slice(buf);
+        drop(preview);
+        let _ = self.redraw_tx.send();
+        Ok(buf.len())
+    }
 
-        return Ok(pipe_cmd(&bin, &bin_args, &ui_prompt_string, stdin_input)?
-            .code()
-            .unwrap_or(2));
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
     }
+}
 
-    Ok(0)
+impl UiPreview {
+    fn new(cmd: String, kill_grace: time::Duration, redraw_tx: terminal::RedrawSender) -> Self {
+        let preview = sync::Arc::new(sync::Mutex::new(Preview::default()));
+        let (request_tx, request_rx) = sync::mpsc::channel::<(u64, Vec<u8>)>();
+        thread::spawn({
+            let preview = preview.clone();
+            move || {
+                while let Ok(mut request) = request_rx.recv() {
+                    // moving on within the debounce replaces the request
+                    loop {
+                        match request_rx.recv_timeout(PREVIEW_DEBOUNCE) {
+                            Ok(next) => request = next,
+                            Err(sync::mpsc::RecvTimeoutError::Timeout) => break,
+                            Err(sync::mpsc::RecvTimeoutError::Disconnected) => return,
+                        }
+                    }
+                    let (run, record) = request;
+                    let mut writer = PreviewWriter {
+                        preview: preview.clone(),
+                        run,
+                        redraw_tx: redraw_tx.clone(),
+                    };
+                    // the record is $1 of the shell command
+                    let args = ["-c".to_string(), cmd.clone(), "sh".to_string()];
+                    let mut command = create_command(
+                        "sh",
+                        &args,
+                        &String::from_utf8_lossy(&record),
+                        config::QueryMode::Append,
+                        &None,
+                    );
+                    let copies = match child::own_group(&mut command).spawn() {
+                        Ok(v) => {
+                            let mut child = child::DroppableChild::with_grace(v, kill_grace);
+                            let mut stdout = child.0.stdout.take().unwrap();
+                            let mut stderr = child.0.stderr.take().unwrap();
+                            let copies = [
+                                thread::spawn({
+                                    let mut writer = writer.clone();
+                                    move || io::copy(&mut stdout, &mut writer)
+                                }),
+                                thread::spawn(move || io::copy(&mut stderr, &mut writer)),
+                            ];
+                            let mut preview = preview.lock().unwrap();
+                            match preview.run == run {
+                                true => preview.child = Some(child),
+                                false => child.kill_in_background(),
+                            }
+                            copies.into_iter().collect()
+                        }
+                        Err(err) => {
+                            let _ = writer.write_all(&format_spawn_error("sh", &err, false));
+                            Vec::new()
+                        }
+                    };
+                    // until it is done or killed for the next record
+                    for copy in copies {
+                        let _ = copy.join();
+                    }
+
+                    let mut preview = preview.lock().unwrap();
+                    if preview.run == run {
+                        preview.running = false;
+                        if let Some(child) = preview.child.take() {
+                            child.kill_in_background();
+                        }
+                        drop(preview);
+                        let _ = redraw_tx.send();
+                    }
+                }
+            }
+        });
+        Self {
+            preview,
+            request_tx,
+        }
+    }
+
+    // what to show for the record, a placeholder until its command writes something
+    fn show(&self, record: Option<&[u8]>) -> Vec<u8> {
+        let mut preview = self.preview.lock().unwrap();
+        if preview.record.as_deref() != record {
+            preview.record = record.map(<[u8]>::to_vec);
+            preview.run += 1;
+            if let Some(child) = preview.child.take() {
+                child.kill_in_background();
+            }
+            preview.output.clear();
+            preview.running = record.is_some();
+            if let Some(record) = record {
+                let _ = self.request_tx.send((preview.run, record.to_vec()));
+            }
+        }
+        match preview.running && preview.output.is_empty() {
+            true => PREVIEW_LOADING.to_vec(),
+            false => preview.output.clone(),
+        }
+    }
 }
 
-fn main() {
-    match main_err() {
-        Ok(v) => process::exit(v),
-        Err(err) => {
-            eprintln!("{}", err);
-            process::exit(1);
+// output of the accept command with --review
+struct Review {
+    // stdout and stderr as shown
+    output: sync::Arc<sync::Mutex<Vec<u8>>>,
+    // only stdout, what accepting the review prints
+    accepted: sync::Arc<sync::Mutex<Vec<u8>>>,
+    // killed once the review is left
+    child: Option<child::DroppableChild>,
+    // where editing was, restored when going back to it
+    editing: Viewport,
+}
+
+// appends to the review it was created for, writes after it is left fail with a broken pipe
+#[derive(Clone)]
+struct ReviewWriter {
+    review: sync::Arc<sync::Mutex<Option<Review>>>,
+    output: sync::Arc<sync::Mutex<Vec<u8>>>,
+    // none for stderr
+    accepted: Option<sync::Arc<sync::Mutex<Vec<u8>>>>,
+    redraw_tx: terminal::RedrawSender,
+}
+
+impl Write for ReviewWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &*self.review.lock().unwrap() {
+            Some(review) if sync::Arc::ptr_eq(&review.output, &self.output) => {}
+            _ => return Err(io::ErrorKind::BrokenPipe.into()),
+        }
+        self.output.lock().unwrap().extend_from_slice(buf);
+        if let Some(accepted) = &self.accepted {
+            accepted.lock().unwrap().extend_from_slice(buf);
+        }
+        let _ = self.redraw_tx.send();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct Reviewer {
+    review: sync::Arc<sync::Mutex<Option<Review>>>,
+    viewport: sync::Arc<sync::Mutex<Viewport>>,
+    status: sync::Arc<sync::Mutex<UiStatus>>,
+    redraw_tx: terminal::RedrawSender,
+}
+
+impl Reviewer {
+    fn reviewing(&self) -> bool {
+        self.review.lock().unwrap().is_some()
+    }
+
+    // what accepting the review prints, the program does not run again
+    fn reviewed(&self) -> Option<sync::Arc<Vec<u8>>> {
+        let review = self.review.lock().unwrap();
+        let accepted = review.as_ref()?.accepted.lock().unwrap().clone();
+        Some(sync::Arc::new(accepted))
+    }
+
+    // runs what accepting would in the background, its stderr included,
+    // the captured output is shown as is
+    fn start(
+        &self,
+        config: &config::Config,
+        query: &str,
+        input: Option<sync::Arc<Vec<u8>>>,
+        captured: Option<sync::Arc<Vec<u8>>>,
+    ) {
+        let output = sync::Arc::new(sync::Mutex::new(Vec::new()));
+        let accepted = sync::Arc::new(sync::Mutex::new(Vec::new()));
+        let editing = std::mem::take(&mut *self.viewport.lock().unwrap());
+        *self.review.lock().unwrap() = Some(Review {
+            output: output.clone(),
+            accepted: accepted.clone(),
+            child: None,
+            editing,
+        });
+        self.status.lock().unwrap().review = true;
+
+        let mut writer = ReviewWriter {
+            review: self.review.clone(),
+            output,
+            accepted: Some(accepted),
+            redraw_tx: self.redraw_tx.clone(),
+        };
+        if let Some(captured) = captured {
+            let _ = writer.write_all(&captured);
+            return;
+        }
+
+        // skipped by the hook, nothing is accepted
+        let query = match hooked_query(config, query) {
+            Ok(v) => v,
+            Err(err) => {
+                writer.accepted = None;
+                let _ = writer.write_all(&format_error_line(&err, config.color));
+                return;
+            }
+        };
+        let query = query.as_str();
+        let input = build_stdin(&input, query, config.query_mode);
+        let mut command = create_command(
+            &config.bin,
+            &config.bin_args,
+            query,
+            config.query_mode,
+            &input,
+        );
+        let mut child = match child::own_group(&mut command).spawn() {
+            Ok(v) => child::DroppableChild::with_grace(v, config.kill_grace),
+            Err(err) => {
+                writer.accepted = None;
+                let _ = writer.write_all(&format_spawn_error(&config.bin, &err, config.color));
+                return;
+            }
+        };
+        if let (Some(mut stdin), Some(input)) = (child.0.stdin.take(), input) {
+            thread::spawn(move || stdin.write_all(&input));
+        }
+        let mut stdout = child.0.stdout.take().unwrap();
+        let mut stderr = child.0.stderr.take().unwrap();
+        thread::spawn({
+            let mut writer = writer.clone();
+            move || io::copy(&mut stdout, &mut writer)
+        });
+        writer.accepted = None;
+        thread::spawn(move || io::copy(&mut stderr, &mut writer));
+
+        if let Some(review) = &mut *self.review.lock().unwrap() {
+            review.child = Some(child);
+        }
+    }
+
+    fn stop(&self) {
+        let review = self.review.lock().unwrap().take();
+        self.status.lock().unwrap().review = false;
+        if let Some(review) = review {
+            *self.viewport.lock().unwrap() = review.editing;
+            // its command is killed outside the lock, the writers take it
+            if let Some(child) = review.child {
+                child.kill_in_background();
+            }
+        }
+    }
+}
+
+// the same snapshot is rendered once
+struct RenderCache {
+    // of the output it was rendered from
+    version: u64,
+    rendered: sync::Arc<Vec<u8>>,
+}
+
+// the diff of the same two snapshots is rendered once
+struct DiffCache {
+    previous: sync::Arc<Vec<u8>>,
+    current: sync::Arc<Vec<u8>>,
+    rendered: sync::Arc<Vec<u8>>,
+}
+
+// every line that is a json value shows the field instead, others stay as they are
+fn render_json_key(data: &[u8], path: &str) -> Vec<u8> {
+    let mut rendered = Vec::with_capacity(data.len());
+    for (i, line) in data.split(|v| *v == b'\n').enumerate() {
+        if i > 0 {
+            rendered.push(b'\n');
+        }
+        match json::Value::parse(line)
+            .as_ref()
+            .and_then(|v| v.field(path))
+        {
+            Some(field) => rendered.extend_from_slice(field.display().as_bytes()),
+            None => rendered.extend_from_slice(line),
+        }
+    }
+    rendered
+}
+
+// records separated by the delimiter go on lines of their own, newlines within them show as
+// spaces, a trailing newline still ends the output
+fn render_records(data: &[u8], delimiter: &[u8]) -> Vec<u8> {
+    let (mut rest, end) = match data.strip_suffix(b"\n") {
+        Some(v) => (v, &b"\n"[..]),
+        None => (data, &b""[..]),
+    };
+    let mut rendered = Vec::with_capacity(data.len());
+    loop {
+        let at = rest.windows(delimiter.len()).position(|v| v == delimiter);
+        let record = &rest[..at.unwrap_or(rest.len())];
+        rendered.extend(record.iter().map(|v| if *v == b'\n' { b' ' } else { *v }));
+
+        let Some(at) = at else { break };
+        rendered.push(b'\n');
+        rest = &rest[at + delimiter.len()..];
+    }
+    rendered.extend_from_slice(end);
+    rendered
+}
+
+// a trailing newline ends the output, not the last record
+fn split_records<'a>(data: &'a [u8], delimiter: &[u8]) -> Vec<&'a [u8]> {
+    let mut rest = data.strip_suffix(b"\n").unwrap_or(data);
+    let mut records = Vec::new();
+    while let Some(at) = rest.windows(delimiter.len()).position(|v| v == delimiter) {
+        records.push(&rest[..at]);
+        rest = &rest[at + delimiter.len()..];
+    }
+    records.push(rest);
+    records
+}
+
+// like tac, a trailing newline still ends the output
+fn reverse_records(data: &[u8], delimiter: &[u8]) -> Vec<u8> {
+    let end = match data.ends_with(b"\n") {
+        true => &b"\n"[..],
+        false => &b""[..],
+    };
+    let records = split_records(data, delimiter);
+
+    let mut reversed = Vec::with_capacity(data.len());
+    for (i, record) in records.iter().rev().enumerate() {
+        if i > 0 {
+            reversed.extend_from_slice(delimiter);
+        }
+        reversed.extend_from_slice(record);
+    }
+    reversed.extend_from_slice(end);
+    reversed
+}
+
+// malformed lines are previewed raw
+fn render_json_preview(line: &[u8]) -> Vec<u8> {
+    match json::Value::parse(line) {
+        Some(value) => value.pretty().into_bytes(),
+        None => line.to_vec(),
+    }
+}
+
+fn render_diff(previous: &[u8], current: &[u8]) -> Vec<u8> {
+    let mut rendered = Vec::with_capacity(current.len());
+    for (i, line) in diff::diff_lines(previous, current).into_iter().enumerate() {
+        if i > 0 {
+            rendered.push(b'\n');
+        }
+        match line {
+            diff::DiffLine::Same(line) => rendered.extend_from_slice(line),
+            diff::DiffLine::Added(line) => {
+                rendered.extend_from_slice(b"\x1b[32m");
+                rendered.extend_from_slice(line);
+                rendered.extend_from_slice(b"\x1b[39m");
+            }
+            diff::DiffLine::Removed(line) => {
+                rendered.extend_from_slice(b"\x1b[31m");
+                rendered.extend_from_slice(line);
+                rendered.extend_from_slice(b"\x1b[39m");
+            }
+        }
+    }
+    rendered
+}
+
+// state the worker shares with the renderer
+// the program of the current run and its --transform, killed once dropped
+type RunningChildren = sync::Arc<sync::Mutex<Vec<child::DroppableChild>>>;
+
+struct UiWaitingProcessShared {
+    query: sync::Arc<sync::Mutex<String>>,
+    status: sync::Arc<sync::Mutex<UiStatus>>,
+    viewport: sync::Arc<sync::Mutex<Viewport>>,
+    running: RunningChildren,
+    started: sync::Arc<sync::atomic::AtomicBool>,
+    hex: sync::Arc<sync::atomic::AtomicBool>,
+    pane: PaneSize,
+}
+
+// rows and columns of the data pane, none before it is first drawn
+type PaneSize = sync::Arc<sync::Mutex<Option<(usize, usize)>>>;
+
+static LAZY_HINT: &[u8] = b"type a query or press run to start";
+
+// positions are in displayed lines, lines hidden by a filter do not count
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Viewport {
+    // lines skipped from the top
+    scroll: usize,
+    // the highlighted line with --cursor-line
+    cursor: usize,
+}
+
+impl Viewport {
+    // the cursor is kept within the output and the scroll follows it to keep it visible
+    fn move_cursor(self, lines: isize, height: usize, line_count: usize) -> Self {
+        let cursor = self
+            .cursor
+            .saturating_add_signed(lines)
+            .min(line_count.saturating_sub(1));
+        let scroll = if cursor < self.scroll {
+            cursor
+        } else if cursor >= self.scroll + height {
+            cursor + 1 - height
+        } else {
+            self.scroll
+        };
+        Self { scroll, cursor }
+    }
+
+    // where following keeps it
+    fn at_bottom(self, cursor_line: bool, height: usize, line_count: usize) -> bool {
+        match cursor_line {
+            true => self.cursor + 1 >= line_count,
+            false => self.scroll >= line_count.saturating_sub(height),
         }
+    }
+}
+
+// an explicit scroll takes over from the initial scroll and pauses following
+fn scroll_viewport(
+    mut viewport: Viewport,
+    mut follow: Follow,
+    cursor_line: bool,
+    lines: isize,
+    height: usize,
+    line_count: usize,
+) -> (Viewport, Follow) {
+    let max_scroll = line_count.saturating_sub(height);
+    // scrolling goes from where the view was pinned
+    if follow.pinned() {
+        viewport = Viewport {
+            scroll: max_scroll,
+            cursor: line_count.saturating_sub(1),
+        };
+    }
+
+    if cursor_line {
+        viewport = viewport.move_cursor(lines, height, line_count);
+    } else {
+        viewport.scroll = viewport.scroll.saturating_add_signed(lines).min(max_scroll);
+    }
+
+    follow = match follow {
+        Follow::Off | Follow::Bottom => Follow::Off,
+        _ if viewport.at_bottom(cursor_line, height, line_count) => Follow::Following,
+        _ => Follow::Paused,
     };
+    (viewport, follow)
+}
+
+// a re-run keeps the scroll position if its output still reaches it
+fn preserved_scroll(scroll: usize, line_count: usize) -> usize {
+    if scroll < line_count { scroll } else { 0 }
+}
+
+// records and what is displayed of them are line for line, hidden lines do not count
+fn focused_record<'a>(
+    records: impl IntoIterator<Item = &'a [u8]>,
+    displayed: &[u8],
+    focused: usize,
+    hidden: impl Fn(&[u8]) -> bool,
+) -> Option<&'a [u8]> {
+    records
+        .into_iter()
+        .zip(displayed.split(|v| *v == b'\n'))
+        .filter(|(_, displayed)| !hidden(displayed))
+        .nth(focused)
+        .map(|(record, _)| record)
+}
+
+fn line_count(data: &[u8]) -> usize {
+    data.split(|v| *v == b'\n').count()
+}
+
+impl UiWaitingProcess {
+    // left out of the pane by --filter hide
+    fn hidden(&self, matcher: Option<&terminal::Matcher>, line: &[u8]) -> bool {
+        self.filter == Some(terminal::FilterMode::Hide)
+            && matcher.is_some_and(|matcher| !matcher.is_match(line))
+    }
+
+    fn new(
+        config: sync::Arc<config::Config>,
+        input: Option<sync::Arc<Vec<u8>>>,
+        status: sync::Arc<sync::Mutex<UiStatus>>,
+        redraw_tx: terminal::RedrawSender,
+        query_rx: sync::mpsc::Receiver<String>,
+    ) -> Self {
+        let review = sync::Arc::new(sync::Mutex::new(None));
+        let mut follow = match config.follow {
+            true => Follow::Following,
+            false => Follow::Off,
+        };
+        follow = follow.reset(config.initial_scroll);
+        status.lock().unwrap().follow = follow;
+        let data = sync::Arc::new(data::SharedData::new(config.diff));
+        let query = sync::Arc::new(sync::Mutex::new(String::new()));
+        let case = config.case;
+        let highlight = config.highlight;
+        let filter = config.filter;
+        let keymap = config.keymap.clone();
+        let viewport = sync::Arc::new(sync::Mutex::new(Viewport::default()));
+        let running = RunningChildren::default();
+        let cursor_line = config.cursor_line;
+        let paging_threshold = config.paging_threshold;
+        let auto_select = config.auto_select;
+        let started = sync::Arc::new(sync::atomic::AtomicBool::new(!config.lazy));
+        let diff = config.diff;
+        let delimiter = config.delimiter.clone();
+        let nth = config.nth.clone();
+        let tac = config.tac;
+        let json = config.json;
+        let json_key = config.json_key.clone();
+        let preview = config
+            .preview
+            .clone()
+            .map(|cmd| UiPreview::new(cmd, config.kill_grace, redraw_tx.clone()));
+        let hex = sync::Arc::new(sync::atomic::AtomicBool::new(
+            config.binary == config::BinaryMode::Hex,
+        ));
+        let pane = PaneSize::default();
+        Self::start(
+            config,
+            input,
+            redraw_tx,
+            query_rx,
+            data.clone(),
+            UiWaitingProcessShared {
+                query: query.clone(),
+                status: status.clone(),
+                viewport: viewport.clone(),
+                running: running.clone(),
+                started: started.clone(),
+                hex: hex.clone(),
+                pane: pane.clone(),
+            },
+        );
+        Self {
+            data,
+            query,
+            case,
+            highlight,
+            filter,
+            keymap,
+            viewport,
+            running,
+            cursor_line,
+            paging_threshold,
+            auto_select,
+            started,
+            diff,
+            diff_cache: sync::Mutex::new(None),
+            delimiter,
+            records_cache: sync::Mutex::new(None),
+            nth,
+            nth_cache: sync::Mutex::new(None),
+            tac,
+            json,
+            json_key,
+            json_key_cache: sync::Mutex::new(None),
+            status,
+            review,
+            hex,
+            hex_cache: sync::Mutex::new(None),
+            preview,
+            pane,
+        }
+    }
+
+    fn load_rendered(
+        &self,
+        cache: &sync::Mutex<Option<RenderCache>>,
+        (version, data): (u64, sync::Arc<Vec<u8>>),
+        render: impl FnOnce(&[u8]) -> Vec<u8>,
+    ) -> sync::Arc<Vec<u8>> {
+        let mut cache = cache.lock().unwrap();
+        if let Some(cache) = &*cache
+            && cache.version == version
+        {
+            return cache.rendered.clone();
+        }
+
+        let rendered = sync::Arc::new(render(&data));
+        *cache = Some(RenderCache {
+            version,
+            rendered: rendered.clone(),
+        });
+        rendered
+    }
+
+    // only the focused line gets parsed
+    fn json_preview<'a>(
+        &self,
+        records: impl IntoIterator<Item = &'a [u8]>,
+        displayed: &[u8],
+        focused: usize,
+        matcher: Option<&terminal::Matcher>,
+    ) -> Vec<u8> {
+        let record = focused_record(records, displayed, focused, |line| {
+            self.hidden(matcher, line)
+        });
+        match record {
+            Some(line) => render_json_preview(line),
+            None => Vec::new(),
+        }
+    }
+
+    fn matcher(&self) -> Option<terminal::Matcher> {
+        (self.highlight || self.filter.is_some()).then(|| terminal::Matcher {
+            query: self.query.lock().unwrap().clone(),
+            case: self.case,
+        })
+    }
+
+    fn reviewing(&self) -> bool {
+        self.review.lock().unwrap().is_some()
+    }
+
+    // the output as is only grows within a generation, unlike what gets rendered from it
+    fn shown_as_is(&self) -> bool {
+        !self.reviewing()
+            && self.started.load(sync::atomic::Ordering::Relaxed)
+            && !self.hex.load(sync::atomic::Ordering::Relaxed)
+            && !self.diff
+            && self.json_key.is_none()
+            && self.nth.is_none()
+            && self.delimiter.is_none()
+            && !self.tac
+    }
+
+    fn displayed(&self) -> sync::Arc<Vec<u8>> {
+        if let Some(review) = &*self.review.lock().unwrap() {
+            return sync::Arc::new(review.output.lock().unwrap().clone());
+        }
+        if !self.started.load(sync::atomic::Ordering::Relaxed) {
+            sync::Arc::new(LAZY_HINT.to_vec())
+        } else if self.hex.load(sync::atomic::Ordering::Relaxed) {
+            self.load_rendered(&self.hex_cache, self.data.snapshot(), hex::render_hex)
+        } else if self.diff {
+            self.load_diff()
+        } else if let Some(json_key) = &self.json_key {
+            self.load_rendered(&self.json_key_cache, self.versioned_records(), |data| {
+                render_json_key(data, json_key)
+            })
+        } else if let Some(nth) = &self.nth {
+            self.load_rendered(&self.nth_cache, self.versioned_records(), |data| {
+                fields::render_fields(data, nth)
+            })
+        } else {
+            self.records()
+        }
+    }
+
+    // the records of the output as the program wrote them, in the order they are shown
+    fn original_records<'a>(&self, data: &'a [u8]) -> Vec<&'a [u8]> {
+        if self.delimiter.is_none() && !self.tac {
+            return data.split(|v| *v == b'\n').collect();
+        }
+        let mut records = split_records(data, self.delimiter.as_deref().unwrap_or(b"\n"));
+        if self.tac {
+            records.reverse();
+        }
+        records
+    }
+
+    // the output with one record per line, last first with --tac
+    fn records(&self) -> sync::Arc<Vec<u8>> {
+        self.versioned_records().1
+    }
+
+    // with the version of the output they are from, what the caches rendered from them go by
+    fn versioned_records(&self) -> (u64, sync::Arc<Vec<u8>>) {
+        let snapshot = self.data.snapshot();
+        if self.delimiter.is_none() && !self.tac {
+            return snapshot;
+        }
+        let version = snapshot.0;
+        let records = self.load_rendered(&self.records_cache, snapshot, |data| {
+            match (&self.delimiter, self.tac) {
+                (Some(delimiter), true) => reverse_records(&render_records(data, delimiter), b"\n"),
+                (Some(delimiter), false) => render_records(data, delimiter),
+                (None, _) => reverse_records(data, b"\n"),
+            }
+        });
+        (version, records)
+    }
+
+    // the first run has nothing to compare against and shows as is
+    fn load_diff(&self) -> sync::Arc<Vec<u8>> {
+        let current = self.data.load();
+        let Some(previous) = self.data.load_previous() else {
+            return current;
+        };
+
+        let mut diff_cache = self.diff_cache.lock().unwrap();
+        if let Some(cache) = &*diff_cache
+            && sync::Arc::ptr_eq(&cache.previous, &previous)
+            && sync::Arc::ptr_eq(&cache.current, &current)
+        {
+            return cache.rendered.clone();
+        }
+
+        let rendered = sync::Arc::new(render_diff(&previous, &current));
+        *diff_cache = Some(DiffCache {
+            previous,
+            current,
+            rendered: rendered.clone(),
+        });
+        rendered
+    }
+
+    fn start(
+        config: sync::Arc<config::Config>,
+        input: Option<sync::Arc<Vec<u8>>>,
+        redraw_tx: terminal::RedrawSender,
+        query_rx: sync::mpsc::Receiver<String>,
+        data: sync::Arc<data::SharedData>,
+        shared: UiWaitingProcessShared,
+    ) -> thread::JoinHandle<()> {
+        thread::spawn({
+            move || {
+                let UiWaitingProcessShared {
+                    query: shared_query,
+                    status,
+                    viewport,
+                    running,
+                    started,
+                    hex,
+                    pane,
+                } = shared;
+                // killed outside the lock, as it waits for them to exit
+                let take_running = || mem::take(&mut *running.lock().unwrap());
+                // the next run starts without waiting out the grace of the previous one
+                let kill_running = || {
+                    let children = take_running();
+                    children
+                        .into_iter()
+                        .for_each(child::DroppableChild::kill_in_background);
+                };
+                let mut query = config.query.clone().unwrap_or_default();
+                let arg_max = config.arg_max.unwrap_or_else(system_arg_max);
+                // invalid regexes are not spawned, the last good output stays
+                let next_query = || -> Option<String> {
+                    loop {
+                        let query = query_rx.recv().ok()?;
+                        // a different query is a different list, so it starts over from the top
+                        // or the bottom, re-runs of the same query keep their place
+                        if query != *shared_query.lock().unwrap() {
+                            *viewport.lock().unwrap() = Viewport::default();
+                            let mut status = status.lock().unwrap();
+                            status.follow = status.follow.reset(config.initial_scroll);
+                        }
+
+                        // filtering happens client side over the output of the first run
+                        if config.filter.is_some() && started.load(sync::atomic::Ordering::Relaxed)
+                        {
+                            shared_query.lock().unwrap().clone_from(&query);
+                            redraw_tx.send().ok()?;
+                            continue;
+                        }
+
+                        if !config.validate_regex {
+                            return Some(query);
+                        }
+
+                        let regex_error = regex::Regex::new(&query)
+                            .err()
+                            .map(|err| format_regex_error(&err));
+                        let valid = regex_error.is_none();
+                        status.lock().unwrap().regex_error = regex_error;
+                        redraw_tx.send().ok()?;
+
+                        if valid {
+                            return Some(query);
+                        }
+                    }
+                };
+
+                // shown instead of the output, with the run it was for gone
+                let spawn_failed = |bin: &str, err: &io::Error| {
+                    kill_running();
+                    let mut writer = data::DataWriter::new(data.clone());
+                    writer.push(&format_spawn_error(bin, err, config.color));
+                    writer.finish();
+                    status.lock().unwrap().run = None;
+                    redraw_tx.send()
+                };
+
+                // returns once the sender of the queries is gone, the run goes with it
+                let mut work = || loop {
+                    shared_query.lock().unwrap().clone_from(&query);
+                    if !started.load(sync::atomic::Ordering::Relaxed) {
+                        let Some(next) = next_query() else { return };
+                        query = next;
+                        started.store(true, sync::atomic::Ordering::Relaxed);
+                        continue;
+                    }
+
+                    // the output of the last query stays, a filter runs without one anyway
+                    let short =
+                        config.filter.is_none() && query.chars().count() < config.min_query_length;
+                    status.lock().unwrap().short_query = short.then(|| {
+                        format!(
+                            "not run, type at least {} characters",
+                            config.min_query_length
+                        )
+                    });
+                    if short {
+                        onerr!(redraw_tx.send(), { return });
+                        let Some(next) = next_query() else { return };
+                        query = next;
+                        continue;
+                    }
+
+                    // with a filter the program always runs without the query
+                    let run_query = if config.filter.is_some() { "" } else { &query };
+                    let hooked = config
+                        .query_hook
+                        .as_ref()
+                        .map(|hook| run_query_hook(hook, run_query));
+                    let run_query = match &hooked {
+                        Some(Ok(hooked)) => hooked,
+                        Some(Err(_)) | None => run_query,
+                    };
+                    let hook_error = hooked.as_ref().and_then(|v| v.as_ref().err().cloned());
+                    let vetoed = hook_error.is_some();
+                    status.lock().unwrap().hook_error = hook_error;
+                    // the output of the last query stays
+                    if vetoed {
+                        onerr!(redraw_tx.send(), { return });
+                        let Some(next) = next_query() else { return };
+                        query = next;
+                        continue;
+                    }
+                    let argv =
+                        build_argv(&config.bin, &config.bin_args, run_query, config.query_mode);
+                    let arg_error = arg_length_error(&argv, arg_max);
+                    let too_long = arg_error.is_some();
+                    status.lock().unwrap().arg_error = arg_error;
+                    // the output of the last query stays
+                    if too_long {
+                        onerr!(redraw_tx.send(), { return });
+                        let Some(next) = next_query() else { return };
+                        query = next;
+                        continue;
+                    }
+
+                    let run_input = build_stdin(&input, run_query, config.query_mode);
+                    let mut command = create_command(
+                        &config.bin,
+                        &config.bin_args,
+                        run_query,
+                        config.query_mode,
+                        &run_input,
+                    );
+                    child::own_group(&mut command);
+                    // tip's stdout is for what gets accepted
+                    if !config.capture.stdout() {
+                        command.stdout(process::Stdio::null());
+                    }
+                    if !config.capture.stderr() {
+                        let tty = terminal::isatty(libc::STDERR_FILENO);
+                        command.stderr(match inherits_stderr(config.uncaptured, tty) {
+                            true => process::Stdio::inherit(),
+                            false => process::Stdio::null(),
+                        });
+                    }
+                    // the captured streams go to the terminal, read as one
+                    let mut pty_master = None;
+                    if config.pty {
+                        let size = pty_size(*pane.lock().unwrap());
+                        let opened = child::open_pty(size)
+                            .and_then(|(master, slave)| Ok((master, slave.try_clone()?, slave)));
+                        let (master, slave_stderr, slave) = match opened {
+                            Ok(v) => v,
+                            Err(err) => {
+                                onerr!(spawn_failed("--pty", &err), { return });
+                                let Some(next) = next_query() else { return };
+                                query = next;
+                                continue;
+                            }
+                        };
+                        if config.capture.stdout() {
+                            command.stdout(slave);
+                        }
+                        if config.capture.stderr() {
+                            command.stderr(slave_stderr);
+                        }
+                        pty_master = Some(master);
+                    }
+                    let spawned = command.spawn();
+                    // the parent's copy of the pty slave would keep the master from ever ending
+                    drop(command);
+                    let spawned = match spawned {
+                        Ok(v) => v,
+                        Err(err) => {
+                            onerr!(spawn_failed(&config.bin, &err), { return });
+                            let Some(next) = next_query() else { return };
+                            query = next;
+                            continue;
+                        }
+                    };
+                    let mut child = child::DroppableChild::with_grace(spawned, config.kill_grace);
+                    let stdin = child.0.stdin.take();
+                    // read one after the other, only stderr when stdout is not captured
+                    let mut captured = [
+                        child.0.stdout.take().map(OwnedFd::from),
+                        child.0.stderr.take().map(OwnedFd::from),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .map(fs::File::from);
+                    let (mut stdout, mut stderr) = match pty_master {
+                        Some(master) => (master, None),
+                        None => (captured.next().unwrap(), captured.next()),
+                    };
+                    // the previous run and its transform go
+                    kill_running();
+                    running.lock().unwrap().push(child);
+
+                    let started_at = time::Instant::now();
+                    status.lock().unwrap().run = Some(RunStatus::Running(started_at));
+
+                    // the transform reads everything the command outputs,
+                    // and its own output is what gets displayed
+                    if let Some(transform) = &config.transform {
+                        let transform = match create_transform_command(transform).spawn() {
+                            Ok(v) => v,
+                            Err(err) => {
+                                onerr!(spawn_failed("--transform", &err), { return });
+                                let Some(next) = next_query() else { return };
+                                query = next;
+                                continue;
+                            }
+                        };
+                        let mut transform =
+                            child::DroppableChild::with_grace(transform, config.kill_grace);
+                        let mut transform_stdin = transform.0.stdin.take().unwrap();
+                        thread::spawn(move || {
+                            let _ = io::copy(&mut stdout, &mut transform_stdin);
+                            if let Some(mut stderr) = stderr {
+                                let _ = io::copy(&mut stderr, &mut transform_stdin);
+                            }
+                        });
+
+                        stdout = fs::File::from(OwnedFd::from(transform.0.stdout.take().unwrap()));
+                        stderr = Some(fs::File::from(OwnedFd::from(
+                            transform.0.stderr.take().unwrap(),
+                        )));
+                        running.lock().unwrap().push(transform);
+                    }
+
+                    // the previous output stays until this run writes something
+                    thread::spawn({
+                        let input = run_input;
+                        let buffer_size = config.buffer_size;
+                        let line_buffered = config.line_buffered;
+                        let line_limit = config.line_limit();
+                        let binary = config.binary;
+                        let data = data.clone();
+                        let redraw_tx = redraw_tx.clone();
+                        let status = status.clone();
+                        let hex = hex.clone();
+                        move || {
+                            // keeps the elapsed time in the footer moving
+                            thread::spawn({
+                                let status = status.clone();
+                                let redraw_tx = redraw_tx.clone();
+                                move || loop {
+                                    thread::sleep(RUNNING_TICK);
+                                    if status.lock().unwrap().run
+                                        != Some(RunStatus::Running(started_at))
+                                    {
+                                        break;
+                                    }
+                                    onerr!(redraw_tx.send(), { break });
+                                }
+                            });
+
+                            let write_handle = input.map(|input| {
+                                thread::spawn(move || {
+                                    let _ = stdin.unwrap().write_all(&input);
+                                })
+                            });
+
+                            let mut writer = data::DataWriter::new(data);
+                            writer.set_line_limit(line_limit);
+                            // a run is shown one way throughout, output that turns binary
+                            // later stays text until the next run
+                            let mut detect = (binary == config::BinaryMode::Auto).then_some(&*hex);
+                            let _ = Self::read_child_stream(
+                                stdout,
+                                buffer_size,
+                                line_buffered,
+                                &mut writer,
+                                &redraw_tx,
+                                &mut detect,
+                            );
+                            if let Some(stderr) = stderr {
+                                let _ = Self::read_child_stream(
+                                    stderr,
+                                    buffer_size,
+                                    line_buffered,
+                                    &mut writer,
+                                    &redraw_tx,
+                                    &mut detect,
+                                );
+                            }
+                            let mut status = status.lock().unwrap();
+                            // a superseded run must not clear the output of the next one
+                            if status.run == Some(RunStatus::Running(started_at)) {
+                                writer.finish();
+                            }
+                            status.finish_run(started_at);
+                            drop(status);
+                            let _ = redraw_tx.send();
+
+                            if let Some(write_handle) = write_handle {
+                                write_handle.join().unwrap();
+                            }
+                        }
+                    });
+
+                    let Some(next) = next_query() else { return };
+                    query = next;
+                };
+                work();
+                // tip is exiting, they are gone before it is, not with the lock held
+                let children = take_running();
+                drop(children);
+            }
+        })
+    }
+
+    fn read_child_stream(
+        mut stream: impl Read + AsRawFd,
+        buffer_size: usize,
+        line_buffered: bool,
+        writer: &mut data::DataWriter,
+        redraw_tx: &terminal::RedrawSender,
+        detect: &mut Option<&sync::atomic::AtomicBool>,
+    ) -> Result<()> {
+        let mut buf = vec![0; buffer_size];
+        // held back until its newline arrives when line buffered
+        let mut partial = Vec::new();
+        loop {
+            // wait for more output, but no longer than pending data is allowed to stay unpublished
+            if !poll_readable(stream.as_raw_fd(), writer.flush_timeout())? {
+                if writer.flush() {
+                    redraw_tx.send()?;
+                }
+                continue;
+            }
+
+            let size = match stream.read(&mut buf) {
+                // a pty master once everything writing to the terminal has closed it
+                Err(err) if err.raw_os_error() == Some(libc::EIO) => 0,
+                size => size?,
+            };
+            if size == 0 {
+                break;
+            }
+            if let Some(hex) = detect.take() {
+                hex.store(
+                    hex::looks_binary(&buf[..size]),
+                    sync::atomic::Ordering::Relaxed,
+                );
+            }
+            let published = match line_buffered {
+                false => writer.push(&buf[..size]),
+                true => {
+                    partial.extend_from_slice(&buf[..size]);
+                    match partial.iter().rposition(|v| *v == b'\n') {
+                        Some(end) => {
+                            let rest = partial.split_off(end + 1);
+                            let published = writer.push(&partial);
+                            partial = rest;
+                            published
+                        }
+                        None => false,
+                    }
+                }
+            };
+            if published {
+                redraw_tx.send()?;
+            }
+        }
+
+        // the last line does not need to end with a newline
+        let published = !partial.is_empty() && writer.push(&partial);
+        if writer.flush() || published {
+            redraw_tx.send()?;
+        }
+        Ok(())
+    }
+}
+
+impl terminal::ComponentData for UiWaitingProcess {
+    fn content(&self) -> sync::Arc<Vec<u8>> {
+        self.data.load()
+    }
+
+    fn shown(&self) -> sync::Arc<Vec<u8>> {
+        self.displayed()
+    }
+
+    // the whole record, even when --nth shows only some of its fields
+    fn selection(&self) -> Option<Vec<u8>> {
+        if !self.cursor_line {
+            return None;
+        }
+        let displayed = self.displayed();
+        let cursor = preserved_scroll(self.viewport.lock().unwrap().cursor, line_count(&displayed));
+        let matcher = self.matcher();
+        let hidden = |line: &[u8]| self.hidden(matcher.as_ref(), line);
+        let data = self.data.load();
+        focused_record(self.original_records(&data), &displayed, cursor, hidden).map(<[u8]>::to_vec)
+    }
+
+    // the trailing newline does not start another record.
+    // without a cursor line it is the line at the top, once there are enough records to page
+    fn position(&self) -> Option<(usize, usize)> {
+        let displayed = self.displayed();
+        let matcher = self.matcher();
+        let records = displayed.strip_suffix(b"\n").unwrap_or(&displayed);
+        let total = match records.is_empty() {
+            true => 0,
+            false => records
+                .split(|v| *v == b'\n')
+                .filter(|line| !self.hidden(matcher.as_ref(), line))
+                .count(),
+        };
+        if !self.cursor_line && total <= self.paging_threshold {
+            return None;
+        }
+        if total == 0 {
+            return Some((0, 0));
+        }
+        let viewport = *self.viewport.lock().unwrap();
+        let line = if self.cursor_line {
+            viewport.cursor
+        } else {
+            viewport.scroll
+        };
+        // the renderer keeps a followed cursor on the last line, and the last line in view
+        let cursor = match self.status.lock().unwrap().follow.pinned() {
+            true => total - 1,
+            false => preserved_scroll(line, line_count(&displayed)),
+        };
+        Some((cursor.min(total - 1) + 1, total))
+    }
+
+    fn render(&self) -> terminal::ComponentDataOut {
+        // loaded together, so the generation is the one of the output drawn
+        let (generation, data) = match self.shown_as_is() {
+            true => {
+                let (generation, data) = self.data.generational();
+                (Some(generation), data)
+            }
+            false => (None, self.displayed()),
+        };
+        let viewport = *self.viewport.lock().unwrap();
+        let line_count = line_count(&data);
+        let scroll = preserved_scroll(viewport.scroll, line_count);
+        let cursor = preserved_scroll(viewport.cursor, line_count);
+
+        let matcher = self.matcher();
+        // the cursor line is the focused one, otherwise the line at the top
+        let focused = if self.cursor_line { cursor } else { scroll };
+        let shows_preview = self.started.load(sync::atomic::Ordering::Relaxed) && !self.reviewing();
+        let preview = match &self.preview {
+            Some(preview) => shows_preview.then(|| {
+                let output = self.data.load();
+                let hidden = |line: &[u8]| self.hidden(matcher.as_ref(), line);
+                preview.show(focused_record(
+                    self.original_records(&output),
+                    &data,
+                    focused,
+                    hidden,
+                ))
+            }),
+            None => (self.json && shows_preview).then(|| {
+                let output = self.data.load();
+                let records = self.original_records(&output);
+                self.json_preview(records, &data, focused, matcher.as_ref())
+            }),
+        };
+
+        terminal::ComponentDataOut {
+            data,
+            scroll,
+            cursor_line: self.cursor_line.then_some(cursor),
+            matcher,
+            highlight: self.highlight,
+            filter: self.filter,
+            preview,
+            follow: self.status.lock().unwrap().follow.pinned(),
+            generation,
+        }
+    }
+
+    fn input(&mut self, input: &terminal::TerminalInput, height: usize) -> Result<()> {
+        let Some(action) = self.keymap.action(input) else {
+            return Ok(());
+        };
+
+        if action == keymap::Action::Cancel {
+            // the worker is waiting for the next query already, only the run is left,
+            // taken before the status lock so the two are never held together
+            let children = mem::take(&mut *self.running.lock().unwrap());
+            let mut status = self.status.lock().unwrap();
+            if let Some(RunStatus::Running(started_at)) = status.run {
+                status.run = Some(RunStatus::Cancelled(started_at.elapsed()));
+            }
+            drop(status);
+            children
+                .into_iter()
+                .for_each(child::DroppableChild::kill_in_background);
+            return Ok(());
+        }
+
+        let line_count = line_count(&self.displayed());
+        let mut viewport = self.viewport.lock().unwrap();
+        let mut status = self.status.lock().unwrap();
+        let lines = match action {
+            keymap::Action::ToggleCase => {
+                self.case = self.case.next();
+                return Ok(());
+            }
+            keymap::Action::ToggleFollow => {
+                status.follow = match status.follow {
+                    Follow::Off | Follow::Bottom => Follow::Following,
+                    _ => Follow::Off,
+                };
+                return Ok(());
+            }
+            keymap::Action::ScrollUp => -1,
+            keymap::Action::ScrollDown => 1,
+            keymap::Action::PageUp => -(height as isize),
+            keymap::Action::PageDown => height as isize,
+            _ => return Ok(()),
+        };
+
+        (*viewport, status.follow) = scroll_viewport(
+            *viewport,
+            status.follow,
+            self.cursor_line,
+            lines,
+            height,
+            line_count,
+        );
+        Ok(())
+    }
+
+    fn reflowed(&mut self, scroll: usize) {
+        self.viewport.lock().unwrap().scroll = scroll;
+    }
+
+    fn pane_size(&mut self, rows: usize, columns: usize) {
+        *self.pane.lock().unwrap() = Some((rows, columns));
+    }
+
+    // a run still streaming may have more records to come
+    fn stops(&self) -> bool {
+        if !self.auto_select || self.reviewing() {
+            return false;
+        }
+        let finished = matches!(
+            self.status.lock().unwrap().run,
+            Some(RunStatus::Finished(_))
+        );
+        finished && self.position() == Some((1, 1))
+    }
+}
+
+// the program given on the command line and the commands of --tab, which all run with the
+// same query. the header, the footer and the input handler go by the tab shown
+#[derive(Clone)]
+struct Tabs {
+    tabs: sync::Arc<Vec<Tab>>,
+    active: sync::Arc<sync::atomic::AtomicUsize>,
+}
+
+struct Tab {
+    label: String,
+    data: sync::Arc<data::SharedData>,
+    status: sync::Arc<sync::Mutex<UiStatus>>,
+}
+
+impl Tabs {
+    fn new(tabs: Vec<Tab>) -> Self {
+        Self {
+            tabs: sync::Arc::new(tabs),
+            active: sync::Arc::new(sync::atomic::AtomicUsize::new(0)),
+        }
+    }
+
+    fn index(&self) -> usize {
+        self.active.load(sync::atomic::Ordering::Relaxed)
+    }
+
+    fn active(&self) -> &Tab {
+        &self.tabs[self.index()]
+    }
+
+    fn next(&self) {
+        let next = (self.index() + 1) % self.tabs.len();
+        self.active.store(next, sync::atomic::Ordering::Relaxed);
+    }
+
+    // the labels with the one shown in reverse video, none without --tab
+    fn bar(&self) -> Option<Vec<u8>> {
+        if self.tabs.len() < 2 {
+            return None;
+        }
+        let mut bar = Vec::new();
+        for (i, tab) in self.tabs.iter().enumerate() {
+            if i > 0 {
+                bar.push(b' ');
+            }
+            let label = match i == self.index() {
+                true => format!("\x1b[7m {} \x1b[27m", tab.label),
+                false => format!(" {} ", tab.label),
+            };
+            bar.extend_from_slice(label.as_bytes());
+        }
+        Some(bar)
+    }
+}
+
+// a --tab command runs through the shell with the query as $1
+fn tab_config(config: &config::Config, cmd: &str) -> config::Config {
+    let mut config = config.clone();
+    config.bin = "sh".to_string();
+    config.bin_args = vec!["-c".to_string(), cmd.to_string(), "sh".to_string()];
+    config.query_mode = config::QueryMode::Append;
+    config
+}
+
+// the data pane shows the output of the tab shown, each tab keeps its own scroll
+struct UiTabs {
+    processes: Vec<UiWaitingProcess>,
+    tabs: Tabs,
+    // the tab of the last frame
+    shown: sync::atomic::AtomicUsize,
+    // the last frame stopped the renderer, it is checked after every one
+    stopped: sync::atomic::AtomicBool,
+}
+
+impl UiTabs {
+    fn active(&self) -> &UiWaitingProcess {
+        &self.processes[self.tabs.index()]
+    }
+
+    // once tip is done with them, the graces are waited out together
+    fn kill_running(&self) {
+        let children = self
+            .processes
+            .iter()
+            .flat_map(|v| mem::take(&mut *v.running.lock().unwrap()))
+            .collect::<Vec<_>>();
+        thread::scope(|scope| {
+            for child in children {
+                scope.spawn(move || drop(child));
+            }
+        });
+    }
+}
+
+impl terminal::ComponentData for UiTabs {
+    fn render(&self) -> terminal::ComponentDataOut {
+        let index = self.tabs.index();
+        let mut out = self.processes[index].render();
+        // the generation of another tab's output says nothing about what was drawn
+        if self.shown.swap(index, sync::atomic::Ordering::Relaxed) != index {
+            out.generation = None;
+        }
+        out
+    }
+
+    fn content(&self) -> sync::Arc<Vec<u8>> {
+        self.active().content()
+    }
+
+    fn shown(&self) -> sync::Arc<Vec<u8>> {
+        self.active().shown()
+    }
+
+    fn selection(&self) -> Option<Vec<u8>> {
+        self.active().selection()
+    }
+
+    fn position(&self) -> Option<(usize, usize)> {
+        self.active().position()
+    }
+
+    fn input(&mut self, input: &terminal::TerminalInput, height: usize) -> Result<()> {
+        // the case mode goes with the query, which every tab runs with
+        if self.active().keymap.action(input) == Some(keymap::Action::ToggleCase) {
+            for process in &mut self.processes {
+                process.input(input, height)?;
+            }
+            return Ok(());
+        }
+        let index = self.tabs.index();
+        self.processes[index].input(input, height)
+    }
+
+    fn reflowed(&mut self, scroll: usize) {
+        let index = self.tabs.index();
+        self.processes[index].reflowed(scroll);
+    }
+
+    // the tabs share the pane
+    fn pane_size(&mut self, rows: usize, columns: usize) {
+        for process in &mut self.processes {
+            process.pane_size(rows, columns);
+        }
+    }
+
+    fn stops(&self) -> bool {
+        let stops = self.active().stops();
+        self.stopped.store(stops, sync::atomic::Ordering::Relaxed);
+        stops
+    }
+}
+
+// the data pane once it is drawn, before that the terminal's width and the rows below the
+// prompt and separator
+fn pty_size(pane: Option<(usize, usize)>) -> libc::winsize {
+    if let Some((rows, columns)) = pane {
+        return libc::winsize {
+            ws_row: rows.clamp(1, u16::MAX as usize) as u16,
+            ws_col: columns.clamp(1, u16::MAX as usize) as u16,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+    }
+    let mut size = match fs::File::open("/dev/tty") {
+        Ok(tty) => terminal::get_terminal_size(tty.as_raw_fd()),
+        Err(_) => terminal::get_terminal_size(-1),
+    };
+    size.ws_row = size.ws_row.saturating_sub(2).max(1);
+    size
+}
+
+// returns false on timeout, none waits forever
+fn poll_readable(fd: i32, timeout: Option<time::Duration>) -> Result<bool> {
+    let mut pollfd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout_ms = timeout
+        .map(|v| v.as_micros().div_ceil(1000) as i32)
+        .unwrap_or(-1);
+
+    match unsafe { libc::poll(&mut pollfd, 1, timeout_ms) } {
+        0 => Ok(false),
+        -1 => {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                return Ok(false);
+            }
+            Err(err.into())
+        }
+        _ => Ok(true),
+    }
+}
+
+// exit code a shell reports for a process killed by SIGPIPE
+const BROKEN_PIPE_EXIT_CODE: i32 = 128 + libc::SIGPIPE;
+
+// the exit code to stop with when the output could not take everything. the consumer
+// closing its end (e.g `tip cmd | head`) is not an error, anything else (e.g a full disk)
+// is, so a partial --output is never committed
+fn copied_output(result: io::Result<u64>) -> Result<Option<i32>> {
+    match result {
+        Ok(_) => Ok(None),
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(Some(BROKEN_PIPE_EXIT_CODE)),
+        Err(err) => Err(anyhow!("failed writing output: {}", err)),
+    }
+}
+
+fn pipe_cmd(
+    cmd: &str,
+    args: &[String],
+    query: &str,
+    mode: config::QueryMode,
+    input: Option<sync::Arc<Vec<u8>>>,
+    mut output: impl Write + Send + 'static,
+    mut errors: impl Write + Send + 'static,
+) -> Result<i32> {
+    let input = build_stdin(&input, query, mode);
+    let mut command = create_command(cmd, args, query, mode, &input);
+    let spawned = command
+        .spawn()
+        .map_err(|err| error::TipError::SpawnFailed(cmd.to_string(), err))?;
+    let mut child = child::DroppableChild::new(spawned);
+
+    let stdin_handle = input.map(|input| {
+        thread::spawn({
+            let mut stdin = child.0.stdin.take().unwrap();
+            move || stdin.write_all(&input)
+        })
+    });
+
+    let stdout_handle = thread::spawn({
+        let mut stdout = child.0.stdout.take().unwrap();
+        move || io::copy(&mut stdout, &mut output)
+    });
+
+    let stderr_handle = thread::spawn({
+        let mut stderr = child.0.stderr.take().unwrap();
+        move || io::copy(&mut stderr, &mut errors)
+    });
+
+    // the child gets killed when dropped, as nobody is reading its output anymore
+    if let Some(code) = copied_output(stdout_handle.join().unwrap())? {
+        return Ok(code);
+    }
+
+    let exit_status = child.0.wait()?;
+
+    if let Some(stdin_handle) = stdin_handle {
+        let _ = stdin_handle.join().unwrap();
+    }
+    let _ = stderr_handle.join().unwrap();
+
+    Ok(exit_status.code().unwrap_or(2))
+}
+
+// it gets the terminal like an execute action, so it can be an editor, e.g vim "$2". the
+// captured output is its stdin instead when there is one, and stdout is tip's own unless
+// an output is given to copy it to
+fn run_accept_command(
+    args: &[String],
+    input: Option<sync::Arc<Vec<u8>>>,
+    output: Option<impl Write + Send + 'static>,
+) -> Result<i32> {
+    let mut command = process::Command::new("sh");
+    command.args(args);
+    command.stdin(match &input {
+        Some(_) => process::Stdio::piped(),
+        // without one, e.g in a cron job, it reads nothing
+        None => match fs::File::open("/dev/tty") {
+            Ok(tty) => process::Stdio::from(tty),
+            Err(_) => process::Stdio::null(),
+        },
+    });
+    if output.is_some() {
+        command.stdout(process::Stdio::piped());
+    }
+    let spawned = command
+        .spawn()
+        .map_err(|err| error::TipError::SpawnFailed("sh".to_string(), err))?;
+    let mut child = child::DroppableChild::new(spawned);
+
+    let stdin_handle = input.map(|input| {
+        let mut stdin = child.0.stdin.take().unwrap();
+        thread::spawn(move || stdin.write_all(&input))
+    });
+    if let Some(mut output) = output {
+        let mut stdout = child.0.stdout.take().unwrap();
+        // killed when dropped, nobody reads what it prints any more
+        if let Some(code) = copied_output(io::copy(&mut stdout, &mut output))? {
+            return Ok(code);
+        }
+    }
+
+    let exit_status = child.0.wait()?;
+    if let Some(stdin_handle) = stdin_handle {
+        let _ = stdin_handle.join().unwrap();
+    }
+    Ok(exit_status.code().unwrap_or(2))
+}
+
+// output collected from another thread
+#[derive(Clone, Default)]
+struct SharedBuf(sync::Arc<sync::Mutex<Vec<u8>>>);
+
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// decided by --select-1 and --exit-0 before the interactive session
+#[derive(Debug, PartialEq, Eq)]
+enum Shortcut {
+    Exit(i32),
+    // accepted like enter would
+    Accept(Accepted),
+}
+
+// runs the program once with the initial query, the records counted the way the session shows them
+fn shortcut(
+    config: &config::Config,
+    input: Option<sync::Arc<Vec<u8>>>,
+) -> Result<Option<Shortcut>> {
+    if !config.select_1 && !config.exit_0 {
+        return Ok(None);
+    }
+
+    let query = config.query.as_deref().unwrap_or_default();
+    let run_query = if config.filter.is_some() { "" } else { query };
+    let output = SharedBuf::default();
+    pipe_cmd(
+        &config.bin,
+        &config.bin_args,
+        run_query,
+        config.query_mode,
+        input.clone(),
+        output.clone(),
+        io::sink(),
+    )?;
+    let captured = sync::Arc::new(output.0.lock().unwrap().clone());
+    let (output, originals) = match &config.delimiter {
+        Some(delimiter) => (
+            render_records(&captured, delimiter),
+            split_records(&captured, delimiter),
+        ),
+        None => (captured.to_vec(), captured.split(|v| *v == b'\n').collect()),
+    };
+
+    let matcher = config.filter.map(|_| terminal::Matcher {
+        query: query.to_string(),
+        case: config.case,
+    });
+    // counted as shown, selected as written
+    let mut records = originals
+        .into_iter()
+        .zip(output.split(|v| *v == b'\n'))
+        .filter(|(_, v)| !v.is_empty())
+        .filter(|(_, v)| matcher.as_ref().is_none_or(|matcher| matcher.is_match(v)))
+        .map(|(record, _)| record);
+    Ok(match (records.next(), records.next()) {
+        (None, _) if config.exit_0 => Some(Shortcut::Exit(CANCEL_EXIT_CODE)),
+        (Some(record), None) if config.select_1 => Some(Shortcut::Accept(
+            match (config.select, config.accept_output) {
+                (true, _) => Accepted::Selected(Some(record.to_vec())),
+                (false, config::AcceptOutput::Captured) => Accepted::Captured(captured),
+                (false, config::AcceptOutput::Run) => Accepted::Run(input),
+            },
+        )),
+        _ => None,
+    })
+}
+
+// written to the terminal after leaving the alternate screen, so it stays in the scrollback,
+// not to stdout as it is not the result
+fn keep_output(data: &[u8]) -> Result<()> {
+    if data.is_empty() {
+        return Ok(());
+    }
+    let mut tty = fs::OpenOptions::new().write(true).open("/dev/tty")?;
+    tty.write_all(data)?;
+    if !data.ends_with(b"\n") {
+        tty.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+// exit code when accepting an empty query is configured to cancel
+const CANCEL_EXIT_CODE: i32 = 1;
+
+// whether accepting goes ahead or is ignored, leaving tip open
+fn accepts(config: &config::Config, query: &str, output_empty: bool) -> bool {
+    if config.empty_accept != config::EmptyAccept::Ignore {
+        return true;
+    }
+    // what is accepted is the selected record, not the query
+    match config.select {
+        true => !output_empty,
+        false => !query.is_empty(),
+    }
+}
+
+// what accepting prints after the --expect line
+#[derive(Debug, PartialEq, Eq)]
+enum Accepted {
+    // the record under the cursor line with --select, none in an empty output
+    Selected(Option<Vec<u8>>),
+    // the output of the session with --accept-output captured
+    Captured(sync::Arc<Vec<u8>>),
+    // the program once more, with the input tip read
+    Run(Option<sync::Arc<Vec<u8>>>),
+}
+
+// output_is_stdout: output is tip's own stdout, which the accept command writes to itself
+fn accept(
+    config: &config::Config,
+    key: keymap::Key,
+    cmd: &str,
+    query: &str,
+    accepted: Accepted,
+    mut output: impl Write + Send + 'static,
+    output_is_stdout: bool,
+) -> Result<i32> {
+    if let Accepted::Selected(selected) = &accepted {
+        // nothing to select in an empty output
+        if selected.is_none() {
+            return Ok(CANCEL_EXIT_CODE);
+        }
+    } else if query.is_empty() {
+        match config.empty_accept {
+            config::EmptyAccept::Run | config::EmptyAccept::Ignore => {}
+            config::EmptyAccept::Cancel => return Ok(CANCEL_EXIT_CODE),
+            config::EmptyAccept::None => return Ok(0),
+        }
+    }
+
+    // like fzf, the first line tells which of the expected keys accepted, empty for others
+    if !config.expect.is_empty() {
+        if config.expect.contains(&key) {
+            writeln!(output, "{}", key)?;
+        } else {
+            writeln!(output)?;
+        }
+        output.flush()?;
+    }
+
+    // the command gets what would have been printed, the program does not run again
+    if let Some(accept_command) = &config.accept_command {
+        let mut args = vec![
+            "-c".to_string(),
+            accept_command.clone(),
+            "sh".to_string(),
+            query.to_string(),
+        ];
+        let input = match accepted {
+            Accepted::Selected(selected) => {
+                args.extend(selected.map(|v| String::from_utf8_lossy(&v).into_owned()));
+                None
+            }
+            Accepted::Captured(captured) => Some(captured),
+            Accepted::Run(_) => None,
+        };
+        return run_accept_command(&args, input, (!output_is_stdout).then_some(output));
+    }
+
+    // the record is already the output, the program does not run again
+    let input = match accepted {
+        Accepted::Selected(Some(selected)) => {
+            output.write_all(&selected)?;
+            output.write_all(b"\n")?;
+            output.flush()?;
+            return Ok(0);
+        }
+        Accepted::Captured(captured) => {
+            output.write_all(&captured)?;
+            output.flush()?;
+            return Ok(0);
+        }
+        // cancelled above already
+        Accepted::Selected(None) => return Ok(CANCEL_EXIT_CODE),
+        Accepted::Run(input) => input,
+    };
+
+    let query = match hooked_query(config, query) {
+        Ok(v) => v,
+        Err(err) => {
+            eprintln!("{}", err);
+            return Ok(CANCEL_EXIT_CODE);
+        }
+    };
+    if !query.is_empty() {
+        eprintln!("{} '{}'", cmd, query);
+    }
+
+    pipe_cmd(
+        &config.bin,
+        &config.bin_args,
+        &query,
+        config.query_mode,
+        input,
+        output,
+        io::stderr(),
+    )
+}
+
+// a slow producer looks like tip hung without it
+const INPUT_PROGRESS_DELAY: time::Duration = time::Duration::from_millis(200);
+const INPUT_PROGRESS_INTERVAL: time::Duration = time::Duration::from_millis(100);
+
+// read in chunks to show how much came in once it takes longer than delay,
+// the progress line is cleared at the end. ctrl-c still quits, the terminal is not raw yet
+fn read_input(
+    mut input: impl Read,
+    mut progress: Option<impl Write>,
+    delay: time::Duration,
+) -> io::Result<Vec<u8>> {
+    let start = time::Instant::now();
+    let mut shown_at: Option<time::Instant> = None;
+    let mut data = Vec::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        data.extend_from_slice(&buf[..n]);
+
+        if let Some(progress) = &mut progress
+            && start.elapsed() >= delay
+            && shown_at.is_none_or(|v| v.elapsed() >= INPUT_PROGRESS_INTERVAL)
+        {
+            let mb = data.len() as f64 / 1_000_000.0;
+            write!(progress, "\rtip: reading input… {:.1} MB", mb)?;
+            progress.flush()?;
+            shown_at = Some(time::Instant::now());
+        }
+    }
+
+    if let Some(progress) = &mut progress
+        && shown_at.is_some()
+    {
+        progress.write_all(b"\r\x1b[K")?;
+        progress.flush()?;
+    }
+    Ok(data)
+}
+
+fn main_err() -> Result<i32> {
+    let mut config = config::Config::parse(env::args().skip(1))?;
+    for warning in &config.warnings {
+        eprintln!("{}", warning);
+    }
+    let state_dir = config.restore.then(state::state_dir).flatten();
+    if config.query.is_none()
+        && let Some(state_dir) = &state_dir
+    {
+        config.query = state::load_query(state_dir, &config.bin, &config.bin_args);
+    }
+    if let Some(format) = config.print_command {
+        let query = config.query.as_deref().unwrap_or_default();
+        let argv = build_argv(&config.bin, &config.bin_args, query, config.query_mode);
+        print!("{}", print_command(&argv, format));
+        return Ok(0);
+    }
+    let config = sync::Arc::new(config);
+    let bin = config.bin.clone();
+    let bin_args = config.bin_args.clone();
+
+    let stdin_input = match &config.input {
+        config::InputSource::Stdin if !terminal::isatty(libc::STDIN_FILENO) => {
+            let progress = terminal::isatty(libc::STDERR_FILENO).then(io::stderr);
+            let v = read_input(io::stdin(), progress, INPUT_PROGRESS_DELAY)
+                .with_context(|| "failed reading stdin")?;
+            Some(sync::Arc::new(v))
+        }
+        config::InputSource::File(path) => {
+            Some(sync::Arc::new(fs::read(path).with_context(|| {
+                format!("failed reading {}", path.display())
+            })?))
+        }
+        _ => None,
+    };
+
+    // created up front so a bad path fails before the interactive session
+    let output_file = config
+        .output
+        .as_deref()
+        .map(output::AtomicFile::create)
+        .transpose()?;
+    let cmd = {
+        let mut cmd = vec![bin.clone()];
+        if !bin_args.is_empty() {
+            cmd.push(bin_args.join(" "))
+        };
+        cmd.join(" ")
+    };
+
+    match shortcut(&config, stdin_input.clone())? {
+        Some(Shortcut::Exit(code)) => return Ok(code),
+        Some(Shortcut::Accept(accepted)) => {
+            let query = config.query.as_deref().unwrap_or_default();
+            return accept_into(
+                &config,
+                keymap::Key::Enter,
+                &cmd,
+                query,
+                accepted,
+                output_file,
+            );
+        }
+        None => {}
+    }
+
+    // neither channel blocks its sender: queries queue up for the worker, redraw requests
+    // are merged into the one pending, see terminal::RedrawSender
+    let (query_tx, query_rx) = sync::mpsc::channel::<String>();
+    let (redraw_tx, redraw_rx) = terminal::redraw_channel();
+
+    let configs = iter::once(config.clone())
+        .chain(
+            config
+                .tabs
+                .iter()
+                .map(|cmd| sync::Arc::new(tab_config(&config, cmd))),
+        )
+        .collect::<Vec<_>>();
+    let labels = iter::once(cmd.clone()).chain(config.tabs.iter().cloned());
+    let mut query_txs = Vec::new();
+    let mut processes = Vec::new();
+    let mut tabs = Vec::new();
+    for (config, label) in configs.iter().zip(labels) {
+        let (query_tx, query_rx) = sync::mpsc::channel();
+        let status = sync::Arc::new(sync::Mutex::new(UiStatus::default()));
+        let process = UiWaitingProcess::new(
+            config.clone(),
+            stdin_input.clone(),
+            status.clone(),
+            redraw_tx.clone(),
+            query_rx,
+        );
+        query_txs.push(query_tx);
+        tabs.push(Tab {
+            label,
+            data: process.data.clone(),
+            status,
+        });
+        processes.push(process);
+    }
+    // every tab runs every query
+    thread::spawn(move || {
+        for query in query_rx {
+            for query_tx in &query_txs {
+                let _ = query_tx.send(query.clone());
+            }
+        }
+    });
+    let tabs = Tabs::new(tabs);
+    let mut ui_tabs = UiTabs {
+        processes,
+        tabs: tabs.clone(),
+        shown: sync::atomic::AtomicUsize::new(0),
+        stopped: sync::atomic::AtomicBool::new(false),
+    };
+
+    let mut ui_prompt = UiPrompt::new(
+        query_tx,
+        cmd.clone(),
+        config.keymap.clone(),
+        config.max_query_length,
+    );
+    ui_prompt.set_query(config.query.as_deref().unwrap_or_default());
+    ui_prompt.set_word_mode(config.word_mode);
+    ui_prompt.set_completer(Completer { tabs: tabs.clone() });
+    // --review goes without --tab, so it is of the first
+    let reviewer = Reviewer {
+        review: ui_tabs.processes[0].review.clone(),
+        viewport: ui_tabs.processes[0].viewport.clone(),
+        status: tabs.tabs[0].status.clone(),
+        redraw_tx: redraw_tx.clone(),
+    };
+    ui_prompt.set_review(reviewer.review.clone());
+    let mut ui_header = UiHeader {
+        text: config.header.clone(),
+        output: sync::Arc::new(sync::Mutex::new(Vec::new())),
+        tabs: tabs.clone(),
+    };
+    // not re-run on queries, the program does not wait for it
+    if let Some(header_command) = config.header_command.clone() {
+        let output = ui_header.output.clone();
+        let redraw_tx = redraw_tx.clone();
+        thread::spawn(move || {
+            *output.lock().unwrap() = run_header_command(&header_command);
+            let _ = redraw_tx.send();
+        });
+    }
+    let mut ui_footer = UiFooter::new(tabs.clone());
+    let mut accepted_with = None;
+
+    let mut renderer = terminal::TerminalRenderer::new(
+        vec![
+            terminal::Component::Prompt(&mut ui_prompt),
+            terminal::Component::Header(&mut ui_header),
+            terminal::Component::Data(&mut ui_tabs),
+            terminal::Component::Footer(&mut ui_footer),
+        ],
+        redraw_rx,
+    )?;
+    renderer.set_separator(config.separator.clone());
+    renderer.set_redraw(config.redraw);
+    renderer.set_wrap_prompt(config.wrap_prompt);
+    renderer.set_border(config.border);
+    renderer.set_ruler(config.ruler);
+    renderer.set_preview_window(config.preview_window);
+    renderer.set_alternate_screen(config.alternate_screen);
+    renderer.set_max_data_height(config.max_height);
+    renderer.set_prompt_styles(config.prompt_style.clone(), config.query_style.clone());
+    renderer.set_header_style(config.header_style.clone());
+    renderer.set_color_rules(config.color_rules.clone());
+    renderer.set_control_mode(config.control);
+    renderer.set_size_poll(config.size_poll);
+    renderer.set_keys(config.keys.iter().map(|v| v.to_input()).collect());
+    if let Some(placeholder) = &config.placeholder {
+        renderer.set_placeholder(placeholder.clone(), config.placeholder_style.clone());
+    }
+    renderer.start(|input, query| {
+        let action = config.keymap.action(input);
+        // the review is only scrolled, going back to the query takes what would abort
+        if reviewer.reviewing() {
+            match action {
+                Some(keymap::Action::Abort | keymap::Action::ClearOrAbort) => {
+                    reviewer.stop();
+                    return terminal::InputOutcome::Consumed;
+                }
+                Some(
+                    keymap::Action::Accept
+                    | keymap::Action::ScrollUp
+                    | keymap::Action::ScrollDown
+                    | keymap::Action::PageUp
+                    | keymap::Action::PageDown
+                    | keymap::Action::Refresh
+                    | keymap::Action::ToggleRuler,
+                ) => {}
+                _ => return terminal::InputOutcome::Consumed,
+            }
+        }
+
+        match action {
+            Some(keymap::Action::Accept)
+                if !accepts(&config, query, tabs.active().data.load().is_empty()) =>
+            {
+                terminal::InputOutcome::Consumed
+            }
+            Some(keymap::Action::Accept) if config.review && !reviewer.reviewing() => {
+                let captured = (config.accept_output == config::AcceptOutput::Captured)
+                    .then(|| captured_output(&config, tabs.active().data.load()));
+                reviewer.start(&config, query, stdin_input.clone(), captured);
+                terminal::InputOutcome::Consumed
+            }
+            Some(keymap::Action::Accept) => {
+                accepted_with = keymap::Key::from_input(input);
+                terminal::InputOutcome::Stop
+            }
+            Some(keymap::Action::Abort) => terminal::InputOutcome::Stop,
+            Some(keymap::Action::ClearOrAbort) if query.is_empty() => terminal::InputOutcome::Stop,
+            Some(keymap::Action::Paste) => terminal::InputOutcome::RequestPaste,
+            Some(keymap::Action::Refresh) => terminal::InputOutcome::Refresh,
+            Some(keymap::Action::ToggleRuler) => terminal::InputOutcome::ToggleRuler,
+            Some(keymap::Action::NextTab) => {
+                tabs.next();
+                terminal::InputOutcome::Consumed
+            }
+            Some(keymap::Action::Execute(index)) => {
+                let cmd = config.keymap.command(index).to_string();
+                let query = query.to_string();
+                let status = tabs.active().status.clone();
+                let redraw_tx = redraw_tx.clone();
+                terminal::InputOutcome::HandOff(Box::new(move || {
+                    let action = execute_action(&cmd, &query);
+                    status.lock().unwrap().action = Some((action, time::Instant::now()));
+                    // to take it off the footer again
+                    thread::spawn(move || {
+                        thread::sleep(ACTION_SHOWN);
+                        let _ = redraw_tx.send();
+                    });
+                }))
+            }
+            _ => terminal::InputOutcome::Continue,
+        }
+    })?;
+
+    ui_tabs.kill_running();
+
+    // --auto-select accepts the same as enter would
+    if ui_tabs.stopped.load(sync::atomic::Ordering::Relaxed) {
+        accepted_with = Some(keymap::Key::Enter);
+    }
+
+    // failing to remember it is not worth failing the accept for
+    if let Some(state_dir) = &state_dir {
+        let _ = state::save_query(state_dir, &bin, &bin_args, &ui_prompt.get_string());
+    }
+
+    // accepted output printed to the terminal already is the same output again
+    let accepted_to_terminal =
+        accepted_with.is_some() && output_file.is_none() && terminal::isatty(libc::STDOUT_FILENO);
+    if config.keep_output && !accepted_to_terminal {
+        keep_output(&ui_tabs.shown())?;
+    }
+
+    // the tab shown is what gets accepted
+    if let Some(key) = accepted_with {
+        let query = ui_prompt.get_string();
+        let config = &configs[tabs.index()];
+        let accepted = match (config.select, config.accept_output, reviewer.reviewed()) {
+            (true, _, _) => Accepted::Selected(ui_tabs.selection()),
+            // what was reviewed, whether or not its command finished
+            (false, _, Some(reviewed)) => Accepted::Captured(reviewed),
+            (false, config::AcceptOutput::Captured, None) => {
+                Accepted::Captured(captured_output(config, tabs.active().data.load()))
+            }
+            (false, config::AcceptOutput::Run, None) => Accepted::Run(stdin_input),
+        };
+        return accept_into(
+            config,
+            key,
+            &tabs.active().label,
+            &query,
+            accepted,
+            output_file,
+        );
+    }
+
+    Ok(0)
+}
+
+// as --accept-output captured prints it, last first with --tac-output
+fn captured_output(config: &config::Config, captured: sync::Arc<Vec<u8>>) -> sync::Arc<Vec<u8>> {
+    match config.tac_output {
+        true => {
+            let delimiter = config.delimiter.as_deref().unwrap_or(b"\n");
+            sync::Arc::new(reverse_records(&captured, delimiter))
+        }
+        false => captured,
+    }
+}
+
+// to stdout or --output
+fn accept_into(
+    config: &config::Config,
+    key: keymap::Key,
+    cmd: &str,
+    query: &str,
+    accepted: Accepted,
+    output_file: Option<output::AtomicFile>,
+) -> Result<i32> {
+    let Some(output_file) = output_file else {
+        return accept(config, key, cmd, query, accepted, io::stdout(), true);
+    };
+
+    // a failed or cancelled run leaves the file as it was
+    let code = accept(
+        config,
+        key,
+        cmd,
+        query,
+        accepted,
+        output_file.file()?,
+        false,
+    )?;
+    if code == 0 {
+        output_file.commit()?;
+    }
+    Ok(code)
+}
+
+fn main() {
+    match main_err() {
+        Ok(v) => process::exit(v),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use terminal::ComponentPrompt;
+
+    #[test]
+    fn prompt_render_multibyte() {
+        let (tx, _rx) = sync::mpsc::channel();
+        let mut prompt = UiPrompt::new(
+            tx,
+            "grep ž".to_string(),
+            keymap::Keymap::default(),
+            config::DEFAULT_MAX_QUERY_LENGTH,
+        );
+        for ch in "日本".chars() {
+            prompt.add_character(ch);
+        }
+        prompt.move_cursor(-1);
+
+        let out = prompt.render();
+        assert_eq!(out.query.iter().collect::<String>(), "[grep ž]> 日本");
+        // cursor index counts characters, not bytes
+        assert_eq!(out.query[out.cursor_index], '本');
+    }
+
+    #[test]
+    fn prompt_insert_tab() {
+        let (tx, rx) = sync::mpsc::channel();
+        let tab = terminal::TerminalInput::Ctrl(b'i');
+        let mut prompt = UiPrompt::new(
+            tx.clone(),
+            "grep".to_string(),
+            keymap::Keymap::default(),
+            config::DEFAULT_MAX_QUERY_LENGTH,
+        );
+        prompt.input(&tab).unwrap();
+        assert_eq!(prompt.get_string(), "");
+
+        let mut keymap = keymap::Keymap::default();
+        keymap.bind("tab:insert-tab").unwrap();
+        let mut prompt = UiPrompt::new(
+            tx,
+            "grep".to_string(),
+            keymap,
+            config::DEFAULT_MAX_QUERY_LENGTH,
+        );
+        prompt
+            .input(&terminal::TerminalInput::Printable('a'))
+            .unwrap();
+        prompt.input(&tab).unwrap();
+        assert_eq!(prompt.get_string(), "a\t");
+        assert_eq!(rx.try_iter().last().unwrap(), "a\t");
+
+        let out = prompt.render();
+        assert_eq!(out.query[out.prompt_len..].iter().collect::<String>(), "a⇥");
+    }
+
+    #[test]
+    fn prompt_complete() {
+        let (tx, rx) = sync::mpsc::channel();
+        let data = sync::Arc::new(data::SharedData::new(false));
+        let mut writer = data::DataWriter::new(data.clone());
+        writer.push(b"src/main.rs src/config.rs\nREADME.md src/main.rs\n");
+        writer.flush();
+
+        let status = sync::Arc::new(sync::Mutex::new(UiStatus::default()));
+        let mut prompt = UiPrompt::new(
+            tx,
+            "rg".to_string(),
+            keymap::Keymap::default(),
+            config::DEFAULT_MAX_QUERY_LENGTH,
+        );
+        prompt.set_completer(Completer {
+            tabs: Tabs::new(vec![Tab {
+                label: "rg".to_string(),
+                data,
+                status: status.clone(),
+            }]),
+        });
+        let tab = terminal::TerminalInput::Ctrl(b'i');
+
+        for ch in "x R".chars() {
+            prompt
+                .input(&terminal::TerminalInput::Printable(ch))
+                .unwrap();
+        }
+        prompt.input(&tab).unwrap();
+        assert_eq!(prompt.get_string(), "x README.md");
+
+        // ambiguous, completed to the shared prefix with the candidates listed
+        for ch in " s".chars() {
+            prompt
+                .input(&terminal::TerminalInput::Printable(ch))
+                .unwrap();
+        }
+        prompt.input(&tab).unwrap();
+        assert_eq!(prompt.get_string(), "x README.md src/");
+        assert_eq!(
+            status.lock().unwrap().completions.as_deref(),
+            Some("src/config.rs src/main.rs")
+        );
+
+        prompt.input(&tab).unwrap();
+        assert_eq!(prompt.get_string(), "x README.md src/config.rs");
+        prompt.input(&tab).unwrap();
+        assert_eq!(prompt.get_string(), "x README.md src/main.rs");
+        prompt.input(&tab).unwrap();
+        assert_eq!(prompt.get_string(), "x README.md src/config.rs");
+        assert_eq!(rx.try_iter().last().unwrap(), "x README.md src/config.rs");
+
+        prompt.input(&terminal::TerminalInput::Delete).unwrap();
+        assert_eq!(status.lock().unwrap().completions, None);
+    }
+
+    #[test]
+    fn prompt_clear_or_abort() {
+        let (tx, rx) = sync::mpsc::channel();
+        let mut keymap = keymap::Keymap::default();
+        keymap.bind("esc:clear-or-abort").unwrap();
+        let mut prompt = UiPrompt::new(
+            tx,
+            "grep".to_string(),
+            keymap,
+            config::DEFAULT_MAX_QUERY_LENGTH,
+        );
+        let esc = terminal::TerminalInput::Escape(terminal::TerminalEscape::Timeout);
+        for ch in "abc".chars() {
+            prompt
+                .input(&terminal::TerminalInput::Printable(ch))
+                .unwrap();
+        }
+        assert_eq!(rx.try_iter().last().unwrap(), "abc");
+
+        // the first press clears, the next one aborts as the query is empty
+        prompt.input(&esc).unwrap();
+        assert_eq!(prompt.get_string(), "");
+        assert_eq!(prompt.render().cursor_index, prompt.render().prompt_len);
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![""]);
+
+        prompt.input(&esc).unwrap();
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn prompt_overwrite() {
+        let (tx, _rx) = sync::mpsc::channel();
+        let mut prompt = UiPrompt::new(
+            tx,
+            "grep".to_string(),
+            keymap::Keymap::default(),
+            config::DEFAULT_MAX_QUERY_LENGTH,
+        );
+        let insert = terminal::TerminalInput::Escape(terminal::TerminalEscape::Insert);
+        for ch in "abc".chars() {
+            prompt.add_character(ch);
+        }
+
+        prompt.input(&insert).unwrap();
+        assert!(prompt.render().overwrite);
+        prompt.move_cursor(-2);
+        prompt.add_character('x');
+        assert_eq!(prompt.get_string(), "axc");
+
+        // past the end overwrite appends
+        prompt.move_cursor(1);
+        prompt.add_character('y');
+        prompt.add_character('z');
+        assert_eq!(prompt.get_string(), "axcyz");
+
+        prompt.input(&insert).unwrap();
+        assert!(!prompt.render().overwrite);
+        prompt.move_cursor(-1);
+        prompt.add_character('w');
+        assert_eq!(prompt.get_string(), "axcywz");
+    }
+
+    #[test]
+    fn prompt_delete_word() {
+        let (tx, _rx) = sync::mpsc::channel();
+        let mut prompt = UiPrompt::new(
+            tx,
+            "grep".to_string(),
+            keymap::Keymap::default(),
+            config::DEFAULT_MAX_QUERY_LENGTH,
+        );
+        for ch in "foo bar baz".chars() {
+            prompt.add_character(ch);
+        }
+
+        prompt.delete_word_backward();
+        assert_eq!(prompt.get_string(), "foo bar ");
+        prompt.delete_word_backward();
+        assert_eq!(prompt.get_string(), "foo ");
+
+        prompt.move_cursor(-4);
+        prompt.delete_word_forward();
+        assert_eq!(prompt.get_string(), " ");
+        assert_eq!(prompt.cursor_index, 0);
+        prompt.delete_word_backward();
+        assert_eq!(prompt.get_string(), " ");
+    }
+
+    #[test]
+    fn prompt_word_modes() {
+        let edited = |word_mode, edit: fn(&mut UiPrompt)| {
+            let (tx, _rx) = sync::mpsc::channel();
+            let mut prompt = UiPrompt::new(
+                tx,
+                "grep".to_string(),
+                keymap::Keymap::default(),
+                config::DEFAULT_MAX_QUERY_LENGTH,
+            );
+            prompt.set_word_mode(word_mode);
+            prompt.set_query("src/main.rs --flag=some-value");
+            edit(&mut prompt);
+            (prompt.get_string(), prompt.cursor_index)
+        };
+
+        let word = config::WordMode::Word;
+        let whitespace = config::WordMode::Whitespace;
+        assert_eq!(
+            edited(word, UiPrompt::delete_word_backward),
+            ("src/main.rs --flag=some-".to_string(), 24)
+        );
+        assert_eq!(
+            edited(whitespace, UiPrompt::delete_word_backward),
+            ("src/main.rs ".to_string(), 12)
+        );
+
+        let forward = |prompt: &mut UiPrompt| {
+            prompt.cursor_index = 0;
+            prompt.delete_word_forward();
+        };
+        assert_eq!(
+            edited(word, forward),
+            ("/main.rs --flag=some-value".to_string(), 0)
+        );
+        assert_eq!(
+            edited(whitespace, forward),
+            (" --flag=some-value".to_string(), 0)
+        );
+
+        let upper = |prompt: &mut UiPrompt| {
+            prompt.cursor_index = 12;
+            prompt.change_word_case(WordCase::Upper);
+        };
+        assert_eq!(
+            edited(word, upper),
+            ("src/main.rs --FLAG=some-value".to_string(), 18)
+        );
+        assert_eq!(
+            edited(whitespace, upper),
+            ("src/main.rs --FLAG=SOME-VALUE".to_string(), 29)
+        );
+    }
+
+    #[test]
+    fn prompt_transpose() {
+        let transposed = |query: &str, cursor_index: usize| {
+            let (tx, _rx) = sync::mpsc::channel();
+            let mut prompt = UiPrompt::new(
+                tx,
+                "grep".to_string(),
+                keymap::Keymap::default(),
+                config::DEFAULT_MAX_QUERY_LENGTH,
+            );
+            prompt.query = query.chars().collect();
+            prompt.cursor_index = cursor_index;
+            prompt.transpose_characters();
+            (prompt.get_string(), prompt.cursor_index)
+        };
+
+        assert_eq!(transposed("ab", 2), ("ba".to_string(), 2));
+        assert_eq!(transposed("ab", 1), ("ba".to_string(), 2));
+        assert_eq!(transposed("abc", 1), ("bac".to_string(), 2));
+        assert_eq!(transposed("a", 0), ("a".to_string(), 0));
+        assert_eq!(transposed("a", 1), ("a".to_string(), 1));
+        assert_eq!(transposed("ab", 0), ("ab".to_string(), 0));
+    }
+
+    #[test]
+    fn prompt_change_word_case() {
+        let changed = |query: &str, cursor_index: usize, cases: &[WordCase]| {
+            let (tx, _rx) = sync::mpsc::channel();
+            let mut prompt = UiPrompt::new(
+                tx,
+                "grep".to_string(),
+                keymap::Keymap::default(),
+                config::DEFAULT_MAX_QUERY_LENGTH,
+            );
+            prompt.query = query.chars().collect();
+            prompt.cursor_index = cursor_index;
+            for case in cases {
+                prompt.change_word_case(*case);
+            }
+            (prompt.get_string(), prompt.cursor_index)
+        };
+
+        assert_eq!(
+            changed("foo bar", 0, &[WordCase::Upper]),
+            ("FOO bar".to_string(), 3)
+        );
+        // from the middle of a word, then over the separator into the next one
+        assert_eq!(
+            changed("fOO-BAR baz", 1, &[WordCase::Lower, WordCase::Lower]),
+            ("foo-bar baz".to_string(), 7)
+        );
+        assert_eq!(
+            changed(
+                "  hELLO, wORLD",
+                0,
+                &[WordCase::Capitalize, WordCase::Capitalize]
+            ),
+            ("  Hello, World".to_string(), 14)
+        );
+        // ß uppercases into two characters
+        assert_eq!(
+            changed("straße x", 0, &[WordCase::Upper]),
+            ("STRASSE x".to_string(), 7)
+        );
+        assert_eq!(
+            changed("foo", 3, &[WordCase::Upper]),
+            ("foo".to_string(), 3)
+        );
+    }
+
+    #[test]
+    fn prompt_max_query_length() {
+        let (tx, rx) = sync::mpsc::channel();
+        let mut prompt = UiPrompt::new(tx, "grep".to_string(), keymap::Keymap::default(), 3);
+        for ch in "abcd".chars() {
+            prompt
+                .input(&terminal::TerminalInput::Printable(ch))
+                .unwrap();
+        }
+        assert_eq!(prompt.get_string(), "abc");
+        assert_eq!(prompt.cursor_index, 3);
+        // the refused character does not trigger a run
+        assert_eq!(rx.try_iter().count(), 3);
+
+        // overwriting does not grow the query
+        prompt.overwrite = true;
+        prompt.move_cursor(-1);
+        prompt.add_character('x');
+        assert_eq!(prompt.get_string(), "abx");
+    }
+
+    #[test]
+    fn prompt_numeric_arg() {
+        let (tx, rx) = sync::mpsc::channel();
+        let mut prompt = UiPrompt::new(
+            tx,
+            "grep".to_string(),
+            keymap::Keymap::default(),
+            config::DEFAULT_MAX_QUERY_LENGTH,
+        );
+        let mut input = |inputs: &[terminal::TerminalInput]| {
+            for input in inputs {
+                prompt.input(input).unwrap();
+            }
+            (prompt.get_string(), prompt.cursor_index)
+        };
+        let alt = terminal::TerminalInput::Alt;
+        let left = || terminal::TerminalInput::Escape(terminal::TerminalEscape::LeftArrow);
+
+        let typed = "one two three four five six seven eight nine ten eleven twelve"
+            .chars()
+            .map(terminal::TerminalInput::Printable)
+            .collect::<Vec<_>>();
+        input(&typed);
+        rx.try_iter().count();
+
+        // a repeated edit sends the query once
+        let (query, _) = input(&[alt('3'), terminal::TerminalInput::Ctrl(b'w')]);
+        assert_eq!(query, "one two three four five six seven eight nine ");
+        assert_eq!(rx.try_iter().count(), 1);
+
+        // multi digit counts
+        let (_, cursor_index) = input(&[alt('1'), alt('2'), left()]);
+        assert_eq!(cursor_index, query.len() - 12);
+
+        // zero repeats nothing, the count only applies to the next edit
+        let (query, cursor_index) = input(&[alt('0'), terminal::TerminalInput::Delete]);
+        assert_eq!(query, "one two three four five six seven eight nine ");
+        assert_eq!(cursor_index, query.len() - 12);
+        let (_, next_cursor_index) = input(&[left()]);
+        assert_eq!(next_cursor_index, cursor_index - 1);
+        assert_eq!(rx.try_iter().count(), 0);
+    }
+
+    #[test]
+    fn render_diff_colors() {
+        assert_eq!(
+            render_diff(b"a\nb", b"a\nc"),
+            b"a\n\x1b[31mb\x1b[39m\n\x1b[32mc\x1b[39m"
+        );
+    }
+
+    #[test]
+    fn render_json_lines() {
+        let data = b"{\"name\":\"web\",\"n\":1}\nnot json\n{\"n\":2}";
+        assert_eq!(render_json_key(data, "name"), b"web\nnot json\n{\"n\":2}");
+        assert_eq!(
+            render_json_preview(b"{\"n\":[1]}"),
+            b"{\n  \"n\": [\n    1\n  ]\n}"
+        );
+        assert_eq!(render_json_preview(b"{\"n\":"), b"{\"n\":");
+    }
+
+    #[test]
+    fn pipe_cmd_broken_pipe() {
+        let mut head = process::Command::new("head")
+            .args(["-n", "1"])
+            .stdin(process::Stdio::piped())
+            .stdout(process::Stdio::null())
+            .spawn()
+            .unwrap();
+        let head_stdin = head.stdin.take().unwrap();
+
+        let code = pipe_cmd(
+            "yes",
+            &[],
+            "",
+            config::QueryMode::Append,
+            None,
+            head_stdin,
+            io::sink(),
+        )
+        .unwrap();
+        assert_eq!(code, BROKEN_PIPE_EXIT_CODE);
+        assert!(head.wait().unwrap().success());
+    }
+
+    #[test]
+    fn accept_output_write_error() {
+        // e.g a full disk
+        struct FullDisk;
+        impl Write for FullDisk {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::from_raw_os_error(libc::ENOSPC))
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let dir = std::env::temp_dir().join(format!("tip-accept-error-test-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.txt");
+        fs::write(&path, "old").unwrap();
+
+        // both the program and an accept command, printing forever
+        for args in [&["yes"][..], &["--accept-command", "yes", "false"]] {
+            let config = config::Config::parse(args.iter().map(|v| v.to_string())).unwrap();
+            let output_file = output::AtomicFile::create(&path).unwrap();
+            let err = accept(
+                &config,
+                keymap::Key::Enter,
+                "",
+                "",
+                Accepted::Run(None),
+                FullDisk,
+                false,
+            )
+            .unwrap_err();
+            assert!(err.to_string().contains("No space left on device"));
+            // the error returns before accept_into commits it
+            drop(output_file);
+            assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+            assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn accept_select() {
+        let config = config::Config::parse(
+            ["--select", "--expect", "ctrl-y", "false"]
+                .iter()
+                .map(|v| v.to_string()),
+        )
+        .unwrap();
+        let output = SharedBuf::default();
+        let code = accept(
+            &config,
+            keymap::Key::Ctrl('y'),
+            "",
+            "",
+            Accepted::Selected(Some(b"2 db down".to_vec())),
+            output.clone(),
+            false,
+        )
+        .unwrap();
+        // the program, false, does not run again
+        assert_eq!(code, 0);
+        assert_eq!(*output.0.lock().unwrap(), b"ctrl-y\n2 db down\n");
+
+        let code = accept(
+            &config,
+            keymap::Key::Enter,
+            "",
+            "",
+            Accepted::Selected(None),
+            io::sink(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(code, CANCEL_EXIT_CODE);
+    }
+
+    #[test]
+    fn uncaptured_stderr_is_not_drawn_over() {
+        assert!(inherits_stderr(config::Uncaptured::Inherit, false));
+        assert!(!inherits_stderr(config::Uncaptured::Inherit, true));
+        assert!(!inherits_stderr(config::Uncaptured::Null, false));
+    }
+
+    #[test]
+    fn accept_runs_the_hooked_query() {
+        let config = config::Config::parse(
+            [
+                "--query-hook",
+                "test \"$1\" != skip && echo \"$1!\"",
+                "echo",
+            ]
+            .map(String::from),
+        )
+        .unwrap();
+        let accepted = |query: &str| {
+            let output = SharedBuf::default();
+            let code = accept(
+                &config,
+                keymap::Key::Enter,
+                "echo",
+                query,
+                Accepted::Run(None),
+                output.clone(),
+                false,
+            )
+            .unwrap();
+            let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+            (code, output)
+        };
+        assert_eq!(accepted("hi"), (0, "hi!\n".to_string()));
+        // skipped by the hook, not run unhooked either
+        assert_eq!(accepted("skip"), (CANCEL_EXIT_CODE, String::new()));
+    }
+
+    #[test]
+    fn shortcut_select_1_exit_0() {
+        let shortcut_with = |args: &[&str]| {
+            let config = config::Config::parse(args.iter().map(|v| v.to_string())).unwrap();
+            shortcut(&config, None).unwrap()
+        };
+
+        assert_eq!(shortcut_with(&["printf", "a\\nb\\n"]), None);
+        assert_eq!(shortcut_with(&["--select-1", "printf", "a\\nb\\n"]), None);
+        assert_eq!(
+            shortcut_with(&["--select-1", "printf", "a\\n"]),
+            Some(Shortcut::Accept(Accepted::Run(None)))
+        );
+        assert_eq!(
+            shortcut_with(&[
+                "--select-1",
+                "--select",
+                "--delimiter",
+                ",",
+                "printf",
+                "a\\nb,"
+            ]),
+            Some(Shortcut::Accept(Accepted::Selected(Some(b"a\nb".to_vec()))))
+        );
+        // only the lines the filter shows count
+        assert_eq!(
+            shortcut_with(&[
+                "--select-1",
+                "--select",
+                "--filter",
+                "hide",
+                "--query",
+                "b",
+                "printf",
+                "a\\nb\\n"
+            ]),
+            Some(Shortcut::Accept(Accepted::Selected(Some(b"b".to_vec()))))
+        );
+
+        assert_eq!(
+            shortcut_with(&["--exit-0", "true"]),
+            Some(Shortcut::Exit(CANCEL_EXIT_CODE))
+        );
+        assert_eq!(
+            shortcut_with(&[
+                "--select-1",
+                "--accept-output",
+                "captured",
+                "printf",
+                "a\\n"
+            ]),
+            Some(Shortcut::Accept(Accepted::Captured(sync::Arc::new(
+                b"a\n".to_vec()
+            ))))
+        );
+        assert_eq!(shortcut_with(&["--select-1", "true"]), None);
+        assert_eq!(shortcut_with(&["--exit-0", "printf", "a\\n"]), None);
+    }
+
+    // the exit code and what accepting with the given arguments prints
+    fn accepted(args: &[&str], query: &str, accepted: Accepted) -> (i32, Vec<u8>) {
+        let config = config::Config::parse(args.iter().map(|v| v.to_string())).unwrap();
+        let output = SharedBuf::default();
+        let code = accept(
+            &config,
+            keymap::Key::Enter,
+            "",
+            query,
+            accepted,
+            output.clone(),
+            false,
+        )
+        .unwrap();
+        (code, output.0.lock().unwrap().clone())
+    }
+
+    #[test]
+    fn accept_empty_query() {
+        assert_eq!(
+            accepted(&["echo", "hi"], "", Accepted::Run(None)),
+            (0, b"hi\n".to_vec())
+        );
+        assert_eq!(
+            accepted(
+                &["--empty-accept", "run", "echo", "hi"],
+                "",
+                Accepted::Run(None)
+            ),
+            (0, b"hi\n".to_vec())
+        );
+        assert_eq!(
+            accepted(
+                &["--empty-accept", "cancel", "echo", "hi"],
+                "",
+                Accepted::Run(None)
+            ),
+            (CANCEL_EXIT_CODE, vec![])
+        );
+        assert_eq!(
+            accepted(
+                &["--empty-accept", "none", "--expect", "ctrl-y", "echo", "hi"],
+                "",
+                Accepted::Run(None)
+            ),
+            (0, vec![])
+        );
+        // a query is accepted as usual
+        assert_eq!(
+            accepted(
+                &["--empty-accept", "cancel", "echo", "hi"],
+                "there",
+                Accepted::Run(None)
+            ),
+            (0, b"hi there\n".to_vec())
+        );
+    }
+
+    #[test]
+    fn accept_captured_output() {
+        // the program, false, does not run again
+        let captured = || Accepted::Captured(sync::Arc::new(b"shown\n".to_vec()));
+        assert_eq!(
+            accepted(&["false"], "hi", captured()),
+            (0, b"shown\n".to_vec())
+        );
+        assert_eq!(
+            accepted(&["--empty-accept", "cancel", "false"], "", captured()),
+            (CANCEL_EXIT_CODE, vec![])
+        );
+        // while running it again prints what it prints now
+        assert_eq!(
+            accepted(&["echo"], "now", Accepted::Run(None)),
+            (0, b"now\n".to_vec())
+        );
+    }
+
+    #[test]
+    fn accept_command_instead_of_program() {
+        // the program, echo filter, does not run again
+        let command = "echo \"open $1\"; exit 3";
+        assert_eq!(
+            accepted(
+                &["--accept-command", command, "echo", "filter"],
+                "hi there",
+                Accepted::Run(None)
+            ),
+            (3, b"open hi there\n".to_vec())
+        );
+        // the selected record is $2
+        assert_eq!(
+            accepted(
+                &[
+                    "--select",
+                    "--accept-command",
+                    "echo \"$1|$2\"",
+                    "echo",
+                    "filter"
+                ],
+                "q",
+                Accepted::Selected(Some(b"b c".to_vec()))
+            ),
+            (0, b"q|b c\n".to_vec())
+        );
+        // and the captured output its stdin
+        assert_eq!(
+            accepted(
+                &["--accept-command", "tr a-z A-Z", "echo", "filter"],
+                "q",
+                Accepted::Captured(sync::Arc::new(b"shown\n".to_vec()))
+            ),
+            (0, b"SHOWN\n".to_vec())
+        );
+        // an empty query is still up to --empty-accept
+        assert_eq!(
+            accepted(
+                &[
+                    "--empty-accept",
+                    "cancel",
+                    "--accept-command",
+                    "echo",
+                    "true"
+                ],
+                "",
+                Accepted::Run(None)
+            ),
+            (CANCEL_EXIT_CODE, vec![])
+        );
+    }
+
+    #[test]
+    fn accepts_empty_accept_ignore() {
+        let accepts_with = |args: &[&str], query: &str, output_empty: bool| {
+            let config = config::Config::parse(args.iter().map(|v| v.to_string())).unwrap();
+            accepts(&config, query, output_empty)
+        };
+
+        assert!(accepts_with(&["echo"], "", true));
+        assert!(accepts_with(
+            &["--empty-accept", "cancel", "echo"],
+            "",
+            true
+        ));
+
+        let ignore = ["--empty-accept", "ignore", "echo"];
+        assert!(!accepts_with(&ignore, "", false));
+        assert!(accepts_with(&ignore, "hi", true));
+
+        let select = ["--empty-accept", "ignore", "--select", "echo"];
+        assert!(!accepts_with(&select, "hi", true));
+        assert!(accepts_with(&select, "", false));
+    }
+
+    #[test]
+    fn read_input_progress() {
+        // a slow producer of 2MB
+        struct Slow(usize);
+        impl Read for Slow {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.0 == 0 {
+                    return Ok(0);
+                }
+                thread::sleep(time::Duration::from_millis(20));
+                self.0 -= 1;
+                buf[..50_000].fill(b'x');
+                Ok(50_000)
+            }
+        }
+
+        let progress = SharedBuf::default();
+        let data = read_input(Slow(40), Some(progress.clone()), time::Duration::ZERO).unwrap();
+        assert_eq!(data.len(), 2_000_000);
+        let progress = String::from_utf8(progress.0.lock().unwrap().clone()).unwrap();
+        assert!(
+            progress.starts_with("\rtip: reading input… 0."),
+            "{}",
+            progress
+        );
+        assert!(
+            progress.contains(" MB\rtip: reading input… 1."),
+            "{}",
+            progress
+        );
+        assert!(progress.ends_with(" MB\r\x1b[K"));
+
+        // quick reads show nothing
+        let progress = SharedBuf::default();
+        let data = read_input(&b"abc"[..], Some(progress.clone()), INPUT_PROGRESS_DELAY).unwrap();
+        assert_eq!(data, b"abc");
+        assert!(progress.0.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn ui_status_finish_run() {
+        let mut status = UiStatus::default();
+        let previous = time::Instant::now();
+        let current = previous + time::Duration::from_millis(1);
+
+        status.run = Some(RunStatus::Running(current));
+        status.finish_run(previous);
+        assert_eq!(status.run, Some(RunStatus::Running(current)));
+
+        status.finish_run(current);
+        assert!(matches!(status.run, Some(RunStatus::Finished(_))));
+
+        assert_eq!(format_elapsed(time::Duration::from_millis(340)), "340ms");
+        assert_eq!(format_elapsed(time::Duration::from_millis(1250)), "1.2s");
+    }
+
+    #[test]
+    fn viewport_move_cursor() {
+        let viewport = Viewport::default();
+        let viewport = viewport.move_cursor(4, 3, 10);
+        assert_eq!(
+            viewport,
+            Viewport {
+                scroll: 2,
+                cursor: 4
+            }
+        );
+        let viewport = viewport.move_cursor(-1, 3, 10);
+        assert_eq!(
+            viewport,
+            Viewport {
+                scroll: 2,
+                cursor: 3
+            }
+        );
+        let viewport = viewport.move_cursor(-3, 3, 10);
+        assert_eq!(
+            viewport,
+            Viewport {
+                scroll: 0,
+                cursor: 0
+            }
+        );
+        assert_eq!(
+            viewport.move_cursor(100, 3, 10),
+            Viewport {
+                scroll: 7,
+                cursor: 9
+            }
+        );
+    }
+
+    #[test]
+    fn viewport_at_bottom() {
+        let viewport = Viewport {
+            scroll: 90,
+            cursor: 95,
+        };
+        assert!(viewport.at_bottom(false, 10, 100));
+        assert!(!viewport.at_bottom(true, 10, 100));
+        assert!(!viewport.at_bottom(false, 5, 100));
+
+        // scrolling up from the bottom pauses, coming back down resumes
+        let up = viewport.move_cursor(-1, 10, 100);
+        assert!(!up.at_bottom(true, 10, 100));
+        let down = up.move_cursor(10, 10, 100);
+        assert!(down.at_bottom(true, 10, 100));
+
+        // output shorter than the pane is always at the bottom
+        assert!(Viewport::default().at_bottom(false, 10, 3));
+    }
+
+    #[test]
+    fn scroll_viewport_initial_scroll() {
+        let start = |initial_scroll| Follow::Off.reset(initial_scroll);
+
+        // output of 100 lines in a pane of 10, starting at the top scrolls from there
+        let follow = start(config::InitialScroll::Top);
+        assert!(!follow.pinned());
+        let (viewport, follow) = scroll_viewport(Viewport::default(), follow, false, 1, 10, 100);
+        assert_eq!((viewport.scroll, follow), (1, Follow::Off));
+
+        // starting at the bottom scrolls from the bottom, and stays put after
+        let follow = start(config::InitialScroll::Bottom);
+        assert!(follow.pinned());
+        let (viewport, follow) = scroll_viewport(Viewport::default(), follow, false, -1, 10, 100);
+        assert_eq!((viewport.scroll, follow), (89, Follow::Off));
+        let (viewport, follow) = scroll_viewport(viewport, follow, false, 1, 10, 100);
+        assert_eq!((viewport.scroll, follow), (90, Follow::Off));
+
+        // an explicit --follow wins over the initial scroll
+        assert_eq!(
+            Follow::Paused.reset(config::InitialScroll::Bottom),
+            Follow::Paused
+        );
+        assert_eq!(
+            Follow::Following.reset(config::InitialScroll::Top),
+            Follow::Following
+        );
+        let (viewport, follow) =
+            scroll_viewport(Viewport::default(), Follow::Following, true, -1, 10, 100);
+        assert_eq!((viewport.cursor, follow), (98, Follow::Paused));
+    }
+
+    #[test]
+    fn read_child_stream_no_output() {
+        let data = sync::Arc::new(data::SharedData::new(false));
+        data::DataWriter::new(data.clone()).push(b"previous");
+        let (redraw_tx, _redraw_rx) = terminal::redraw_channel();
+
+        let mut child = process::Command::new("true")
+            .stdout(process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut writer = data::DataWriter::new(data.clone());
+        UiWaitingProcess::read_child_stream(
+            child.stdout.take().unwrap(),
+            1024,
+            false,
+            &mut writer,
+            &redraw_tx,
+            &mut None,
+        )
+        .unwrap();
+        child.wait().unwrap();
+        assert_eq!(*data.load(), b"previous");
+
+        // the run ended without writing anything, so nothing is shown
+        writer.finish();
+        assert_eq!(*data.load(), b"");
+    }
+
+    #[test]
+    fn read_child_stream_line_buffered() {
+        let data = sync::Arc::new(data::SharedData::new(false));
+        let (redraw_tx, redraw_rx) = terminal::redraw_channel();
+        let snapshots = thread::spawn({
+            let data = data.clone();
+            move || redraw_rx.iter().map(|_| data.load()).collect::<Vec<_>>()
+        });
+
+        let mut child = process::Command::new("sh")
+            .args([
+                "-c",
+                "printf ab; sleep 0.1; printf 'c\\nd'; sleep 0.1; printf 'e\\n'; sleep 0.1; printf f",
+            ])
+            .stdout(process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut writer = data::DataWriter::new(data.clone());
+        UiWaitingProcess::read_child_stream(
+            child.stdout.take().unwrap(),
+            1024,
+            true,
+            &mut writer,
+            &redraw_tx,
+            &mut None,
+        )
+        .unwrap();
+        child.wait().unwrap();
+        drop(redraw_tx);
+
+        let snapshots = snapshots.join().unwrap();
+        let (last, shown) = snapshots.split_last().unwrap();
+        assert_eq!(**last, b"abc\nde\nf");
+        // nothing but whole lines is shown while the output is still coming
+        assert!(!shown.is_empty());
+        for snapshot in shown {
+            assert_eq!(snapshot.last(), Some(&b'\n'), "{:?}", snapshot);
+        }
+    }
+
+    #[test]
+    fn preserved_scroll_within_output() {
+        let data = b"a\nb\nc";
+        assert_eq!(preserved_scroll(2, line_count(data)), 2);
+        assert_eq!(preserved_scroll(3, line_count(data)), 0);
+        assert_eq!(preserved_scroll(1, line_count(b"")), 0);
+    }
+
+    #[test]
+    fn focused_record_nth() {
+        let records = b"1 web up\n2 db down\n3 cache up";
+        let displayed = fields::render_fields(records, &"2".parse().unwrap());
+        assert_eq!(displayed, b"web\ndb\ncache");
+
+        let records = || records.split(|v| *v == b'\n');
+        let shown = |_: &[u8]| false;
+        assert_eq!(
+            focused_record(records(), &displayed, 1, shown),
+            Some(&b"2 db down"[..])
+        );
+        // hidden lines do not count
+        let hidden = |line: &[u8]| line == b"db";
+        assert_eq!(
+            focused_record(records(), &displayed, 1, hidden),
+            Some(&b"3 cache up"[..])
+        );
+        assert_eq!(focused_record(records(), &displayed, 3, shown), None);
+    }
+
+    // a process running the program of the arguments, queried with the sender
+    fn started(args: &[&str]) -> (UiWaitingProcess, sync::mpsc::Sender<String>) {
+        let config = config::Config::parse(args.iter().map(|v| v.to_string())).unwrap();
+        let (query_tx, query_rx) = sync::mpsc::channel();
+        let (redraw_tx, redraw_rx) = terminal::redraw_channel();
+        thread::spawn(move || while redraw_rx.recv().is_ok() {});
+        let status = sync::Arc::new(sync::Mutex::new(UiStatus::default()));
+        let ui = UiWaitingProcess::new(sync::Arc::new(config), None, status, redraw_tx, query_rx);
+        (ui, query_tx)
+    }
+
+    // for what the worker threads do, failing when it does not happen in time
+    fn wait_until(done: impl Fn() -> bool) {
+        let start = time::Instant::now();
+        while !done() {
+            assert!(start.elapsed() < time::Duration::from_secs(5));
+            thread::sleep(time::Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn data_content_and_selection() {
+        let loaded = |args: &[&str]| {
+            let (ui, _query_tx) = started(args);
+            wait_until(|| !ui.data.load().is_empty());
+            ui
+        };
+
+        let ui = loaded(&[
+            "--nth",
+            "2",
+            "--select",
+            "printf",
+            "1 web up\\n2 db down\\n",
+        ]);
+        assert_eq!(*ui.content(), b"1 web up\n2 db down\n");
+        assert_eq!(*ui.shown(), b"web\ndb\n");
+        assert_eq!(ui.selection(), Some(b"1 web up".to_vec()));
+        assert_eq!(ui.position(), Some((1, 2)));
+        ui.viewport.lock().unwrap().cursor = 1;
+        assert_eq!(ui.selection(), Some(b"2 db down".to_vec()));
+        assert_eq!(ui.position(), Some((2, 2)));
+
+        // nothing is selected without a cursor line
+        let ui = loaded(&["printf", "1 web up\\n"]);
+        assert_eq!(*ui.content(), b"1 web up\n");
+        assert_eq!(*ui.shown(), b"1 web up\n");
+        assert_eq!(ui.selection(), None);
+        assert_eq!(ui.position(), None);
+
+        // the last record shows first, the selection is still the record shown
+        let ui = loaded(&[
+            "--tac",
+            "--nth",
+            "2",
+            "--select",
+            "printf",
+            "1 web up\\n2 db down\\n3 cache up\\n",
+        ]);
+        assert_eq!(*ui.shown(), b"cache\ndb\nweb\n");
+        assert_eq!(ui.selection(), Some(b"3 cache up".to_vec()));
+        ui.viewport.lock().unwrap().cursor = 2;
+        assert_eq!(ui.selection(), Some(b"1 web up".to_vec()));
+        assert_eq!(*ui.content(), b"1 web up\n2 db down\n3 cache up\n");
+
+        // hidden lines are not counted
+        let ui = loaded(&[
+            "--select", "--filter", "hide", "--query", "x", "printf", "a\\nb\\n",
+        ]);
+        assert_eq!(ui.position(), Some((0, 0)));
+
+        // the top line is counted only past the threshold
+        let ui = loaded(&["--paging-threshold", "3", "printf", "a\\nb\\nc\\n"]);
+        assert_eq!(ui.position(), None);
+        let ui = loaded(&["--paging-threshold", "3", "printf", "a\\nb\\nc\\nd\\n"]);
+        ui.viewport.lock().unwrap().scroll = 1;
+        assert_eq!(ui.position(), Some((2, 4)));
+    }
+
+    #[test]
+    fn queries_and_redraws_do_not_block() {
+        let config = config::Config::parse(["echo".to_string()]).unwrap();
+        let (query_tx, query_rx) = sync::mpsc::channel();
+        // nothing takes the redraws, as when the renderer is busy
+        let (redraw_tx, _redraw_rx) = terminal::redraw_channel();
+        let status = sync::Arc::new(sync::Mutex::new(UiStatus::default()));
+        let ui = UiWaitingProcess::new(
+            sync::Arc::new(config),
+            None,
+            status,
+            redraw_tx.clone(),
+            query_rx,
+        );
+
+        let redraws = (0..4)
+            .map(|_| {
+                let redraw_tx = redraw_tx.clone();
+                thread::spawn(move || {
+                    for _ in 0..10000 {
+                        redraw_tx.send().unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for i in 0..200 {
+            query_tx.send(format!("query{}", i)).unwrap();
+        }
+        for redraw in redraws {
+            redraw.join().unwrap();
+        }
+
+        let start = time::Instant::now();
+        while *ui.data.load() != b"query199\n" {
+            assert!(start.elapsed() < time::Duration::from_secs(10));
+            thread::sleep(time::Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn preview_debounced() {
+        let runs = std::env::temp_dir().join(format!("tip-preview-runs-{}", process::id()));
+        let _ = fs::remove_file(&runs);
+        let (redraw_tx, redraw_rx) = terminal::redraw_channel();
+        thread::spawn(move || while redraw_rx.recv().is_ok() {});
+        let preview = UiPreview::new(
+            format!("echo >> {}; echo \"preview $1\"", runs.display()),
+            child::DEFAULT_KILL_GRACE,
+            redraw_tx,
+        );
+
+        // arrow presses quicker than the debounce
+        for i in 0..10 {
+            let shown = preview.show(Some(format!("{}", i).as_bytes()));
+            assert_eq!(shown, PREVIEW_LOADING);
+            thread::sleep(time::Duration::from_millis(10));
+        }
+        wait_until(|| !preview.preview.lock().unwrap().running);
+        assert_eq!(preview.show(Some(b"9")), b"preview 9\n");
+        assert_eq!(fs::read_to_string(&runs).unwrap(), "\n");
+        let _ = fs::remove_file(&runs);
+
+        assert_eq!(preview.show(None), b"");
+    }
+
+    #[test]
+    fn preview_killed_for_the_next_record() {
+        let (redraw_tx, redraw_rx) = terminal::redraw_channel();
+        thread::spawn(move || while redraw_rx.recv().is_ok() {});
+        let preview = UiPreview::new(
+            "echo $$; exec sleep 30".to_string(),
+            child::DEFAULT_KILL_GRACE,
+            redraw_tx,
+        );
+        let shown = |record: &[u8]| {
+            wait_until(|| preview.show(Some(record)).ends_with(b"\n"));
+            String::from_utf8(preview.show(Some(record))).unwrap()
+        };
+
+        let pid = shown(b"a").trim().parse::<i32>().unwrap();
+        // the next one runs without waiting for it
+        assert_ne!(shown(b"b").trim().parse::<i32>().unwrap(), pid);
+        wait_until(|| unsafe { libc::kill(pid, 0) } != 0);
+    }
+
+    #[test]
+    fn header_text_and_output() {
+        use terminal::ComponentHeader;
+
+        let tab = |label: &str| Tab {
+            label: label.to_string(),
+            data: sync::Arc::new(data::SharedData::new(false)),
+            status: sync::Arc::new(sync::Mutex::new(UiStatus::default())),
+        };
+        let header = UiHeader {
+            text: Some("NAME  STATUS".to_string()),
+            output: sync::Arc::new(sync::Mutex::new(Vec::new())),
+            tabs: Tabs::new(vec![tab("ps")]),
+        };
+        assert_eq!(header.render().0, b"NAME  STATUS");
+        *header.output.lock().unwrap() = b"main".to_vec();
+        assert_eq!(header.render().0, b"NAME  STATUS\nmain");
+
+        // the tab bar goes above it
+        let header = UiHeader {
+            text: Some("NAME".to_string()),
+            output: sync::Arc::new(sync::Mutex::new(Vec::new())),
+            tabs: Tabs::new(vec![tab("ps"), tab("top")]),
+        };
+        assert_eq!(header.render().0, b"\x1b[7m ps \x1b[27m  top \nNAME");
+        header.tabs.next();
+        assert_eq!(header.render().0, b" ps  \x1b[7m top \x1b[27m\nNAME");
+    }
+
+    #[test]
+    fn auto_select_narrowed_to_one() {
+        let config = config::Config::parse(["--auto-select", "grep"].map(String::from)).unwrap();
+        let (query_tx, query_rx) = sync::mpsc::channel();
+        let (redraw_tx, _redraw_rx) = terminal::redraw_channel();
+        let status = sync::Arc::new(sync::Mutex::new(UiStatus::default()));
+        let ui = UiWaitingProcess::new(
+            sync::Arc::new(config),
+            Some(sync::Arc::new(b"apple\napricot\nbanana\n".to_vec())),
+            status,
+            redraw_tx,
+            query_rx,
+        );
+        let ran = |query: &str, records| {
+            query_tx.send(query.to_string()).unwrap();
+            wait_until(|| {
+                ui.position().map(|(_, total)| total) == Some(records)
+                    && matches!(ui.status.lock().unwrap().run, Some(RunStatus::Finished(_)))
+            });
+        };
+
+        ran("a", 3);
+        assert!(!ui.stops());
+        ran("ap", 2);
+        assert!(!ui.stops());
+        ran("apr", 1);
+        assert!(ui.stops());
+        assert_eq!(ui.selection(), Some(b"apricot".to_vec()));
+
+        // not while the run is still going
+        ui.status.lock().unwrap().run = Some(RunStatus::Running(time::Instant::now()));
+        assert!(!ui.stops());
+    }
+
+    #[test]
+    fn capture_modes() {
+        let captured = |capture: &str| {
+            let (ui, _query_tx) =
+                started(&["--capture", capture, "sh", "-c", "echo out; echo err >&2"]);
+            wait_until(|| matches!(ui.status.lock().unwrap().run, Some(RunStatus::Finished(_))));
+            String::from_utf8(ui.data.load().to_vec()).unwrap()
+        };
+        assert_eq!(captured("both"), "out\nerr\n");
+        assert_eq!(captured("stdout"), "out\n");
+        assert_eq!(captured("stderr"), "err\n");
+        assert!(config::Config::parse(["--capture", "all", "sh"].map(String::from)).is_err());
+    }
+
+    #[test]
+    fn min_query_length_gates_runs() {
+        let (ui, query_tx) = started(&["--min-query-len", "2", "echo"]);
+        let typed = |query: &str, short: bool, output: &[u8]| {
+            query_tx.send(query.to_string()).unwrap();
+            wait_until(|| {
+                let status = ui.status.lock().unwrap();
+                status.short_query.is_some() == short
+                    && !matches!(status.run, Some(RunStatus::Running(_)))
+                    && *ui.query.lock().unwrap() == query
+                    && *ui.data.load() == output
+            });
+        };
+
+        typed("", true, b"");
+        typed("a", true, b"");
+        typed("ab", false, b"ab\n");
+        // deleting back below it keeps the output of the last run
+        typed("a", true, b"ab\n");
+        typed("abc", false, b"abc\n");
+    }
+
+    #[test]
+    fn cancel_kills_the_run_and_idles() {
+        let (mut ui, query_tx) = started(&["sh", "-c", "echo $$; exec sleep 10"]);
+        let wait_pid = |ui: &UiWaitingProcess| {
+            wait_until(|| ui.data.load().ends_with(b"\n"));
+            String::from_utf8_lossy(&ui.data.load())
+                .trim()
+                .parse::<i32>()
+                .unwrap()
+        };
+        let pid = wait_pid(&ui);
+
+        ui.input(&terminal::TerminalInput::Ctrl(b'x'), 10).unwrap();
+        assert!(matches!(
+            ui.status.lock().unwrap().run,
+            Some(RunStatus::Cancelled(_))
+        ));
+        wait_until(|| unsafe { libc::kill(pid, 0) } != 0);
+
+        // nothing runs again, and the output stays
+        thread::sleep(time::Duration::from_millis(100));
+        assert!(ui.running.lock().unwrap().is_empty());
+        assert!(matches!(
+            ui.status.lock().unwrap().run,
+            Some(RunStatus::Cancelled(_))
+        ));
+        assert_eq!(*ui.data.load(), format!("{}\n", pid).into_bytes());
+
+        // until the next query
+        query_tx.send("x".to_string()).unwrap();
+        wait_until(|| matches!(ui.status.lock().unwrap().run, Some(RunStatus::Running(_))));
+        assert!(!ui.running.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn selection_is_the_record_as_written() {
+        let (ui, _query_tx) = started(&[
+            "--select",
+            "--tac",
+            "--delimiter",
+            ",",
+            "printf",
+            "a\\nb,c,d\\n",
+        ]);
+        wait_until(|| !ui.data.load().is_empty());
+
+        // shown on a line of its own, printed with its newline
+        assert_eq!(*ui.displayed(), b"d\nc\na b\n");
+        ui.viewport.lock().unwrap().cursor = 2;
+        assert_eq!(ui.selection(), Some(b"a\nb".to_vec()));
+        ui.viewport.lock().unwrap().cursor = 0;
+        assert_eq!(ui.selection(), Some(b"d".to_vec()));
+    }
+
+    #[test]
+    fn review_accepts_what_was_reviewed() {
+        let config = config::Config::parse(
+            [
+                "--review",
+                "sh",
+                "-c",
+                "echo $$; echo err >&2; exec sleep 10",
+            ]
+            .map(String::from),
+        )
+        .unwrap();
+        let (redraw_tx, _redraw_rx) = terminal::redraw_channel();
+        let reviewer = Reviewer {
+            review: sync::Arc::new(sync::Mutex::new(None)),
+            viewport: sync::Arc::default(),
+            status: sync::Arc::default(),
+            redraw_tx,
+        };
+        reviewer.start(&config, "", None, None);
+        let shown = || {
+            let review = reviewer.review.lock().unwrap();
+            let output = review.as_ref().unwrap().output.lock().unwrap().clone();
+            String::from_utf8(output).unwrap()
+        };
+        wait_until(|| shown().lines().count() >= 2);
+
+        // stdout is printed as it was shown, the program does not run again
+        let reviewed = String::from_utf8(reviewer.reviewed().unwrap().to_vec()).unwrap();
+        assert!(shown().contains("err\n"));
+        assert!(!reviewed.contains("err"));
+        let pid = reviewed.trim().parse::<i32>().unwrap();
+
+        // and leaving the review kills it
+        reviewer.stop();
+        assert!(!reviewer.reviewing());
+        assert_eq!(reviewer.reviewed(), None);
+        wait_until(|| unsafe { libc::kill(pid, 0) } != 0);
+    }
+
+    #[test]
+    fn tabs_keep_their_scroll() {
+        let config = config::Config::parse(
+            ["--cursor-line", "--tab", "seq 20 30", "seq", "10"].map(String::from),
+        )
+        .unwrap();
+        let (redraw_tx, _redraw_rx) = terminal::redraw_channel();
+        // the runs are killed once their query senders are gone
+        let mut query_txs = Vec::new();
+        let processes = [config.clone(), tab_config(&config, &config.tabs[0])]
+            .map(|config| {
+                let (query_tx, query_rx) = sync::mpsc::channel();
+                query_txs.push(query_tx);
+                let status = sync::Arc::new(sync::Mutex::new(UiStatus::default()));
+                UiWaitingProcess::new(
+                    sync::Arc::new(config),
+                    None,
+                    status,
+                    redraw_tx.clone(),
+                    query_rx,
+                )
+            })
+            .into_iter()
+            .collect::<Vec<_>>();
+        let tabs = Tabs::new(
+            processes
+                .iter()
+                .map(|process| Tab {
+                    label: String::new(),
+                    data: process.data.clone(),
+                    status: process.status.clone(),
+                })
+                .collect(),
+        );
+        for process in &processes {
+            wait_until(|| !process.data.load().is_empty());
+        }
+        let mut ui = UiTabs {
+            processes,
+            tabs: tabs.clone(),
+            shown: sync::atomic::AtomicUsize::new(0),
+            stopped: sync::atomic::AtomicBool::new(false),
+        };
+        let down = terminal::TerminalInput::Escape(terminal::TerminalEscape::DownArrow);
+
+        ui.input(&down, 5).unwrap();
+        ui.input(&down, 5).unwrap();
+        assert_eq!(ui.selection(), Some(b"3".to_vec()));
+        assert!(ui.render().generation.is_some());
+
+        tabs.next();
+        // a switch draws the other output from scratch
+        let out = ui.render();
+        assert_eq!((out.cursor_line, out.generation), (Some(0), None));
+        ui.input(&down, 5).unwrap();
+        assert_eq!(ui.selection(), Some(b"21".to_vec()));
+
+        tabs.next();
+        assert_eq!(ui.render().cursor_line, Some(2));
+        assert_eq!(ui.selection(), Some(b"3".to_vec()));
+        assert_eq!(ui.position(), Some((3, 10)));
+
+        // while the case mode is the same in all of them
+        let case = ui.processes[0].case;
+        ui.input(&terminal::TerminalInput::Ctrl(b's'), 5).unwrap();
+        assert!(
+            ui.processes
+                .iter()
+                .all(|process| process.case == case.next())
+        );
+    }
+
+    #[test]
+    fn header_command_output() {
+        assert_eq!(run_header_command("printf 'a\\nb\\n\\n'"), b"a\nb");
+        assert_eq!(run_header_command("echo out; echo err >&2"), b"out\nerr");
+    }
+
+    #[test]
+    fn query_hook_outcomes() {
+        assert_eq!(
+            run_query_hook("printf '%s.*' \"$1\"", "foo"),
+            Ok("foo.*".to_string())
+        );
+        // only logging it runs the query as typed
+        assert_eq!(
+            run_query_hook("echo \"$1\" >&2", "foo"),
+            Ok("foo".to_string())
+        );
+        assert_eq!(run_query_hook("echo \"$1\"", "a b"), Ok("a b".to_string()));
+        assert_eq!(
+            run_query_hook("test -n \"$1\"", ""),
+            Err("query hook exited with 1".to_string())
+        );
+    }
+
+    #[test]
+    fn build_argv_modes() {
+        let args = |args: &[&str]| args.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        let build = |argv: &[&str], query, mode| build_argv("rg", &args(argv), query, mode);
+
+        // an empty query is left out, not passed as an empty pattern
+        assert_eq!(
+            build(&["-i"], "", config::QueryMode::Append),
+            args(&["rg", "-i"])
+        );
+        assert_eq!(
+            build(&["-i"], "foo bar", config::QueryMode::Append),
+            args(&["rg", "-i", "foo bar"])
+        );
+        assert_eq!(
+            build(&["-i", "src"], "foo", config::QueryMode::Prepend),
+            args(&["rg", "foo", "-i", "src"])
+        );
+        assert_eq!(
+            build(&["-i"], "", config::QueryMode::Prepend),
+            args(&["rg", "-i"])
+        );
+        assert_eq!(
+            build(
+                &["--glob={}", "{}", "src"],
+                "*.rs",
+                config::QueryMode::Placeholder
+            ),
+            args(&["rg", "--glob=*.rs", "*.rs", "src"])
+        );
+        // the argument stays where it is, even when empty
+        assert_eq!(
+            build(&["{}", "src"], "", config::QueryMode::Placeholder),
+            args(&["rg", "", "src"])
+        );
+    }
+
+    #[test]
+    fn query_literal_argument() {
+        let config = config::Config::parse(
+            ["--query-literal", "--query-first", "printf", "%s\\n", "{}"]
+                .iter()
+                .map(|v| v.to_string()),
+        )
+        .unwrap();
+        let query = r#"a  b "c" $HOME * {} 'd'\"#;
+        let output = SharedBuf::default();
+        pipe_cmd(
+            &config.bin,
+            &config.bin_args,
+            query,
+            config.query_mode,
+            None,
+            output.clone(),
+            io::sink(),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(output.0.lock().unwrap().clone()).unwrap(),
+            format!("{{}}\n{}\n", query)
+        );
+    }
+
+    #[test]
+    fn arg_length_error_long_query() {
+        let args = ["-i".to_string()];
+        let query = "x".repeat(MAX_ARG_STRLEN);
+        let argv = build_argv("rg", &args, &query, config::QueryMode::Append);
+        assert!(
+            arg_length_error(&argv, usize::MAX)
+                .unwrap()
+                .contains("an argument is too long")
+        );
+
+        // the same query in every placeholder adds up
+        let args = vec!["{}".to_string(); 4];
+        let argv = build_argv("rg", &args, &query[..1000], config::QueryMode::Placeholder);
+        assert!(
+            arg_length_error(&argv, 2000)
+                .unwrap()
+                .contains("the command is too long")
+        );
+        assert_eq!(arg_length_error(&argv, system_arg_max()), None);
+    }
+
+    #[test]
+    fn query_stdin_order() {
+        let echoed = |order, input: Option<&[u8]>| {
+            let output = SharedBuf::default();
+            let input = input.map(|v| sync::Arc::new(v.to_vec()));
+            let mode = config::QueryMode::Stdin(order);
+            pipe_cmd(
+                "cat",
+                &[],
+                "foo bar",
+                mode,
+                input,
+                output.clone(),
+                io::sink(),
+            )
+            .unwrap();
+            let output = output.0.lock().unwrap().clone();
+            String::from_utf8(output).unwrap()
+        };
+
+        assert_eq!(
+            echoed(config::StdinOrder::First, Some(b"a\nb\n")),
+            "foo bar\na\nb\n"
+        );
+        // the query still starts a line of its own
+        assert_eq!(
+            echoed(config::StdinOrder::Last, Some(b"a\nb")),
+            "a\nb\nfoo bar\n"
+        );
+        assert_eq!(echoed(config::StdinOrder::Last, None), "foo bar\n");
+    }
+
+    #[test]
+    fn print_command_formats() {
+        let argv = build_argv(
+            "rg",
+            &["-i".to_string()],
+            "it's a $test",
+            config::QueryMode::Append,
+        );
+        assert_eq!(
+            print_command(&argv, config::PrintCommand::Lines),
+            "rg\n-i\nit's a $test\n"
+        );
+        assert_eq!(
+            print_command(&argv, config::PrintCommand::Shell),
+            "rg -i 'it'\\''s a $test'\n"
+        );
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("src/main.rs"), "src/main.rs");
+    }
+
+    #[test]
+    fn render_records_delimiter() {
+        assert_eq!(
+            render_records(b"id,name\n1,web\n", b","),
+            b"id\nname 1\nweb\n"
+        );
+        assert_eq!(render_records(b"a\0b\nc\0", b"\0"), b"a\nb c\n");
+        assert_eq!(render_records(b"a::b", b"::"), b"a\nb");
+        assert_eq!(render_records(b"", b","), b"");
+    }
+
+    #[test]
+    fn reverse_records_order() {
+        assert_eq!(reverse_records(b"a\nb\nc\n", b"\n"), b"c\nb\na\n");
+        assert_eq!(reverse_records(b"a\nb", b"\n"), b"b\na");
+        assert_eq!(reverse_records(b"a\n\nb\n", b"\n"), b"b\n\na\n");
+        assert_eq!(reverse_records(b"a\0b\0", b"\0"), b"\0b\0a");
+        assert_eq!(reverse_records(b"", b"\n"), b"");
+    }
+
+    #[test]
+    fn pipe_cmd_spawn_failed() {
+        let err = pipe_cmd(
+            "tip-does-not-exist",
+            &[],
+            "",
+            config::QueryMode::Append,
+            None,
+            io::sink(),
+            io::sink(),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(error::TipError::SpawnFailed(bin, _)) if bin == "tip-does-not-exist"
+        ));
+        assert!(
+            err.to_string()
+                .starts_with("failed running tip-does-not-exist: ")
+        );
+    }
+
+    #[test]
+    fn format_spawn_error_kinds() {
+        let spawn_error = |bin: &str| {
+            let err = process::Command::new(bin).spawn().unwrap_err();
+            String::from_utf8(format_spawn_error(bin, &err, false)).unwrap()
+        };
+        assert_eq!(
+            spawn_error("tip-does-not-exist"),
+            "tip: tip-does-not-exist: command not found\n"
+        );
+        // a directory is not executable
+        assert_eq!(spawn_error("/"), "tip: /: permission denied\n");
+
+        let err = io::Error::from_raw_os_error(libc::E2BIG);
+        assert_eq!(
+            format_spawn_error("ls", &err, true),
+            format!("\x1b[31mtip: ls: {}\x1b[0m\n", err).into_bytes()
+        );
+    }
+
+    #[test]
+    fn pty_size_of_the_pane() {
+        let size = pty_size(Some((6, 37)));
+        assert_eq!((size.ws_row, size.ws_col), (6, 37));
+        // a pane squeezed to nothing is still a terminal
+        let size = pty_size(Some((0, 0)));
+        assert_eq!((size.ws_row, size.ws_col), (1, 1));
+    }
+
+    #[test]
+    fn format_regex_error_reason() {
+        let query = String::from("(foo");
+        let err = regex::Regex::new(&query).unwrap_err();
+        assert_eq!(format_regex_error(&err), "unclosed group");
+    }
 }
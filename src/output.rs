@@ -0,0 +1,118 @@
+use anyhow::{Context, Result, anyhow};
+use std::{fs, io, path, process};
+
+// written to a temporary file next to the destination and renamed over it,
+// so readers of the destination never see partial output
+#[derive(Debug)]
+pub struct AtomicFile {
+    path: path::PathBuf,
+    tmp_path: path::PathBuf,
+    file: Option<fs::File>,
+}
+
+impl AtomicFile {
+    pub fn create(path: &path::Path) -> Result<Self> {
+        let file_name = path
+            .file_name()
+            .with_context(|| format!("--output is not a file: {}", path.display()))?;
+        let mut tmp_name = std::ffi::OsString::from(".");
+        tmp_name.push(file_name);
+        tmp_name.push(format!(".tip-{}.tmp", process::id()));
+        let tmp_path = path.with_file_name(tmp_name);
+
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmp_path)
+            .map_err(|err| anyhow!("failed creating output file {}: {}", path.display(), err))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            tmp_path,
+            file: Some(file),
+        })
+    }
+
+    pub fn file(&self) -> Result<OutputFile> {
+        Ok(OutputFile {
+            path: self.path.clone(),
+            file: self.file.as_ref().unwrap().try_clone()?,
+        })
+    }
+
+    pub fn commit(mut self) -> Result<()> {
+        self.file.as_ref().unwrap().sync_all()?;
+        fs::rename(&self.tmp_path, &self.path).map_err(|err| {
+            anyhow!(
+                "failed writing output file {}: {}",
+                self.path.display(),
+                err
+            )
+        })?;
+        // renamed, so there is nothing to remove anymore
+        self.file = None;
+        Ok(())
+    }
+}
+
+// dropped without a commit, the destination stays untouched
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(&self.tmp_path);
+        }
+    }
+}
+
+// write errors name the destination, e.g when the disk is full
+pub struct OutputFile {
+    path: path::PathBuf,
+    file: fs::File,
+}
+
+impl io::Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("output file {}: {}", self.path.display(), err),
+            )
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn atomic_file_commit() {
+        let dir = std::env::temp_dir().join(format!("tip-output-test-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.txt");
+        fs::write(&path, "old").unwrap();
+
+        let output = AtomicFile::create(&path).unwrap();
+        output.file().unwrap().write_all(b"new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        output.commit().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+
+        // without a commit nothing changes and nothing is left behind
+        let output = AtomicFile::create(&path).unwrap();
+        output.file().unwrap().write_all(b"discarded").unwrap();
+        drop(output);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        let err = AtomicFile::create(&dir.join("missing").join("out.txt")).unwrap_err();
+        assert!(err.to_string().contains("No such file or directory"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,67 @@
+use anyhow::Result;
+use std::{env, fs, io::Write, path};
+
+use crate::output;
+
+// $XDG_STATE_HOME/tip, none without a home to put it in
+pub fn state_dir() -> Option<path::PathBuf> {
+    let base = match env::var_os("XDG_STATE_HOME").filter(|v| !v.is_empty()) {
+        Some(v) => path::PathBuf::from(v),
+        None => path::PathBuf::from(env::var_os("HOME")?).join(".local/state"),
+    };
+    Some(base.join("tip"))
+}
+
+// fnv-1a, stable across builds unlike the std hasher
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, v| {
+        (hash ^ *v as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+// one file per program and its arguments
+fn query_path(dir: &path::Path, bin: &str, args: &[String]) -> path::PathBuf {
+    let mut key = bin.as_bytes().to_vec();
+    for arg in args {
+        key.push(0);
+        key.extend_from_slice(arg.as_bytes());
+    }
+    dir.join("queries").join(format!("{:016x}", hash(&key)))
+}
+
+pub fn load_query(dir: &path::Path, bin: &str, args: &[String]) -> Option<String> {
+    fs::read_to_string(query_path(dir, bin, args)).ok()
+}
+
+// replaced atomically, so instances saving at the same time never leave a mix of both
+pub fn save_query(dir: &path::Path, bin: &str, args: &[String], query: &str) -> Result<()> {
+    let path = query_path(dir, bin, args);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = output::AtomicFile::create(&path)?;
+    file.file()?.write_all(query.as_bytes())?;
+    file.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_load_query() {
+        let dir = env::temp_dir().join(format!("tip-state-test-{}", std::process::id()));
+        let args = ["log".to_string()];
+
+        assert_eq!(load_query(&dir, "git", &args), None);
+        save_query(&dir, "git", &args, "fix").unwrap();
+        save_query(&dir, "git", &args, "feat").unwrap();
+        assert_eq!(load_query(&dir, "git", &args).as_deref(), Some("feat"));
+
+        // different arguments are a different command
+        assert_eq!(load_query(&dir, "git", &["log -p".to_string()]), None);
+        assert_eq!(load_query(&dir, "git", &["log".into(), "".into()]), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,10 +1,11 @@
+use crate::error::TipError;
 use anyhow::{Context, Result, anyhow};
 use std::{
-    env, fs,
+    collections, env, fs,
     io::{self, Read, Write},
-    mem,
+    mem, ops,
     os::fd::AsRawFd,
-    sync, thread, time,
+    str, sync, thread, time,
 };
 
 macro_rules! onerr {
@@ -16,21 +17,62 @@ macro_rules! onerr {
     }};
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum TerminalEscape {
     LeftArrow,
     RightArrow,
+    UpArrow,
+    DownArrow,
     CtrlLeftArrow,
     CtrlRightArrow,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Insert,
+    BackTab,
     Timeout,
 }
 
 #[derive(Debug)]
 pub enum TerminalInput {
-    Printable(u8),
+    Printable(char),
     Ctrl(u8),
     Escape(TerminalEscape),
     Delete,
+    // escape followed by a key before the escape timeout, what terminals send for alt
+    Alt(char),
+    AltDelete,
+    // clipboard contents the terminal answered a paste request with
+    Paste(String),
+}
+
+// terminals that do not allow reading the clipboard never answer
+const OSC_TIMEOUT_MS: i32 = 1000;
+const MAX_OSC_LENGTH: usize = 1 << 20;
+
+// standard alphabet, padding optional
+fn decode_base64(input: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len() / 4 * 3);
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+    for v in input.iter().take_while(|v| **v != b'=') {
+        let value = match v {
+            b'A'..=b'Z' => v - b'A',
+            b'a'..=b'z' => v - b'a' + 26,
+            b'0'..=b'9' => v - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        bits = (bits << 6) | value as u32;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            out.push((bits >> bit_count) as u8);
+        }
+    }
+    Some(out)
 }
 
 #[derive(Debug)]
@@ -40,7 +82,11 @@ pub struct TerminalReader {
 
 impl TerminalReader {
     pub fn new() -> Result<Self> {
-        let tty = fs::File::open("/dev/tty")?;
+        Self::open("/dev/tty")
+    }
+
+    fn open(path: &str) -> Result<Self> {
+        let tty = fs::File::open(path).map_err(TipError::TtyUnavailable)?;
         Ok(Self { tty })
     }
 
@@ -52,19 +98,50 @@ impl TerminalReader {
         }
     }
 
-    fn read_u8_timeout(&mut self, timeout_ms: i32) -> Result<Option<u8>> {
+    // -1 waits forever. a signal restarts the wait, poll is not restarted on its own
+    fn readable(&self, timeout_ms: i32) -> Result<bool> {
         let mut pollfd = libc::pollfd {
             fd: self.tty.as_raw_fd(),
             events: libc::POLLIN,
             revents: 0,
         };
 
-        let polled = unsafe { libc::poll(&mut pollfd, 1, timeout_ms) };
-        match polled {
-            0 => Ok(None),
-            -1 => Err(anyhow!("error in poll")),
-            _ => self.read_u8().map(|v| Some(v)),
+        loop {
+            match unsafe { libc::poll(&mut pollfd, 1, timeout_ms) } {
+                0 => return Ok(false),
+                -1 => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err).with_context(|| "error in poll");
+                    }
+                }
+                _ => return Ok(true),
+            }
+        }
+    }
+
+    fn read_u8_timeout(&mut self, timeout_ms: i32) -> Result<Option<u8>> {
+        match self.readable(timeout_ms)? {
+            true => self.read_u8().map(Some),
+            false => Ok(None),
+        }
+    }
+
+    // reads the continuation bytes of a utf-8 encoded character, none if it is invalid
+    fn read_char(&mut self, first: u8) -> Result<Option<char>> {
+        let len = match first {
+            0x00..=0x7f => 1,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => return Ok(None),
+        };
+
+        let mut buf = vec![first];
+        for _ in 1..len {
+            buf.push(self.read_u8()?);
         }
+        Ok(str::from_utf8(&buf).ok().and_then(|v| v.chars().next()))
     }
 
     fn is_escape_end(ch: u8) -> bool {
@@ -92,24 +169,59 @@ impl TerminalReader {
         Ok(string)
     }
 
+    // ESC ] ... terminated by BEL or ESC \, only clipboard answers are understood
+    fn read_osc(&mut self) -> Result<Option<TerminalInput>> {
+        // alt-] is the same bytes, but without anything following right away
+        let Some(mut read) = self.read_u8_timeout(50)? else {
+            return Ok(Some(TerminalInput::Alt(']')));
+        };
+        let mut osc = Vec::new();
+        loop {
+            match read {
+                0x07 => break,
+                0x1b => {
+                    self.read_u8_timeout(OSC_TIMEOUT_MS)?;
+                    break;
+                }
+                _ if osc.len() < MAX_OSC_LENGTH => osc.push(read),
+                _ => {}
+            }
+            read = match self.read_u8_timeout(OSC_TIMEOUT_MS)? {
+                Some(read) => read,
+                None => return Ok(None),
+            };
+        }
+
+        // 52;<selection>;<base64>
+        let mut parts = osc.splitn(3, |v| *v == b';');
+        let (Some(b"52"), Some(_), Some(encoded)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Ok(None);
+        };
+        Ok(decode_base64(encoded)
+            .map(|v| TerminalInput::Paste(String::from_utf8_lossy(&v).into_owned())))
+    }
+
     // ^[
-    fn read_escape(&mut self) -> Result<Option<TerminalEscape>> {
+    fn read_escape(&mut self) -> Result<Option<TerminalInput>> {
+        // a lone escape is told apart from alt by nothing following it in time
         let Some(next) = self.read_u8_timeout(50)? else {
-            return Ok(Some(TerminalEscape::Timeout));
+            return Ok(Some(TerminalInput::Escape(TerminalEscape::Timeout)));
         };
-        if next != b'[' {
-            return Err(anyhow!("unexpected: {:x}", next));
+        let escape = match next {
+            b'[' => self.read_escape_to_end()?,
+            b']' => return self.read_osc(),
+            // SS3, sent for arrows and home/end in application mode, alt-O when nothing follows
+            b'O' => match self.read_u8_timeout(50)? {
+                Some(read) => (read as char).to_string(),
+                None => return Ok(Some(TerminalInput::Alt('O'))),
+            },
+            0x7f => return Ok(Some(TerminalInput::AltDelete)),
+            0x20..=0x7e => return Ok(Some(TerminalInput::Alt(next as char))),
+            _ => return Err(anyhow!("unexpected: {:x}", next)),
         };
 
-        let escape = self.read_escape_to_end()?;
-
-        Ok(match escape.as_str() {
-            "D" => Some(TerminalEscape::LeftArrow),
-            "C" => Some(TerminalEscape::RightArrow),
-            "1;5D" => Some(TerminalEscape::CtrlLeftArrow),
-            "1;5C" => Some(TerminalEscape::CtrlRightArrow),
-            _ => None,
-        })
+        Ok(parse_escape(&escape).map(TerminalInput::Escape))
     }
 
     pub fn read_input(&mut self) -> Result<Option<TerminalInput>> {
@@ -117,48 +229,136 @@ impl TerminalReader {
         match self.tty.read(&mut buf)? {
             0 => Ok(None),
             _ => Ok(match buf[0] {
-                0x1b => self.read_escape()?.map(|v| TerminalInput::Escape(v)),
+                0x1b => self.read_escape()?,
                 0x9B => todo!(),
                 0x90 => todo!(),
                 0x9D => todo!(),
                 0x7F => Some(TerminalInput::Delete),
+                // ctrl-c among them, what it does is up to the keymap
                 1..=26 => Some(TerminalInput::Ctrl(97 + buf[0] - 1)),
-                x => Some(TerminalInput::Printable(x)),
+                x => self.read_char(x)?.map(TerminalInput::Printable),
             }),
         }
     }
 }
 
+// what follows ESC [ or ESC O, terminals disagree on home and end so every form maps to them
+fn parse_escape(escape: &str) -> Option<TerminalEscape> {
+    Some(match escape {
+        "D" => TerminalEscape::LeftArrow,
+        "C" => TerminalEscape::RightArrow,
+        "A" => TerminalEscape::UpArrow,
+        "B" => TerminalEscape::DownArrow,
+        "5~" => TerminalEscape::PageUp,
+        "6~" => TerminalEscape::PageDown,
+        "H" | "1H" | "1~" | "7~" => TerminalEscape::Home,
+        "F" | "1F" | "4~" | "8~" => TerminalEscape::End,
+        "2~" => TerminalEscape::Insert,
+        "Z" => TerminalEscape::BackTab,
+        "1;5D" => TerminalEscape::CtrlLeftArrow,
+        "1;5C" => TerminalEscape::CtrlRightArrow,
+        _ => return None,
+    })
+}
+
+// how many lines the last row would hide behind the indicator, none when everything fit,
+// counted from where drawing the data ended
+fn lines_below(
+    data: &[u8],
+    end: &DataProgress,
+    is_hidden: impl Fn(&[u8]) -> bool,
+) -> Option<usize> {
+    let rest = data.get(end.offset..).unwrap_or_default();
+    let rest = rest.strip_suffix(b"\n").unwrap_or(rest);
+    let not_drawn = match rest.is_empty() {
+        true => 0,
+        false => rest
+            .split(|v| *v == b'\n')
+            .filter(|v| !is_hidden(v))
+            .count(),
+    };
+    if not_drawn == 0 && end.left_lines >= 0 {
+        return None;
+    }
+    Some(end.index - end.above_last + not_drawn)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CursorShape {
+    // whatever the user has configured
+    Default,
+    Block,
+    Bar,
+}
+
 pub struct TerminalWriter {
     tty: io::BufWriter<fs::File>,
     fd: i32,
     original_termios: libc::termios,
     debug: bool,
+    // without it the frames are drawn over the normal screen, whose lines are scrolled
+    // out into the scrollback first
+    alternate_screen: bool,
+    // the screen was switched to or scrolled out, so leaving has something to undo
+    entered: bool,
+    cursor_shape: CursorShape,
 }
 
 impl TerminalWriter {
     pub fn new() -> Result<Self> {
-        let tty = fs::File::create("/dev/tty")?;
+        Self::from_tty(open_tty("/dev/tty")?)
+    }
+
+    fn from_tty(tty: fs::File) -> Result<Self> {
         let fd = tty.as_raw_fd();
-        let mut tty = io::BufWriter::new(tty);
+        let tty = io::BufWriter::new(tty);
 
-        let original_termios = unsafe { enable_raw_mode(fd) };
+        let original_termios = unsafe { enable_raw_mode(fd) }?;
 
         let debug = env::var("TIP_DEBUG").unwrap_or("".to_string()) == "true";
-        if !debug {
-            switch_to_alternate_terminal(&mut tty)?
-        };
 
         Ok(Self {
             tty,
             fd,
             original_termios,
             debug,
+            alternate_screen: true,
+            entered: false,
+            cursor_shape: CursorShape::Default,
         })
     }
 
+    // entered with the first frame, so it can be set before
+    fn enter(&mut self) -> Result<()> {
+        if self.entered || self.debug {
+            return Ok(());
+        }
+        self.entered = true;
+        if self.alternate_screen {
+            return switch_to_alternate_terminal(&mut self.tty);
+        }
+        let rows = self.size().ws_row as usize;
+        self.move_cursor(rows, 1)?;
+        self.write("\n".repeat(rows).as_bytes())?;
+        self.write(b"\x1b[H")
+    }
+
+    // only what was entered is left, a terminal that never saw the alternate screen
+    // would have its normal one cleared instead
+    fn leave(&mut self) -> Result<()> {
+        if !self.entered {
+            return Ok(());
+        }
+        self.entered = false;
+        if self.alternate_screen {
+            return switch_to_normal_terminal(&mut self.tty);
+        }
+        // the screen, while the lines scrolled out before stay in the scrollback
+        self.write(b"\x1b[H\x1b[J")
+    }
+
     fn flush(&mut self) -> Result<()> {
-        self.tty.flush()?;
+        self.tty.flush().map_err(TipError::RenderError)?;
         Ok(())
     }
 
@@ -172,21 +372,60 @@ impl TerminalWriter {
         Ok(())
     }
 
+    // off while drawing, a character in the last column would otherwise leave the cursor
+    // waiting to wrap, which terminals disagree on
+    fn set_autowrap(&mut self, on: bool) -> Result<()> {
+        match on {
+            true => self.write(b"\x1b[?7h"),
+            false => self.write(b"\x1b[?7l"),
+        }
+    }
+
+    // terminals that do not support cursor shapes ignore the sequence
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<()> {
+        if self.cursor_shape == shape {
+            return Ok(());
+        }
+        self.cursor_shape = shape;
+        match shape {
+            CursorShape::Default => self.write(b"\x1b[0 q"),
+            CursorShape::Block => self.write(b"\x1b[1 q"),
+            CursorShape::Bar => self.write(b"\x1b[5 q"),
+        }
+    }
+
+    // answered with an osc 52 sequence read as TerminalInput::Paste
+    fn request_clipboard(&mut self) -> Result<()> {
+        self.write(b"\x1b]52;c;?\x1b\\")?;
+        self.flush()
+    }
+
     fn newline_start(&mut self) -> Result<()> {
         self.write("\r\n".as_bytes())?;
         Ok(())
     }
 
     fn write(&mut self, buf: &[u8]) -> Result<()> {
-        self.tty.write_all(buf)?;
+        self.tty.write_all(buf).map_err(TipError::RenderError)?;
         Ok(())
     }
 
     fn clear(&mut self) -> Result<()> {
+        self.enter()?;
+        // some terminals scroll a cleared normal screen into the scrollback,
+        // clearing from the top down does not
+        if !self.alternate_screen {
+            return self.write("\x1b[H\x1b[J\x1b[39m\x1b[49m\x1b[0m".as_bytes());
+        }
         // reset cursor, reset colors, reset styles
         self.write("\x1b[2J\x1b[H\x1b[39m\x1b[49m\x1b[0m".as_bytes())
     }
 
+    // like clear, but leaves the old frame to be overwritten
+    fn home(&mut self) -> Result<()> {
+        self.write("\x1b[H\x1b[39m\x1b[49m\x1b[0m".as_bytes())
+    }
+
     fn move_cursor(&mut self, line: usize, column: usize) -> Result<()> {
         self.write(format!("\x1b[{};{}H", line, column).as_bytes())
     }
@@ -194,31 +433,64 @@ impl TerminalWriter {
     fn size(&self) -> libc::winsize {
         get_terminal_size(self.fd)
     }
+
+    // the terminal as it was before tip, until resume
+    fn suspend(&mut self, flush_input: bool) -> Result<()> {
+        self.set_cursor_shape(CursorShape::Default)?;
+        // the last render may have left it hidden
+        self.show_cursor()?;
+        self.set_autowrap(true)?;
+        self.leave()?;
+        self.flush()?;
+        let action = if flush_input {
+            libc::TCSAFLUSH
+        } else {
+            libc::TCSADRAIN
+        };
+        if unsafe { libc::tcsetattr(self.fd, action, &self.original_termios) } != 0 {
+            let err = io::Error::last_os_error();
+            return Err(TipError::TermiosError("restoring the terminal", err).into());
+        }
+        Ok(())
+    }
+
+    fn resume(&mut self) -> Result<()> {
+        unsafe { enable_raw_mode(self.fd) }?;
+        self.enter()?;
+        self.flush()
+    }
 }
 
 impl Drop for TerminalWriter {
     fn drop(&mut self) {
-        if !self.debug {
-            let _ = switch_to_normal_terminal(&mut self.tty);
-        }
-        let _ = self.tty.flush();
-        unsafe { libc::tcsetattr(self.fd, libc::TCSAFLUSH, &self.original_termios) };
+        let _ = self.suspend(true);
     }
 }
 
+fn open_tty(path: &str) -> Result<fs::File> {
+    Ok(fs::File::create(path).map_err(TipError::TtyUnavailable)?)
+}
+
 // returns the original one
-unsafe fn enable_raw_mode(tty_fd: i32) -> libc::termios {
+unsafe fn enable_raw_mode(tty_fd: i32) -> Result<libc::termios> {
     let mut original_termios = mem::MaybeUninit::<libc::termios>::uninit();
-    unsafe { libc::tcgetattr(tty_fd, original_termios.as_mut_ptr()) };
+    if unsafe { libc::tcgetattr(tty_fd, original_termios.as_mut_ptr()) } != 0 {
+        let err = io::Error::last_os_error();
+        return Err(TipError::TermiosError("reading terminal attributes", err).into());
+    }
     let original_termios = unsafe { original_termios.assume_init() };
 
-    let mut raw_termios = mem::MaybeUninit::<libc::termios>::uninit();
-    unsafe { libc::cfmakeraw(raw_termios.as_mut_ptr()) };
-    let raw_termios = unsafe { raw_termios.assume_init() };
+    // raw from the original, so fields cfmakeraw leaves alone are not garbage.
+    // it clears ISIG too, so ctrl-c is read as a key and never raises SIGINT
+    let mut raw_termios = original_termios;
+    unsafe { libc::cfmakeraw(&mut raw_termios) };
 
-    unsafe { libc::tcsetattr(tty_fd, libc::TCSANOW, &raw_termios) };
+    if unsafe { libc::tcsetattr(tty_fd, libc::TCSANOW, &raw_termios) } != 0 {
+        let err = io::Error::last_os_error();
+        return Err(TipError::TermiosError("enabling terminal raw mode", err).into());
+    }
 
-    original_termios
+    Ok(original_termios)
 }
 
 fn switch_to_alternate_terminal<T: Write>(tty: &mut T) -> Result<()> {
@@ -231,265 +503,2078 @@ fn switch_to_normal_terminal<T: Write>(tty: &mut T) -> Result<()> {
     Ok(())
 }
 
+// display width in terminal columns, wide characters take up two
+pub fn chars_width(chars: &[char]) -> usize {
+    chars
+        .iter()
+        .map(|v| unicode_width::UnicodeWidthChar::width(*v).unwrap_or(0))
+        .sum()
+}
+
+// highlights end reverse video themselves, so it is turned back on after them
+fn reverse_video(line: &[u8]) -> Vec<u8> {
+    let mut reversed = b"\x1b[7m".to_vec();
+    let mut rest = line;
+    while let Some(i) = rest.windows(5).position(|v| v == b"\x1b[27m") {
+        reversed.extend_from_slice(&rest[..i + 5]);
+        reversed.extend_from_slice(b"\x1b[7m");
+        rest = &rest[i + 5..];
+    }
+    reversed.extend_from_slice(rest);
+    reversed
+}
+
+// output is read in chunks, so a render can catch it in the middle of a codepoint,
+// the partial codepoint is held back until the rest of it arrives
+fn complete_utf8_prefix(data: &[u8]) -> &[u8] {
+    for back in 1..=data.len().min(4) {
+        let byte = data[data.len() - back];
+        // continuation byte, the lead byte is further back
+        if byte & 0b1100_0000 == 0b1000_0000 {
+            continue;
+        }
+        let needed = match byte.leading_ones() {
+            2 => 2,
+            3 => 3,
+            4 => 4,
+            _ => 1,
+        };
+        if needed > back {
+            return &data[..data.len() - back];
+        }
+        break;
+    }
+    data
+}
+
 pub fn isatty(fd: i32) -> bool {
     let tty = unsafe { libc::isatty(fd) };
     tty == 1
 }
 
 pub fn get_terminal_size(tty_fd: i32) -> libc::winsize {
-    let mut winsize = mem::MaybeUninit::<libc::winsize>::uninit();
-    unsafe { libc::ioctl(tty_fd, libc::TIOCGWINSZ, winsize.as_mut_ptr()) };
-    unsafe { winsize.assume_init() }
+    let mut winsize = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // on failure it stays zeroed and falls back
+    unsafe { libc::ioctl(tty_fd, libc::TIOCGWINSZ, &mut winsize) };
+
+    override_terminal_size(
+        fallback_terminal_size(winsize),
+        env::var("COLUMNS").ok().as_deref(),
+        env::var("LINES").ok().as_deref(),
+    )
+}
+
+fn same_size(a: &libc::winsize, b: &libc::winsize) -> bool {
+    a.ws_row == b.ws_row && a.ws_col == b.ws_col
+}
+
+// what a terminal that does not report its size, e.g a serial console, is assumed to be
+const FALLBACK_COLUMNS: u16 = 80;
+const FALLBACK_ROWS: u16 = 24;
+
+fn fallback_terminal_size(mut winsize: libc::winsize) -> libc::winsize {
+    if winsize.ws_col == 0 {
+        winsize.ws_col = FALLBACK_COLUMNS;
+    }
+    if winsize.ws_row == 0 {
+        winsize.ws_row = FALLBACK_ROWS;
+    }
+    winsize
+}
+
+// COLUMNS and LINES take precedence over the ioctl, values that are not a positive number are ignored
+fn override_terminal_size(
+    mut winsize: libc::winsize,
+    columns: Option<&str>,
+    lines: Option<&str>,
+) -> libc::winsize {
+    let parse = |v: Option<&str>| {
+        v.and_then(|v| v.trim().parse::<u16>().ok())
+            .filter(|v| *v > 0)
+    };
+
+    if let Some(columns) = parse(columns) {
+        winsize.ws_col = columns;
+    }
+    if let Some(lines) = parse(lines) {
+        winsize.ws_row = lines;
+    }
+    winsize
 }
 
 struct TerminalRenderState {
     left_lines: usize,
+    // rows drawn from the top
+    line: usize,
     cursor_line: usize,
     cursor_col: usize,
+    // only a prompt that can be edited has a cursor to show, otherwise it stays hidden
+    show_cursor: bool,
 }
 
 impl TerminalRenderState {
     fn new(size: &libc::winsize) -> Self {
         Self {
             left_lines: size.ws_row as usize,
+            line: 0,
             cursor_line: 1,
             cursor_col: 1,
+            show_cursor: false,
         }
     }
 }
 
 enum ComponentRenderOut {
     Prompt(ComponentPromptOut),
+    Header(ComponentHeaderOut),
     Data(ComponentDataOut),
+    Footer(ComponentFooterOut),
 }
 
-pub struct ComponentDataOut(pub Vec<u8>);
+pub struct ComponentDataOut {
+    pub data: sync::Arc<Vec<u8>>,
+    // the current query, none when nothing uses it
+    pub matcher: Option<Matcher>,
+    pub highlight: bool,
+    pub filter: Option<FilterMode>,
+    // lines skipped from the top
+    pub scroll: usize,
+    // displayed line shown in reverse video
+    pub cursor_line: Option<usize>,
+    // shown below the data, e.g details of the line at the top
+    pub preview: Option<Vec<u8>>,
+    // scrolled to the last lines, the cursor line on the last one
+    pub follow: bool,
+    // the same as the last frame's means the data only grew since, none when it can change otherwise
+    pub generation: Option<u64>,
+}
 
-pub struct ComponentPromptOut {
-    pub query: Vec<char>,
-    pub cursor_index: usize,
+// how far drawing the data got, the lines before offset are drawn or skipped
+#[derive(Debug, Clone, Copy)]
+struct DataProgress {
+    offset: usize,
+    left_lines: isize,
+    skip: usize,
+    index: usize,
+    // index after the last line that ended above the last row
+    above_last: usize,
 }
 
-pub trait ComponentPrompt {
-    fn input(&mut self, input: &TerminalInput) -> Result<()>;
-    fn render(&self) -> ComponentPromptOut;
+// everything besides the data that decides what the data pane shows
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DataView {
+    matcher: Option<Matcher>,
+    highlight: bool,
+    filter: Option<FilterMode>,
+    scroll: usize,
+    cursor_line: Option<usize>,
+    separator_line: usize,
+    data_lines: usize,
 }
 
-pub trait ComponentData {
-    fn render(&self) -> ComponentDataOut;
+// where the last frame's data pane left off, appended data is drawn from there
+struct DataPaneEnd {
+    generation: u64,
+    view: DataView,
+    // at the line that was not complete yet
+    progress: DataProgress,
 }
 
-pub enum Component<'a> {
-    Prompt(&'a mut dyn ComponentPrompt),
-    Data(&'a mut dyn ComponentData),
+// client side filtering of lines not matching the query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterMode {
+    // keeps line positions
+    Dim,
+    // compacts the remaining lines
+    Hide,
 }
 
-enum TerminalRendererEvent {
-    Resize,
-    Input(TerminalInput),
-    Redraw,
-    Quit,
+impl str::FromStr for FilterMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "dim" => Ok(Self::Dim),
+            "hide" => Ok(Self::Hide),
+            _ => Err(anyhow!("unknown filter mode: {}", s)),
+        }
+    }
 }
 
-pub struct TerminalRenderer<'a> {
-    components: Vec<Component<'a>>,
-    size: libc::winsize,
-    terminal_writer: TerminalWriter,
+// how a frame replaces the one before
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Redraw {
+    // cleared as a whole first
+    Screen,
+    // every line clears what is left of the old one after it, which flickers less
+    Lines,
+}
 
-    event_rx: sync::mpsc::Receiver<TerminalRendererEvent>,
+impl str::FromStr for Redraw {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "screen" => Ok(Self::Screen),
+            "lines" => Ok(Self::Lines),
+            _ => Err(anyhow!("unknown redraw mode: {}", s)),
+        }
+    }
 }
 
-impl<'a> TerminalRenderer<'a> {
-    pub fn new(
-        components: Vec<Component<'a>>,
-        redraw_rx: sync::mpsc::Receiver<()>,
-    ) -> Result<Self> {
-        let (event_tx, event_rx) = sync::mpsc::sync_channel(0);
+// which side of the data the preview goes on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreviewPosition {
+    Up,
+    Down,
+    Left,
+    Right,
+}
 
-        // signals
-        thread::spawn({
-            let event_tx = event_tx.clone();
-            let mut signals = signal_hook::iterator::Signals::new(&[
-                signal_hook::consts::SIGWINCH,
-                signal_hook::consts::SIGINT,
-                signal_hook::consts::SIGTERM,
-            ])?;
+// of the rows for up and down, of the columns for left and right
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreviewSize {
+    Percent(usize),
+    // lines or columns of the preview itself, its separator takes one more
+    Absolute(usize),
+}
 
-            move || {
-                for signal in &mut signals {
-                    match signal {
-                        libc::SIGWINCH => {
-                            onerr!(event_tx.send(TerminalRendererEvent::Resize), { break })
-                        }
-                        libc::SIGINT | libc::SIGTERM => {
-                            onerr!(event_tx.send(TerminalRendererEvent::Quit), { break })
-                        }
-                        _ => unreachable!(),
-                    }
-                }
-            }
-        });
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PreviewWindow {
+    pub position: PreviewPosition,
+    pub size: PreviewSize,
+}
 
-        // input
-        thread::spawn({
-            let event_tx = event_tx.clone();
-            let mut terminal_reader = TerminalReader::new()?;
-            move || {
-                loop {
-                    let input = terminal_reader.read_input().unwrap();
-                    if let Some(input) = input {
-                        onerr!(event_tx.send(TerminalRendererEvent::Input(input)), {
-                            break;
-                        });
+impl Default for PreviewWindow {
+    fn default() -> Self {
+        Self {
+            position: PreviewPosition::Down,
+            size: PreviewSize::Percent(50),
+        }
+    }
+}
+
+// position:size, either can be left out, e.g right:40%, up:10 or left
+impl str::FromStr for PreviewWindow {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut window = Self::default();
+        for part in s.split(':') {
+            window = match part {
+                "up" => Self {
+                    position: PreviewPosition::Up,
+                    ..window
+                },
+                "down" => Self {
+                    position: PreviewPosition::Down,
+                    ..window
+                },
+                "left" => Self {
+                    position: PreviewPosition::Left,
+                    ..window
+                },
+                "right" => Self {
+                    position: PreviewPosition::Right,
+                    ..window
+                },
+                _ => {
+                    let size = match part.strip_suffix('%') {
+                        Some(v) => v
+                            .parse()
+                            .ok()
+                            .filter(|v| *v <= 100)
+                            .map(PreviewSize::Percent),
+                        None => part.parse().ok().map(PreviewSize::Absolute),
+                    };
+                    match size {
+                        Some(size) => Self { size, ..window },
+                        None => return Err(anyhow!("unknown preview window: {}", s)),
                     }
                 }
-            }
-        });
+            };
+        }
+        Ok(window)
+    }
+}
 
-        // pipe redraw
-        thread::spawn({
-            let event_tx = event_tx.clone();
-            move || {
-                let throttle = Throttle::new(move || {
-                    let _ = event_tx.send(TerminalRendererEvent::Redraw);
-                });
-                loop {
-                    onerr!(redraw_rx.recv(), { break });
-                    throttle.call();
-                }
-            }
-        });
+// how control characters of the output are shown, carriage returns are always dropped
+// and tabs always kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlMode {
+    // as they are, for trusted output
+    Pass,
+    // like cat -v, e.g ^G, color sequences are kept
+    Caret,
+    // left out, color sequences are kept
+    Strip,
+}
 
-        let terminal_writer = TerminalWriter::new()?;
-        let size = terminal_writer.size();
+impl str::FromStr for ControlMode {
+    type Err = anyhow::Error;
 
-        Ok(Self {
-            size,
-            terminal_writer,
-            components,
-            event_rx,
-        })
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pass" => Ok(Self::Pass),
+            "caret" => Ok(Self::Caret),
+            "strip" => Ok(Self::Strip),
+            _ => Err(anyhow!("unknown control mode: {}", s)),
+        }
     }
+}
 
-    fn handle_size(&mut self) {
-        self.size = self.terminal_writer.size();
+// the length of the color sequence at the start, e.g `\x1b[1;31m`
+fn sgr_len(bytes: &[u8]) -> Option<usize> {
+    let params = bytes
+        .strip_prefix(b"\x1b[")?
+        .iter()
+        .take_while(|v| matches!(v, b'0'..=b'9' | b';' | b':'))
+        .count();
+    (bytes.get(2 + params) == Some(&b'm')).then_some(3 + params)
+}
+
+// anything but color sequences could move the cursor or change the terminal,
+// a stray escape is a control character like any other
+fn sanitize_controls(line: Vec<u8>, mode: ControlMode) -> Vec<u8> {
+    let is_control = |v: u8| (v < 0x20 && v != b'\t') || v == 0x7f;
+    if mode == ControlMode::Pass || !line.iter().any(|v| is_control(*v)) {
+        return line;
     }
 
-    fn window_str(source: &[char], size: usize, index: usize) -> &[char] {
-        if index < size {
-            let end = size.min(source.len());
-            return &source[..end];
+    let mut sanitized = Vec::with_capacity(line.len());
+    let mut i = 0;
+    while i < line.len() {
+        if let Some(len) = sgr_len(&line[i..]) {
+            sanitized.extend_from_slice(&line[i..i + len]);
+            i += len;
+            continue;
+        }
+        let v = line[i];
+        i += 1;
+        if !is_control(v) {
+            sanitized.push(v);
+        } else if mode == ControlMode::Caret {
+            sanitized.extend([b'^', v ^ 0x40]);
         }
-
-        &source[(index - size)..index]
     }
+    sanitized
+}
 
-    fn render_component_prompt(
-        &mut self,
-        out: ComponentPromptOut,
-        state: &mut TerminalRenderState,
-    ) -> Result<()> {
-        state.left_lines -= 1;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseMode {
+    Sensitive,
+    Insensitive,
+    // insensitive unless the query contains an uppercase character
+    Smart,
+}
 
-        let window = Self::window_str(&out.query, self.size.ws_col as usize, out.cursor_index);
-        self.terminal_writer
-            .write(window.iter().collect::<String>().as_bytes())?;
+impl CaseMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Smart => Self::Sensitive,
+            Self::Sensitive => Self::Insensitive,
+            Self::Insensitive => Self::Smart,
+        }
+    }
+
+    fn ignore_case(self, query: &str) -> bool {
+        match self {
+            Self::Sensitive => false,
+            Self::Insensitive => true,
+            Self::Smart => !query.chars().any(|v| v.is_uppercase()),
+        }
+    }
+}
 
-        state.cursor_line = 1;
-        state.cursor_col = out.cursor_index + 1;
+impl str::FromStr for CaseMode {
+    type Err = anyhow::Error;
 
-        Ok(())
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "sensitive" => Ok(Self::Sensitive),
+            "insensitive" => Ok(Self::Insensitive),
+            "smart" => Ok(Self::Smart),
+            _ => Err(anyhow!("unknown case mode: {}", s)),
+        }
     }
+}
 
-    fn render_component_data(
-        &mut self,
-        out: ComponentDataOut,
-        state: &mut TerminalRenderState,
-    ) -> Result<()> {
-        self.terminal_writer.newline_start()?;
-        state.left_lines -= 1;
-        self.terminal_writer
-            .write("─".repeat(self.size.ws_col as usize).as_bytes())?;
-
-        let mut lines = out.0.split(|v| *v == b'\n');
-        let mut left_lines = state.left_lines as isize;
-        while left_lines > 0 {
-            let Some(line) = lines.next() else { break };
-            let escaped_vec = EscapedVec::new(
-                line.iter()
-                    .filter(|v| **v != b'\r')
-                    .map(|v| *v)
-                    .collect::<Vec<u8>>(),
-            );
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Matcher {
+    pub query: String,
+    pub case: CaseMode,
+}
+
+// a character of the output, or a byte of it that is not utf-8, which no query matches
+#[derive(Debug, Clone, Copy)]
+enum MatchUnit {
+    Char(char),
+    Byte,
+}
 
-            let len = escaped_vec.len();
-            let takes_up_lines = (len as f32 / self.size.ws_col as f32).ceil().max(1.0) as usize;
+// with the offset each starts at
+fn match_units(bytes: &[u8]) -> Vec<(usize, MatchUnit)> {
+    let mut units = Vec::new();
+    let mut offset = 0;
+    for chunk in bytes.utf8_chunks() {
+        for (i, ch) in chunk.valid().char_indices() {
+            units.push((offset + i, MatchUnit::Char(ch)));
+        }
+        offset += chunk.valid().len();
+        for _ in chunk.invalid() {
+            units.push((offset, MatchUnit::Byte));
+            offset += 1;
+        }
+    }
+    units
+}
 
-            let mut cap = len;
-            if (left_lines - takes_up_lines as isize) < 0 {
-                cap = self.size.ws_col as usize * left_lines as usize;
+impl Matcher {
+    // folded per character, the same unicode uppercase smart case looks for, the ranges are
+    // of bytes
+    fn find_matches(&self, haystack: &[u8]) -> Vec<ops::Range<usize>> {
+        let needle = self.query.chars().collect::<Vec<_>>();
+        if needle.is_empty() {
+            return Vec::new();
+        }
+        let ignore_case = self.case.ignore_case(&self.query);
+        let same = |unit: MatchUnit, ch: char| match unit {
+            MatchUnit::Char(v) => {
+                v == ch || (ignore_case && v.to_lowercase().eq(ch.to_lowercase()))
             }
-            left_lines -= takes_up_lines as isize;
+            MatchUnit::Byte => false,
+        };
 
-            self.terminal_writer.newline_start()?;
-            self.terminal_writer.write(escaped_vec.cap(cap))?;
+        let units = match_units(haystack);
+        let offset = |i: usize| units.get(i).map_or(haystack.len(), |v| v.0);
+        let mut matches = Vec::new();
+        let mut i = 0;
+        while i + needle.len() <= units.len() {
+            let window = &units[i..i + needle.len()];
+            if window
+                .iter()
+                .zip(&needle)
+                .all(|(unit, ch)| same(unit.1, *ch))
+            {
+                matches.push(offset(i)..offset(i + needle.len()));
+                i += needle.len();
+            } else {
+                i += 1;
+            }
         }
-        state.left_lines = left_lines.max(0) as usize;
+        matches
+    }
 
-        Ok(())
+    // pairs of (index in line, character) skipping escapes
+    fn visible(line: &[u8]) -> Vec<(usize, u8)> {
+        EscapedIter::new(line)
+            .enumerate()
+            .filter(|(_, v)| !v.in_escape)
+            .map(|(i, v)| (i, v.ch))
+            .collect()
     }
 
-    fn rerender(&mut self) -> Result<()> {
-        self.terminal_writer.clear()?;
-        self.terminal_writer.hide_cursor()?;
+    // an empty query matches every line
+    pub fn is_match(&self, line: &[u8]) -> bool {
+        self.query.is_empty()
+            || !self
+                .find_matches(
+                    &Self::visible(line)
+                        .iter()
+                        .map(|(_, ch)| *ch)
+                        .collect::<Vec<_>>(),
+                )
+                .is_empty()
+    }
 
-        let rendered = self
-            .components
-            .iter()
-            .map(|v| match v {
-                Component::Prompt(x) => ComponentRenderOut::Prompt(x.render()),
-                Component::Data(x) => ComponentRenderOut::Data(x.render()),
-            })
-            .collect::<Vec<_>>();
+    // wraps matches of the visible characters in reverse video,
+    // escapes already present in the line are left untouched
+    fn highlight(&self, line: &[u8]) -> Vec<u8> {
+        let visible = Self::visible(line);
+        let matches = self.find_matches(&visible.iter().map(|(_, ch)| *ch).collect::<Vec<_>>());
+        if matches.is_empty() {
+            return line.to_vec();
+        }
 
-        let mut state = TerminalRenderState::new(&self.size);
-        for x in rendered {
-            match x {
-                ComponentRenderOut::Prompt(x) => self.render_component_prompt(x, &mut state)?,
-                ComponentRenderOut::Data(x) => self.render_component_data(x, &mut state)?,
+        let mut highlighted = Vec::with_capacity(line.len() + matches.len() * 9);
+        let mut last = 0;
+        for range in matches {
+            let start = visible[range.start].0;
+            let end = visible[range.end - 1].0 + 1;
+            highlighted.extend_from_slice(&line[last..start]);
+            highlighted.extend_from_slice(b"\x1b[7m");
+            highlighted.extend_from_slice(&line[start..end]);
+            highlighted.extend_from_slice(b"\x1b[27m");
+            last = end;
+        }
+        highlighted.extend_from_slice(&line[last..]);
+        highlighted
+    }
+}
+
+pub struct ComponentPromptOut {
+    pub query: Vec<char>,
+    // characters of query before the typed part
+    pub prompt_len: usize,
+    pub cursor_index: usize,
+    // typing replaces the character under the cursor
+    pub overwrite: bool,
+    // typing does not edit the query, e.g while reviewing, so no cursor is shown
+    pub read_only: bool,
+}
+
+pub trait ComponentPrompt {
+    fn input(&mut self, input: &TerminalInput) -> Result<()>;
+    fn render(&self) -> ComponentPromptOut;
+}
+
+// lines above the separator, each cut off at the terminal width,
+// it leaves room for the separator and a line of data
+pub struct ComponentHeaderOut(pub Vec<u8>);
+
+pub trait ComponentHeader {
+    fn render(&self) -> ComponentHeaderOut;
+}
+
+// a footer only takes up the last line when it has something to show
+pub struct ComponentFooterOut(pub String);
+
+pub trait ComponentFooter {
+    fn render(&self) -> ComponentFooterOut;
+}
+
+pub trait ComponentData {
+    fn render(&self) -> ComponentDataOut;
+    // the output as the program wrote it, before anything rendered from it
+    fn content(&self) -> sync::Arc<Vec<u8>>;
+    // all of what is shown, not cut to the pane, e.g the fields of --nth
+    fn shown(&self) -> sync::Arc<Vec<u8>> {
+        self.content()
+    }
+    // the record under the cursor line, none without one
+    fn selection(&self) -> Option<Vec<u8>> {
+        None
+    }
+    // the record under the cursor line counting from 1 and how many there are, none when
+    // there is nothing to count
+    fn position(&self) -> Option<(usize, usize)> {
+        None
+    }
+    // height is the amount of lines the data got to render last time
+    fn input(&mut self, _input: &TerminalInput, _height: usize) -> Result<()> {
+        Ok(())
+    }
+    // the renderer scrolled to keep the cursor line in view after a resize
+    fn reflowed(&mut self, _scroll: usize) {}
+    // rows and columns of the pane the data was drawn in
+    fn pane_size(&mut self, _rows: usize, _columns: usize) {}
+    // the renderer stops once a frame is drawn, as if accepted
+    fn stops(&self) -> bool {
+        false
+    }
+}
+
+pub enum Component<'a> {
+    Prompt(&'a mut dyn ComponentPrompt),
+    Header(&'a mut dyn ComponentHeader),
+    Data(&'a mut dyn ComponentData),
+    Footer(&'a mut dyn ComponentFooter),
+}
+
+// what the renderer does with an input
+pub enum InputOutcome {
+    // handed to the components
+    Continue,
+    Stop,
+    // handled by the caller, the components never see it
+    Consumed,
+    // ask the terminal for the clipboard, which comes back as another input
+    RequestPaste,
+    // read the terminal size again and redraw the whole screen, for a missed SIGWINCH
+    Refresh,
+    // show or hide the column ruler above the data
+    ToggleRuler,
+    // run with the terminal as it was before tip, e.g a command of an action, then redraw
+    HandOff(Box<dyn FnOnce()>),
+}
+
+// while a command has the terminal, its keys are not read and its ctrl-c does not quit tip
+#[derive(Default)]
+struct HandOff {
+    state: sync::Mutex<HandOffState>,
+    ended: sync::Condvar,
+}
+
+#[derive(Default)]
+struct HandOffState {
+    active: bool,
+    // an input was sent to the renderer, which may hand the terminal off for it
+    handling: bool,
+}
+
+impl HandOff {
+    fn set(&self, active: bool) {
+        self.state.lock().unwrap().active = active;
+        self.ended.notify_all();
+    }
+
+    fn is_active(&self) -> bool {
+        self.state.lock().unwrap().active
+    }
+
+    fn wait(&self) {
+        let mut state = self.state.lock().unwrap();
+        while state.active {
+            state = self.ended.wait(state).unwrap();
+        }
+    }
+
+    fn sent(&self) {
+        self.state.lock().unwrap().handling = true;
+    }
+
+    fn handled(&self) {
+        self.state.lock().unwrap().handling = false;
+        self.ended.notify_all();
+    }
+
+    // so the keys typed after one that hands the terminal off are left to the command
+    fn wait_handled(&self) {
+        let mut state = self.state.lock().unwrap();
+        while state.handling || state.active {
+            state = self.ended.wait(state).unwrap();
+        }
+    }
+}
+
+enum TerminalRendererEvent {
+    Resize,
+    Input(TerminalInput),
+    Redraw,
+    Quit,
+}
+
+// the line between the prompt and the data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Separator {
+    // tiled to the width
+    pub glyph: String,
+    // none spans the whole terminal
+    pub width: Option<usize>,
+}
+
+impl Default for Separator {
+    fn default() -> Self {
+        Self {
+            glyph: "─".to_string(),
+            width: None,
+        }
+    }
+}
+
+impl Separator {
+    // repeated by display width, a wide glyph that does not fit is left out
+    fn line(&self, columns: usize) -> String {
+        let width = self.width.map_or(columns, |v| v.min(columns));
+        let mut line = String::new();
+        // a glyph without width would never fill the line
+        if chars_width(&self.glyph.chars().collect::<Vec<_>>()) == 0 {
+            return line;
+        }
+
+        let mut line_width = 0;
+        for ch in self.glyph.chars().cycle() {
+            let ch_width = unicode_width::UnicodeWidthChar::width(ch).unwrap_or(0);
+            if line_width + ch_width > width {
+                break;
+            }
+            line.push(ch);
+            line_width += ch_width;
+        }
+        line
+    }
+}
+
+// text attributes and a foreground color, e.g `bold,blue`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Style {
+    attributes: Vec<u8>,
+    color: Option<u8>,
+}
+
+impl str::FromStr for Style {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut style = Self::default();
+        for name in s.split(',').filter(|v| !v.is_empty()) {
+            match name {
+                "bold" => style.attributes.push(1),
+                "dim" => style.attributes.push(2),
+                "italic" => style.attributes.push(3),
+                "underline" => style.attributes.push(4),
+                _ => {
+                    let color = [
+                        "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+                    ]
+                    .iter()
+                    .position(|v| *v == name)
+                    .ok_or_else(|| anyhow!("unknown style: {}", name))?;
+                    style.color = Some(30 + color as u8);
+                }
+            }
+        }
+        Ok(style)
+    }
+}
+
+impl Style {
+    // attributes stay, https://no-color.org only asks for no colors
+    pub fn without_color(self) -> Self {
+        Self {
+            color: None,
+            ..self
+        }
+    }
+
+    // attributes add up, the color of other wins
+    fn layer(&mut self, other: &Style) {
+        for v in &other.attributes {
+            if !self.attributes.contains(v) {
+                self.attributes.push(*v);
+            }
+        }
+        self.color = other.color.or(self.color);
+    }
+
+    // undoes sgr, empty when there is nothing to undo
+    fn sgr_off(&self) -> String {
+        let mut codes = Vec::new();
+        for v in self.attributes.iter().chain(&self.color) {
+            let code = match v {
+                1 | 2 => "22",
+                3 => "23",
+                4 => "24",
+                _ => "39",
+            };
+            if !codes.contains(&code) {
+                codes.push(code);
+            }
+        }
+        if codes.is_empty() {
+            return String::new();
+        }
+        format!("\x1b[{}m", codes.join(";"))
+    }
+
+    // empty when there is nothing to set
+    fn sgr(&self) -> String {
+        let codes = self
+            .attributes
+            .iter()
+            .chain(&self.color)
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>();
+        if codes.is_empty() {
+            return String::new();
+        }
+        format!("\x1b[{}m", codes.join(";"))
+    }
+}
+
+// colors what a regex matches in the output, e.g `ERROR=bold,red`
+#[derive(Debug, Clone)]
+pub struct ColorRule {
+    regex: regex::bytes::Regex,
+    style: Style,
+}
+
+impl str::FromStr for ColorRule {
+    type Err = anyhow::Error;
+
+    // the style is after the last =, the regex can have its own
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((regex, style)) = s.rsplit_once('=') else {
+            return Err(anyhow!("invalid color rule, expected regex=style: {}", s));
+        };
+        Ok(Self {
+            regex: regex::bytes::Regex::new(regex)?,
+            style: style.parse()?,
+        })
+    }
+}
+
+impl ColorRule {
+    pub fn without_color(self) -> Self {
+        Self {
+            style: self.style.without_color(),
+            ..self
+        }
+    }
+}
+
+// matches of the visible characters styled by every rule, where they overlap later rules
+// layer over earlier ones, escapes already present in the line are left untouched
+fn color_line(rules: &[ColorRule], line: &[u8]) -> Vec<u8> {
+    let visible = Matcher::visible(line);
+    let chars = visible.iter().map(|(_, ch)| *ch).collect::<Vec<_>>();
+    let mut styles = vec![Style::default(); chars.len()];
+    for rule in rules {
+        for found in rule.regex.find_iter(&chars) {
+            for style in &mut styles[found.range()] {
+                style.layer(&rule.style);
+            }
+        }
+    }
+
+    let mut colored = Vec::with_capacity(line.len());
+    let mut current = Style::default();
+    let mut last = 0;
+    for ((index, _), style) in visible.iter().zip(styles) {
+        if style != current {
+            colored.extend_from_slice(&line[last..*index]);
+            colored.extend_from_slice(current.sgr_off().as_bytes());
+            colored.extend_from_slice(style.sgr().as_bytes());
+            last = *index;
+            current = style;
+        }
+    }
+    colored.extend_from_slice(&line[last..]);
+    colored.extend_from_slice(current.sgr_off().as_bytes());
+    colored
+}
+
+// lines of the data and of the preview out of the lines left below the separator, and the
+// columns of a preview beside the data out of the columns there are. lines past the maximum
+// data height stay blank
+fn data_pane_lines(
+    left_lines: usize,
+    columns: usize,
+    preview: Option<PreviewWindow>,
+    max_data_height: Option<usize>,
+) -> (usize, usize, usize) {
+    let (preview_lines, preview_columns) = match preview {
+        None => (0, 0),
+        Some(window) => match window.position {
+            PreviewPosition::Up | PreviewPosition::Down => {
+                (preview_part(left_lines, window.size), 0)
+            }
+            PreviewPosition::Left | PreviewPosition::Right => {
+                (0, preview_part(columns, window.size))
+            }
+        },
+    };
+    let data_lines = left_lines - preview_lines;
+    (
+        max_data_height.map_or(data_lines, |v| v.min(data_lines)),
+        preview_lines,
+        preview_columns,
+    )
+}
+
+// separator included, a size there is no room for gets half, and a preview with no room for
+// anything past its separator is left out
+fn preview_part(total: usize, size: PreviewSize) -> usize {
+    let part = match size {
+        PreviewSize::Percent(percent) => total * percent / 100,
+        PreviewSize::Absolute(v) => v.saturating_add(1),
+    };
+    match if part >= total { total / 2 } else { part } {
+        0 | 1 => 0,
+        v => v,
+    }
+}
+
+// threads ask the renderer for a redraw through it without ever waiting on the renderer.
+// the channel holds a single request, one made while another is pending is dropped as the
+// redraw that comes of the pending one draws its changes too. so a thread that holds a lock
+// the renderer wants, or that the renderer waits on through the query channel, can not
+// block on it
+#[derive(Clone)]
+pub struct RedrawSender(sync::mpsc::SyncSender<()>);
+
+impl RedrawSender {
+    // errors only once the renderer is gone
+    pub fn send(&self) -> Result<(), sync::mpsc::SendError<()>> {
+        match self.0.try_send(()) {
+            Ok(()) | Err(sync::mpsc::TrySendError::Full(())) => Ok(()),
+            Err(sync::mpsc::TrySendError::Disconnected(v)) => Err(sync::mpsc::SendError(v)),
+        }
+    }
+}
+
+pub fn redraw_channel() -> (RedrawSender, sync::mpsc::Receiver<()>) {
+    let (redraw_tx, redraw_rx) = sync::mpsc::sync_channel(1);
+    (RedrawSender(redraw_tx), redraw_rx)
+}
+
+// a box needs a column on each side and a row for the data besides the bottom edge
+fn frame_fits(columns: usize, rows: usize) -> bool {
+    columns >= 3 && rows >= 2
+}
+
+// columns between the ticks of the ruler
+const RULER_TICKS: usize = 5;
+
+// the columns of the data, lines start at the first as they are wrapped and not scrolled
+// sideways, ticks every RULER_TICKS counted from 1 and numbered every second tick with the
+// last digit, e.g ----+----1----+----2
+fn ruler_line(width: usize) -> String {
+    (1..=width)
+        .map(|column| match column % (2 * RULER_TICKS) {
+            0 => char::from_digit((column / (2 * RULER_TICKS) % 10) as u32, 10).unwrap(),
+            v if v % RULER_TICKS == 0 => '+',
+            _ => '-',
+        })
+        .collect()
+}
+
+// one row of the box between the corners, the glyphs are a column wide but three bytes long
+fn frame_edge(width: usize, left: char, right: char) -> String {
+    let mut edge = String::with_capacity(width * 3);
+    edge.push(left);
+    edge.push_str(&"─".repeat(width.saturating_sub(2)));
+    edge.push(right);
+    edge
+}
+
+// the scroll moved down only as far as needed for the lines from it through the cursor line
+// to fit into rows once wrapped at width, the last row goes to the lines below indicator
+// when there are any
+fn reflow_scroll<'b>(
+    mut lines: impl Iterator<Item = &'b [u8]>,
+    scroll: usize,
+    cursor: usize,
+    rows: usize,
+    width: usize,
+    control_mode: ControlMode,
+) -> usize {
+    if cursor < scroll {
+        return cursor;
+    }
+    let heights = lines
+        .by_ref()
+        .skip(scroll)
+        .take(cursor + 1 - scroll)
+        .map(|line| {
+            let line = line.iter().filter(|v| **v != b'\r').copied().collect();
+            EscapedVec::new(sanitize_controls(line, control_mode))
+                .wrap(width, usize::MAX)
+                .1
+        })
+        .collect::<Vec<_>>();
+    // the newline ending the output does not start a line
+    let rows = match (lines.next(), lines.next()) {
+        (None, _) | (Some(b""), None) => rows,
+        _ => rows.saturating_sub(1),
+    };
+
+    // the cursor line stays even when it alone does not fit
+    let mut first = heights.len();
+    let mut taken = 0;
+    while first > 0 {
+        taken += heights[first - 1];
+        if taken > rows && first < heights.len() {
+            break;
+        }
+        first -= 1;
+    }
+    scroll + first
+}
+
+// characters kept visible on each side of the cursor when the query scrolls
+const PROMPT_SCROLL_MARGIN: usize = 4;
+// below this many columns for the query the prompt scrolls along with it
+const MIN_QUERY_COLUMNS: usize = 10;
+
+pub struct TerminalRenderer<'a> {
+    components: Vec<Component<'a>>,
+    size: libc::winsize,
+    terminal_writer: TerminalWriter,
+    data_height: usize,
+    // rows and columns of the data pane this frame, the preview and frame left out
+    pane_size: (usize, usize),
+    separator: Separator,
+    // the data pane stays this short on taller terminals
+    max_data_height: Option<usize>,
+    // start of the visible part of the query, kept between renders
+    prompt_scroll: usize,
+    // the prompt before the query and the query itself
+    prompt_styles: (Style, Style),
+    header_style: Style,
+    // shown after the prompt while the query is empty
+    placeholder: Option<(String, Style)>,
+    color_rules: Vec<ColorRule>,
+    control_mode: ControlMode,
+    redraw: Redraw,
+    // the prompt wraps onto more rows instead of scrolling
+    wrap_prompt: bool,
+    // a box around the data pane, its lines are cut instead of wrapped
+    border: bool,
+    // this frame has the box, a terminal too small for it goes without
+    framed: bool,
+    // column numbers in a row above the data
+    ruler: bool,
+    preview_window: PreviewWindow,
+    // of the preview beside the data this frame, its separator included
+    preview_columns: usize,
+    // the next frame clears the screen whatever the redraw mode, e.g after a resize reflowed it
+    clear_screen: bool,
+    // the size changed, the scroll is checked against the new rows once
+    reflow: bool,
+    // where that moved the scroll, handed back to the data
+    reflowed: Option<usize>,
+    // reads the size this often besides on SIGWINCH
+    size_poll: Option<time::Duration>,
+    // handled before what is typed, e.g from --keys
+    keys: collections::VecDeque<TerminalInput>,
+    // this frame draws over the last one, rows it leaves blank are cleared
+    in_place: bool,
+    data_end: Option<DataPaneEnd>,
+
+    event_rx: sync::mpsc::Receiver<TerminalRendererEvent>,
+    hand_off: sync::Arc<HandOff>,
+}
+
+impl<'a> TerminalRenderer<'a> {
+    pub fn new(
+        components: Vec<Component<'a>>,
+        redraw_rx: sync::mpsc::Receiver<()>,
+    ) -> Result<Self> {
+        let (event_tx, event_rx) = sync::mpsc::sync_channel(0);
+        let hand_off = sync::Arc::new(HandOff::default());
+
+        // signals, written before the alternate screen so it is still there after exiting
+        let signals = signal_hook::iterator::Signals::new([
+            signal_hook::consts::SIGWINCH,
+            signal_hook::consts::SIGINT,
+            signal_hook::consts::SIGTERM,
+        ]);
+        if let Some(warning) = listen_signals(signals, event_tx.clone(), hand_off.clone()) {
+            eprintln!("{}", warning);
+        }
+
+        // input
+        thread::spawn({
+            let event_tx = event_tx.clone();
+            let mut terminal_reader = TerminalReader::new()?;
+            let hand_off = hand_off.clone();
+            move || {
+                // the terminal is gone, nothing can be typed to quit
+                let quit = || {
+                    let _ = event_tx.send(TerminalRendererEvent::Quit);
+                };
+                loop {
+                    // what is typed meanwhile is left to the command
+                    onerr!(terminal_reader.readable(-1), {
+                        quit();
+                        break;
+                    });
+                    if hand_off.is_active() {
+                        hand_off.wait();
+                        continue;
+                    }
+                    let input = onerr!(terminal_reader.read_input(), {
+                        quit();
+                        break;
+                    });
+                    if let Some(input) = input {
+                        hand_off.sent();
+                        onerr!(event_tx.send(TerminalRendererEvent::Input(input)), {
+                            break;
+                        });
+                        hand_off.wait_handled();
+                    }
+                }
+            }
+        });
+
+        // pipe redraw
+        thread::spawn({
+            let event_tx = event_tx.clone();
+            move || {
+                let throttle = Throttle::new(move || {
+                    let _ = event_tx.send(TerminalRendererEvent::Redraw);
+                });
+                loop {
+                    onerr!(redraw_rx.recv(), { break });
+                    throttle.call();
+                }
+            }
+        });
+
+        Ok(Self::from_writer(
+            components,
+            TerminalWriter::new()?,
+            event_rx,
+            hand_off,
+        ))
+    }
+
+    // without the threads reading the terminal and the signals, which new starts
+    fn from_writer(
+        components: Vec<Component<'a>>,
+        terminal_writer: TerminalWriter,
+        event_rx: sync::mpsc::Receiver<TerminalRendererEvent>,
+        hand_off: sync::Arc<HandOff>,
+    ) -> Self {
+        let size = terminal_writer.size();
+        Self {
+            size,
+            terminal_writer,
+            data_height: 0,
+            pane_size: (0, 0),
+            separator: Separator::default(),
+            max_data_height: None,
+            prompt_scroll: 0,
+            prompt_styles: Default::default(),
+            header_style: Style::default(),
+            placeholder: None,
+            color_rules: Vec::new(),
+            control_mode: ControlMode::Pass,
+            redraw: Redraw::Screen,
+            wrap_prompt: false,
+            border: false,
+            framed: false,
+            ruler: false,
+            preview_window: PreviewWindow::default(),
+            preview_columns: 0,
+            clear_screen: true,
+            reflow: false,
+            reflowed: None,
+            size_poll: None,
+            keys: collections::VecDeque::new(),
+            in_place: false,
+            data_end: None,
+            components,
+            event_rx,
+            hand_off,
+        }
+    }
+
+    pub fn set_separator(&mut self, separator: Separator) {
+        self.separator = separator;
+    }
+
+    pub fn set_max_data_height(&mut self, max_data_height: Option<usize>) {
+        self.max_data_height = max_data_height;
+    }
+
+    pub fn set_prompt_styles(&mut self, prompt: Style, query: Style) {
+        self.prompt_styles = (prompt, query);
+    }
+
+    pub fn set_header_style(&mut self, style: Style) {
+        self.header_style = style;
+    }
+
+    pub fn set_placeholder(&mut self, text: String, style: Style) {
+        self.placeholder = Some((text, style));
+    }
+
+    pub fn set_keys(&mut self, keys: Vec<TerminalInput>) {
+        self.keys = keys.into();
+    }
+
+    pub fn set_size_poll(&mut self, size_poll: Option<time::Duration>) {
+        self.size_poll = size_poll;
+    }
+
+    pub fn set_control_mode(&mut self, control_mode: ControlMode) {
+        self.control_mode = control_mode;
+    }
+
+    pub fn set_color_rules(&mut self, color_rules: Vec<ColorRule>) {
+        self.color_rules = color_rules;
+    }
+
+    pub fn set_redraw(&mut self, redraw: Redraw) {
+        self.redraw = redraw;
+    }
+
+    pub fn set_wrap_prompt(&mut self, wrap_prompt: bool) {
+        self.wrap_prompt = wrap_prompt;
+    }
+
+    pub fn set_border(&mut self, border: bool) {
+        self.border = border;
+    }
+
+    pub fn set_ruler(&mut self, ruler: bool) {
+        self.ruler = ruler;
+    }
+
+    pub fn set_preview_window(&mut self, preview_window: PreviewWindow) {
+        self.preview_window = preview_window;
+    }
+
+    pub fn set_alternate_screen(&mut self, alternate_screen: bool) {
+        self.terminal_writer.alternate_screen = alternate_screen;
+    }
+
+    // with line redraws, what is left of the old line after the columns just written.
+    // a line filling the width leaves the cursor on its last column, where clearing would
+    // take the last character, and there is nothing left to clear anyway
+    fn end_line(&mut self, columns: usize) -> Result<()> {
+        if self.in_place && columns < self.size.ws_col as usize {
+            self.terminal_writer.write(b"\x1b[K")?;
+        }
+        Ok(())
+    }
+
+    // rows left blank in this frame, starting from the next one
+    fn clear_rows(&mut self, rows: usize) -> Result<()> {
+        if self.in_place {
+            for _ in 0..rows {
+                self.terminal_writer.write(b"\r\n\x1b[K")?;
+            }
+        }
+        Ok(())
+    }
+
+    // the typed part of the prompt
+    fn query(&self) -> String {
+        self.components
+            .iter()
+            .find_map(|comp| match comp {
+                Component::Prompt(x) => {
+                    let out = x.render();
+                    Some(out.query[out.prompt_len..].iter().collect())
+                }
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    fn handle_size(&mut self) {
+        self.size = self.terminal_writer.size();
+        self.clear_screen = true;
+        self.reflow = true;
+    }
+
+    // rows of source hard wrapped at width, and the row and column of the character at index.
+    // a cursor past a full last row starts a row of its own
+    fn wrap_chars(
+        source: &[char],
+        width: usize,
+        index: usize,
+    ) -> (Vec<ops::Range<usize>>, (usize, usize)) {
+        let mut rows = Vec::new();
+        let (mut start, mut column) = (0, 0);
+        let mut cursor = (0, 0);
+        for (i, ch) in source.iter().enumerate() {
+            let ch_width = unicode_width::UnicodeWidthChar::width(*ch).unwrap_or(0);
+            // a wide character that does not fit moves to the next row as a whole
+            if column > 0 && column + ch_width > width {
+                rows.push(start..i);
+                start = i;
+                column = 0;
             }
+            if i == index {
+                cursor = (rows.len(), column);
+            }
+            column += ch_width;
+        }
+        if index >= source.len() {
+            if column >= width {
+                rows.push(start..source.len());
+                start = source.len();
+                column = 0;
+            }
+            cursor = (rows.len(), column);
+        }
+        rows.push(start..source.len());
+        (rows, cursor)
+    }
+
+    // returns the part of source that fits into width columns with the character at index visible,
+    // and the display column of index within it.
+    // like a text field it scrolls from the previous start only as far as needed
+    // to keep a margin of characters around the cursor
+    fn window_str(
+        source: &[char],
+        width: usize,
+        index: usize,
+        start: usize,
+    ) -> (ops::Range<usize>, usize) {
+        let margin = PROMPT_SCROLL_MARGIN.min(width.saturating_sub(1) / 2);
+        // the cursor takes up a column even past the end
+        let right_width = match source.get(index..(index + 1 + margin).min(source.len())) {
+            Some(right) if !right.is_empty() => chars_width(right),
+            _ => 1,
+        };
+        let cursor_end_width = if index == source.len() { 1 } else { 0 };
+
+        let mut start = start.min(index.saturating_sub(margin));
+        while start < index && chars_width(&source[start..index]) + right_width > width {
+            start += 1;
         }
+        // nothing is left unused on the right while there is more on the left
+        while start > 0 && chars_width(&source[start - 1..]) + cursor_end_width <= width {
+            start -= 1;
+        }
+
+        let mut end = start;
+        while end < source.len() && chars_width(&source[start..=end]) <= width {
+            end += 1;
+        }
+
+        (start..end, chars_width(&source[start..index]))
+    }
+
+    fn render_component_prompt(
+        &mut self,
+        out: ComponentPromptOut,
+        state: &mut TerminalRenderState,
+    ) -> Result<()> {
+        let width = self.size.ws_col as usize;
+        // rows of ranges of the prompt, and the row and column of the cursor within them
+        let (rows, (cursor_row, cursor_col)) = if self.wrap_prompt {
+            let (rows, cursor) = Self::wrap_chars(&out.query, width, out.cursor_index);
+            (rows.into_iter().map(|v| vec![v]).collect(), cursor)
+        } else {
+            // the prompt stays in place and only the query scrolls,
+            // unless that leaves too little room, then both do
+            let prompt_width = chars_width(&out.query[..out.prompt_len]);
+            let fixed = match prompt_width + MIN_QUERY_COLUMNS <= width {
+                true => out.prompt_len,
+                false => 0,
+            };
+            let fixed_width = chars_width(&out.query[..fixed]);
+            let (window, cursor_col) = Self::window_str(
+                &out.query[fixed..],
+                width - fixed_width,
+                out.cursor_index - fixed,
+                self.prompt_scroll,
+            );
+            self.prompt_scroll = window.start;
+            let window = window.start + fixed..window.end + fixed;
+            (vec![vec![0..fixed, window]], (0, fixed_width + cursor_col))
+        };
 
+        // the rows around the cursor when they do not all fit above the separator and a line of data
+        let max_rows = state.left_lines.saturating_sub(2).max(1);
+        let first_row = (cursor_row + 1).saturating_sub(max_rows);
+        let (prompt_sgr, query_sgr) = (self.prompt_styles.0.sgr(), self.prompt_styles.1.sgr());
+        for (i, ranges) in rows.iter().skip(first_row).take(max_rows).enumerate() {
+            if i > 0 {
+                self.terminal_writer.newline_start()?;
+            }
+            state.left_lines -= 1;
+            state.line += 1;
+
+            // styles are escapes without width, the cursor column stays as measured
+            let mut line = String::new();
+            let mut line_width = 0;
+            for range in ranges {
+                let prompt_end = out.prompt_len.clamp(range.start, range.end);
+                for (sgr, range) in [
+                    (&prompt_sgr, range.start..prompt_end),
+                    (&query_sgr, prompt_end..range.end),
+                ] {
+                    if range.is_empty() {
+                        continue;
+                    }
+                    line.push_str(sgr);
+                    line.extend(&out.query[range.clone()]);
+                    line_width += chars_width(&out.query[range]);
+                    if !sgr.is_empty() {
+                        line.push_str("\x1b[0m");
+                    }
+                }
+            }
+            // not part of the query, the cursor stays before it
+            if let Some((text, style)) = &self.placeholder
+                && out.query.len() == out.prompt_len
+                && first_row + i + 1 == rows.len()
+            {
+                let mut placeholder = String::new();
+                for ch in text.chars() {
+                    let ch_width = chars_width(&[ch]);
+                    if line_width + ch_width > width {
+                        break;
+                    }
+                    placeholder.push(ch);
+                    line_width += ch_width;
+                }
+                if !placeholder.is_empty() {
+                    line.push_str(&style.sgr());
+                    line.push_str(&placeholder);
+                    line.push_str("\x1b[0m");
+                }
+            }
+            self.terminal_writer.write(line.as_bytes())?;
+            self.end_line(line_width)?;
+        }
+
+        state.cursor_line = cursor_row - first_row + 1;
+        state.cursor_col = cursor_col + 1;
+        state.show_cursor = !out.read_only;
+
+        // the user's own cursor is kept until overwrite mode is used
+        let shape = match (out.overwrite, self.terminal_writer.cursor_shape) {
+            (true, _) => CursorShape::Block,
+            (false, CursorShape::Default) => CursorShape::Default,
+            (false, _) => CursorShape::Bar,
+        };
+        self.terminal_writer.set_cursor_shape(shape)?;
+
+        Ok(())
+    }
+
+    fn render_component_header(
+        &mut self,
+        out: ComponentHeaderOut,
+        state: &mut TerminalRenderState,
+    ) -> Result<()> {
+        if out.0.is_empty() {
+            return Ok(());
+        }
+
+        let sgr = self.header_style.sgr();
+        let lines = out.0.split(|v| *v == b'\n');
+        for line in lines.take(state.left_lines.saturating_sub(2)) {
+            let escaped_vec =
+                EscapedVec::new(line.iter().filter(|v| **v != b'\r').copied().collect());
+            let (fitting, _, columns) = escaped_vec.wrap(self.size.ws_col as usize, 1);
+
+            self.terminal_writer.newline_start()?;
+            self.terminal_writer.write(sgr.as_bytes())?;
+            self.terminal_writer.write(fitting)?;
+            self.terminal_writer.write(b"\x1b[0m")?;
+            self.end_line(columns)?;
+            state.left_lines -= 1;
+            state.line += 1;
+        }
+        Ok(())
+    }
+
+    fn render_component_data(
+        &mut self,
+        out: ComponentDataOut,
+        state: &mut TerminalRenderState,
+    ) -> Result<()> {
+        self.terminal_writer.newline_start()?;
+        state.left_lines -= 1;
+        state.line += 1;
+        let separator_line = state.line;
+        let width = self.size.ws_col as usize;
+        // the bottom edge takes a row of the pane
+        self.framed = self.border && frame_fits(width, state.left_lines);
+        if self.framed {
+            state.left_lines -= 1;
+            self.terminal_writer
+                .write(frame_edge(width, '┌', '┐').as_bytes())?;
+        } else {
+            self.write_separator()?;
+        }
+
+        // its row is taken from the pane, a pane of one row is left to the data
+        let ruler = self.ruler && state.left_lines >= 2;
+        state.left_lines -= ruler as usize;
+
+        let (data_lines, preview_lines, preview_columns) = data_pane_lines(
+            state.left_lines,
+            width - 2 * self.framed as usize,
+            out.preview.is_some().then_some(self.preview_window),
+            self.max_data_height,
+        );
+        self.preview_columns = preview_columns;
+        self.pane_size = (data_lines, self.data_width());
+        // the line above the first line of the data
+        let mut data_top = match self.preview_window.position {
+            PreviewPosition::Up => separator_line + preview_lines,
+            _ => separator_line,
+        };
+        if ruler {
+            data_top += 1;
+            self.terminal_writer
+                .move_cursor(data_top, self.data_column())?;
+            let width = self.data_width();
+            self.terminal_writer.write(b"\x1b[2m")?;
+            self.terminal_writer.write(ruler_line(width).as_bytes())?;
+            self.terminal_writer.write(b"\x1b[22m")?;
+            self.end_line(width)?;
+        } else if data_top != separator_line {
+            self.terminal_writer.move_cursor(data_top, 1)?;
+        }
+
+        let is_hidden = |line: &[u8]| {
+            out.filter == Some(FilterMode::Hide)
+                && out.matcher.as_ref().is_some_and(|v| !v.is_match(line))
+        };
+        let data = complete_utf8_prefix(&out.data);
+        let (scroll, cursor_line) = if out.follow {
+            let line_count = data
+                .split(|v| *v == b'\n')
+                .filter(|line| !is_hidden(line))
+                .count();
+            (
+                line_count.saturating_sub(data_lines),
+                out.cursor_line.map(|_| line_count.saturating_sub(1)),
+            )
+        } else {
+            (out.scroll, out.cursor_line)
+        };
+        // fewer rows or columns can push the cursor line out of the pane
+        let scroll = match cursor_line {
+            Some(cursor) if mem::take(&mut self.reflow) && !out.follow => {
+                let lines = data.split(|v| *v == b'\n').filter(|line| !is_hidden(line));
+                let reflowed = reflow_scroll(
+                    lines,
+                    scroll,
+                    cursor,
+                    data_lines,
+                    if self.cuts_lines() { usize::MAX } else { width },
+                    self.control_mode,
+                );
+                if reflowed != scroll {
+                    self.reflowed = Some(reflowed);
+                }
+                reflowed
+            }
+            _ => scroll,
+        };
+
+        let view = DataView {
+            matcher: out.matcher.clone(),
+            highlight: out.highlight,
+            filter: out.filter,
+            scroll,
+            cursor_line,
+            separator_line: data_top,
+            data_lines,
+        };
+        let progress = match self.data_end.take() {
+            // only what was appended is drawn, over the line that was not complete
+            Some(end)
+                if Some(end.generation) == out.generation
+                    && end.view == view
+                    && end.progress.offset <= data.len()
+                    && out.preview.is_none() =>
+            {
+                let drawn_lines = data_lines - end.progress.left_lines.max(0) as usize;
+                self.terminal_writer
+                    .move_cursor(data_top + drawn_lines, 1)?;
+                end.progress
+            }
+            _ => DataProgress {
+                offset: 0,
+                left_lines: data_lines as isize,
+                skip: scroll,
+                index: scroll,
+                above_last: scroll,
+            },
+        };
+        let (progress, end) = self.write_data_lines(data, &out, cursor_line, progress)?;
+        let left_lines = end.left_lines;
+        // the edges are drawn over the whole pane after it
+        if let Some(generation) = out.generation
+            && out.preview.is_none()
+            && !self.framed
+        {
+            self.data_end = Some(DataPaneEnd {
+                generation,
+                view,
+                progress,
+            });
+        }
+
+        self.clear_rows(left_lines.max(0) as usize)?;
+        // over the last row, so the cursor line is kept above it
+        self.data_height = data_lines;
+        if data_lines > 0
+            && let Some(below) = lines_below(data, &end, is_hidden)
+        {
+            self.terminal_writer
+                .move_cursor(data_top + data_lines, self.data_column())?;
+            let indicator = match below {
+                1 => "… 1 more line".to_string(),
+                _ => format!("… {} more lines", below),
+            };
+            let indicator = indicator
+                .chars()
+                .take(self.data_width())
+                .collect::<Vec<_>>();
+            self.terminal_writer.write(b"\x1b[2m")?;
+            self.terminal_writer
+                .write(indicator.iter().collect::<String>().as_bytes())?;
+            self.terminal_writer.write(b"\x1b[22m")?;
+            self.end_line(chars_width(&indicator))?;
+            self.data_height = data_lines - 1;
+        }
+        // up, its lines are below the top separator and its own separator below them
+        let (preview_top, preview_separator) = match self.preview_window.position {
+            PreviewPosition::Up => (separator_line, separator_line + preview_lines),
+            _ => {
+                let row = separator_line + ruler as usize + data_lines + 1;
+                (row, row)
+            }
+        };
+        if let Some(preview) = &out.preview
+            && preview_lines > 0
+        {
+            if !self.framed {
+                self.terminal_writer.move_cursor(preview_separator, 1)?;
+                self.write_separator()?;
+            }
+            self.terminal_writer.move_cursor(preview_top, 1)?;
+
+            let mut left_lines = preview_lines as isize - 1;
+            for line in preview.split(|v| *v == b'\n') {
+                if left_lines <= 0 {
+                    break;
+                }
+                self.write_data_line(line.to_vec(), &mut left_lines, b"")?;
+            }
+            self.clear_rows(left_lines.max(0) as usize)?;
+        }
+        if let Some(preview) = &out.preview
+            && preview_columns > 0
+        {
+            self.write_preview_beside(
+                preview,
+                data_top - ruler as usize,
+                data_lines + ruler as usize,
+            )?;
+        }
+        state.left_lines =
+            state.left_lines - data_lines - preview_lines + left_lines.max(0) as usize;
+        state.line = separator_line + ruler as usize + data_lines + preview_lines;
+        if self.framed {
+            self.write_frame(
+                separator_line,
+                ruler as usize + data_lines + preview_lines,
+                (preview_lines > 0).then_some(preview_separator),
+            )?;
+            state.line += 1;
+        }
+
+        // below the pane, the footer is drawn after
+        if self.in_place && state.line < self.size.ws_row as usize {
+            self.terminal_writer.move_cursor(state.line + 1, 1)?;
+            self.terminal_writer.write(b"\x1b[J")?;
+        }
+
+        Ok(())
+    }
+
+    // the lines from progress on until the rows run out, returns where the line that was not
+    // complete yet started, to draw over once it is, and where drawing ended
+    fn write_data_lines(
+        &mut self,
+        data: &[u8],
+        out: &ComponentDataOut,
+        cursor_line: Option<usize>,
+        mut progress: DataProgress,
+    ) -> Result<(DataProgress, DataProgress)> {
+        let mut incomplete = None;
+        for line in data[progress.offset..].split(|v| *v == b'\n') {
+            if progress.left_lines <= 0 {
+                break;
+            }
+            let offset = progress.offset + line.len() + 1;
+            if offset > data.len() {
+                incomplete = Some(progress);
+            }
+            progress.offset = offset;
+
+            let line = line
+                .iter()
+                .filter(|v| **v != b'\r')
+                .copied()
+                .collect::<Vec<u8>>();
+            let mut line = sanitize_controls(line, self.control_mode);
+
+            let mut dim = false;
+            if let Some(matcher) = &out.matcher
+                && let Some(filter) = out.filter
+                && !matcher.is_match(&line)
+            {
+                match filter {
+                    FilterMode::Dim => dim = true,
+                    FilterMode::Hide => continue,
+                }
+            }
+            // hidden lines do not count towards the scroll
+            if progress.skip > 0 {
+                progress.skip -= 1;
+                continue;
+            }
+            // dimmed lines stay plain, closing a bold span would end the dim too
+            let colored = !dim && !self.color_rules.is_empty();
+            if colored {
+                line = color_line(&self.color_rules, &line);
+            }
+            if let Some(matcher) = &out.matcher
+                && out.highlight
+            {
+                line = matcher.highlight(&line);
+            }
+
+            if dim {
+                line.splice(0..0, *b"\x1b[2m");
+            }
+            let is_cursor_line = cursor_line == Some(progress.index);
+            if is_cursor_line {
+                line = reverse_video(&line);
+            }
+            progress.index += 1;
+            // capping can cut off the closing sequences of dim, highlights and colors
+            let reset_styles: &[u8] = if colored {
+                b"\x1b[22;23;24;27;39m"
+            } else if out.matcher.is_some() || is_cursor_line {
+                b"\x1b[22;27m"
+            } else {
+                b""
+            };
+            self.write_data_line(line, &mut progress.left_lines, reset_styles)?;
+            if progress.left_lines >= 1 {
+                progress.above_last = progress.index;
+            }
+        }
+        Ok((incomplete.unwrap_or(progress), progress))
+    }
+
+    // columns for the data, the box takes one on each side and the preview can take more
+    fn data_width(&self) -> usize {
+        let width = self.size.ws_col as usize;
+        match self.framed {
+            true => width - 2 - self.preview_columns,
+            false => width - self.preview_columns,
+        }
+    }
+
+    // where the data starts, counting from 1
+    fn data_column(&self) -> usize {
+        let column = 1 + self.framed as usize;
+        match self.preview_window.position {
+            PreviewPosition::Left => column + self.preview_columns,
+            _ => column,
+        }
+    }
+
+    // long lines only wrap at the edge of the terminal
+    fn cuts_lines(&self) -> bool {
+        self.framed || self.preview_columns > 0
+    }
+
+    // of the line between the data and a preview beside it
+    fn preview_separator_column(&self) -> usize {
+        match self.preview_window.position {
+            PreviewPosition::Left => self.data_column() - 1,
+            _ => self.data_column() + self.data_width(),
+        }
+    }
+
+    // over the rows below top, with a line between it and the data
+    fn write_preview_beside(&mut self, preview: &[u8], top: usize, rows: usize) -> Result<()> {
+        let width = self.preview_columns - 1;
+        let separator_column = self.preview_separator_column();
+        let column = match self.preview_window.position {
+            PreviewPosition::Left => separator_column - width,
+            _ => separator_column + 1,
+        };
+        let mut lines = preview.split(|v| *v == b'\n');
+        for row in top + 1..=top + rows {
+            self.terminal_writer.move_cursor(row, separator_column)?;
+            self.terminal_writer.write("\x1b[0m│".as_bytes())?;
+            self.terminal_writer.move_cursor(row, column)?;
+            let line = EscapedVec::new(lines.next().unwrap_or_default().to_vec());
+            let (fitting, _, columns) = line.wrap(width, 1);
+            self.terminal_writer.write(fitting)?;
+            self.terminal_writer.write(b"\x1b[0m")?;
+            // what is left of the old frame, the data is on the other side
+            if self.in_place && columns < width {
+                self.terminal_writer
+                    .write(" ".repeat(width - columns).as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    // the sides and the bottom edge around rows below the top edge, styles of the data are
+    // reset first so they do not color the box
+    fn write_frame(
+        &mut self,
+        top: usize,
+        rows: usize,
+        preview_separator: Option<usize>,
+    ) -> Result<()> {
+        let width = self.size.ws_col as usize;
+        self.terminal_writer.write(b"\x1b[0m")?;
+        for row in top + 1..=top + rows {
+            if Some(row) == preview_separator {
+                self.terminal_writer.move_cursor(row, 1)?;
+                self.terminal_writer
+                    .write(frame_edge(width, '├', '┤').as_bytes())?;
+                continue;
+            }
+            self.terminal_writer.move_cursor(row, 1)?;
+            self.terminal_writer.write("│".as_bytes())?;
+            self.terminal_writer.move_cursor(row, width)?;
+            self.terminal_writer.write("│".as_bytes())?;
+        }
+        self.terminal_writer.move_cursor(top + rows + 1, 1)?;
+        self.terminal_writer
+            .write(frame_edge(width, '└', '┘').as_bytes())?;
+        // joined to the line beside the preview
+        if self.preview_columns > 0 {
+            let column = self.preview_separator_column();
+            self.terminal_writer.move_cursor(top, column)?;
+            self.terminal_writer.write("┬".as_bytes())?;
+            self.terminal_writer.move_cursor(top + rows + 1, column)?;
+            self.terminal_writer.write("┴".as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn write_separator(&mut self) -> Result<()> {
+        let line = self.separator.line(self.size.ws_col as usize);
+        self.terminal_writer.write(line.as_bytes())?;
+        self.end_line(chars_width(&line.chars().collect::<Vec<_>>()))
+    }
+
+    // wrapped over as many lines as it needs, cut off when they run out
+    fn write_data_line(
+        &mut self,
+        line: Vec<u8>,
+        left_lines: &mut isize,
+        reset_styles: &[u8],
+    ) -> Result<()> {
+        let escaped_vec = EscapedVec::new(line);
+        let max_rows = match self.cuts_lines() {
+            true => 1,
+            false => (*left_lines).max(1) as usize,
+        };
+        let (fitting, row_starts, columns) = escaped_vec.wrap_rows(self.data_width(), max_rows);
+        *left_lines -= row_starts.len() as isize + 1;
+
+        self.terminal_writer.newline_start()?;
+        // past the left edge
+        let column = self.data_column();
+        if column > 1 {
+            self.terminal_writer
+                .write(format!("\x1b[{}G", column).as_bytes())?;
+        }
+        // auto-wrap is off, so the rows are started here
+        let mut start = 0;
+        for end in row_starts.iter().copied().chain([fitting.len()]) {
+            if start > 0 {
+                self.terminal_writer.newline_start()?;
+            }
+            self.terminal_writer.write(&fitting[start..end])?;
+            start = end;
+        }
+        self.terminal_writer.write(reset_styles)?;
+        self.end_line(columns)
+    }
+
+    // the position goes to the right, like 3/128
+    fn render_component_footer(
+        &mut self,
+        out: ComponentFooterOut,
+        position: Option<(usize, usize)>,
+    ) -> Result<()> {
+        if out.0.is_empty() && position.is_none() {
+            return Ok(());
+        }
+
+        self.terminal_writer
+            .move_cursor(self.size.ws_row as usize, 1)?;
+        let width = self.size.ws_col as usize;
+        let position = position
+            .map(|(index, total)| format!("{}/{}", index, total))
+            .unwrap_or_default();
+        let mut line = out.0.chars().collect::<Vec<_>>();
+        let mut line_width = chars_width(&line);
+        // the status is cut first, with a space left before the position
+        let room = width.saturating_sub(position.len() + 1);
+        while line_width > room && !position.is_empty() || line_width > width {
+            line.pop();
+            line_width = chars_width(&line);
+        }
         self.terminal_writer
-            .move_cursor(state.cursor_line, state.cursor_col)?;
-        self.terminal_writer.show_cursor()?;
+            .write(line.iter().collect::<String>().as_bytes())?;
+        if position.len() <= width && !position.is_empty() {
+            let padding = width - line_width - position.len();
+            self.terminal_writer.write(" ".repeat(padding).as_bytes())?;
+            self.terminal_writer.write(position.as_bytes())?;
+            line_width = width;
+        }
+        self.end_line(line_width)?;
+
+        Ok(())
+    }
+
+    // the data of the last frame is still on the screen and only grew since
+    fn appends(&self, rendered: &[ComponentRenderOut]) -> bool {
+        let Some(end) = &self.data_end else {
+            return false;
+        };
+        rendered.iter().any(|v| match v {
+            ComponentRenderOut::Data(x) => x.generation == Some(end.generation),
+            _ => false,
+        })
+    }
+
+    fn rerender(&mut self) -> Result<()> {
+        let rendered = self
+            .components
+            .iter()
+            .map(|v| match v {
+                Component::Prompt(x) => ComponentRenderOut::Prompt(x.render()),
+                Component::Header(x) => ComponentRenderOut::Header(x.render()),
+                Component::Data(x) => ComponentRenderOut::Data(x.render()),
+                Component::Footer(x) => ComponentRenderOut::Footer(x.render()),
+            })
+            .collect::<Vec<_>>();
+        let position = self.components.iter().find_map(|v| match v {
+            Component::Data(x) => x.position(),
+            _ => None,
+        });
+
+        self.in_place =
+            !self.clear_screen && (self.redraw == Redraw::Lines || self.appends(&rendered));
+        if self.in_place {
+            self.terminal_writer.home()?;
+        } else {
+            self.terminal_writer.clear()?;
+            self.data_end = None;
+        }
+        self.clear_screen = false;
+        self.terminal_writer.hide_cursor()?;
+        self.terminal_writer.set_autowrap(false)?;
+
+        let mut state = TerminalRenderState::new(&self.size);
+        // footers are drawn at the bottom, reserve their lines up front
+        for x in &rendered {
+            if let ComponentRenderOut::Footer(x) = x
+                && (!x.0.is_empty() || position.is_some())
+            {
+                state.left_lines = state.left_lines.saturating_sub(1);
+            }
+        }
+        for x in rendered {
+            match x {
+                ComponentRenderOut::Prompt(x) => self.render_component_prompt(x, &mut state)?,
+                ComponentRenderOut::Header(x) => self.render_component_header(x, &mut state)?,
+                ComponentRenderOut::Data(x) => self.render_component_data(x, &mut state)?,
+                ComponentRenderOut::Footer(x) => self.render_component_footer(x, position)?,
+            }
+        }
+
+        self.terminal_writer.set_autowrap(true)?;
+        if state.show_cursor {
+            self.terminal_writer
+                .move_cursor(state.cursor_line, state.cursor_col)?;
+            self.terminal_writer.show_cursor()?;
+        }
 
         self.terminal_writer.flush()?;
 
+        self.reflow = false;
+        let reflowed = self.reflowed.take();
+        for x in &mut self.components {
+            if let Component::Data(x) = x {
+                if let Some(scroll) = reflowed {
+                    x.reflowed(scroll);
+                }
+                x.pane_size(self.pane_size.0, self.pane_size.1);
+            }
+        }
+
         Ok(())
     }
 
-    pub fn start(mut self, mut stop: impl FnMut(&TerminalInput) -> bool) -> Result<()> {
+    // the next event, a resize once polling sees the size change without a SIGWINCH
+    fn next_event(&mut self) -> Result<TerminalRendererEvent> {
+        // the last input is done with, the next one can be read
+        self.hand_off.handled();
+        // typing meanwhile waits in the input thread until these ran out
+        if let Some(input) = self.keys.pop_front() {
+            return Ok(TerminalRendererEvent::Input(input));
+        }
+        let Some(size_poll) = self.size_poll else {
+            return self
+                .event_rx
+                .recv()
+                .with_context(|| "main listen loop receive error");
+        };
+        loop {
+            match self.event_rx.recv_timeout(size_poll) {
+                Ok(event) => return Ok(event),
+                Err(sync::mpsc::RecvTimeoutError::Timeout) => {
+                    if !same_size(&self.size, &self.terminal_writer.size()) {
+                        return Ok(TerminalRendererEvent::Resize);
+                    }
+                }
+                Err(err) => return Err(err).with_context(|| "main listen loop receive error"),
+            }
+        }
+    }
+
+    pub fn start(
+        mut self,
+        mut handle: impl FnMut(&TerminalInput, &str) -> InputOutcome,
+    ) -> Result<()> {
         loop {
             self.rerender()?;
-            match self
-                .event_rx
-                .recv()
-                .with_context(|| "main listen loop receive error")?
-            {
+            let stops = self.components.iter().any(|v| match v {
+                Component::Data(x) => x.stops(),
+                _ => false,
+            });
+            if stops {
+                break;
+            }
+            match self.next_event()? {
                 TerminalRendererEvent::Resize => self.handle_size(),
                 TerminalRendererEvent::Input(terminal_input) => {
-                    if stop(&terminal_input) {
-                        break;
+                    match handle(&terminal_input, &self.query()) {
+                        InputOutcome::Continue => {}
+                        InputOutcome::Stop => break,
+                        InputOutcome::Consumed => continue,
+                        InputOutcome::RequestPaste => {
+                            self.terminal_writer.request_clipboard()?;
+                            continue;
+                        }
+                        InputOutcome::Refresh => {
+                            self.handle_size();
+                            continue;
+                        }
+                        InputOutcome::ToggleRuler => {
+                            self.ruler = !self.ruler;
+                            continue;
+                        }
+                        InputOutcome::HandOff(run) => {
+                            self.hand_off.set(true);
+                            let suspended = self.terminal_writer.suspend(false);
+                            if suspended.is_ok() {
+                                run();
+                            }
+                            let resumed = self.terminal_writer.resume();
+                            self.hand_off.set(false);
+                            suspended?;
+                            resumed?;
+                            self.handle_size();
+                            continue;
+                        }
                     }
                     for comp in &mut self.components {
                         match comp {
                             Component::Prompt(x) => x.input(&terminal_input)?,
-                            _ => {}
+                            Component::Data(x) => x.input(&terminal_input, self.data_height)?,
+                            Component::Header(_) | Component::Footer(_) => {}
                         }
                     }
                 }
                 TerminalRendererEvent::Redraw => {}
+                // SIGINT or SIGTERM, quits the same as the abort key would
                 TerminalRendererEvent::Quit => {
                     break;
                 }
@@ -500,6 +2585,43 @@ impl<'a> TerminalRenderer<'a> {
     }
 }
 
+// SIGINT and SIGTERM only come from other processes, ctrl-c is a key in raw mode.
+// without signals, resizes go unnoticed and only the keys quit, returns a warning saying so
+fn listen_signals(
+    signals: io::Result<signal_hook::iterator::Signals>,
+    event_tx: sync::mpsc::SyncSender<TerminalRendererEvent>,
+    hand_off: sync::Arc<HandOff>,
+) -> Option<String> {
+    let mut signals = match signals {
+        Ok(v) => v,
+        Err(err) => {
+            return Some(format!(
+                "tip: can not listen for signals, resizes will go unnoticed: {}",
+                err
+            ));
+        }
+    };
+
+    thread::spawn(move || {
+        for signal in &mut signals {
+            match signal {
+                libc::SIGWINCH => {
+                    onerr!(event_tx.send(TerminalRendererEvent::Resize), { break })
+                }
+                // ctrl-c of a command that has the terminal reaches tip too
+                libc::SIGINT if hand_off.is_active() => {}
+                // once is enough, the renderer stops at the first one
+                libc::SIGINT | libc::SIGTERM => {
+                    let _ = event_tx.send(TerminalRendererEvent::Quit);
+                    break;
+                }
+                _ => unreachable!(),
+            }
+        }
+    });
+    None
+}
+
 struct Throttle {
     handle: Option<thread::JoinHandle<()>>,
     should_fire: sync::Arc<sync::atomic::AtomicBool>,
@@ -578,7 +2700,6 @@ impl<'a> EscapedIter<'a> {
 
 #[derive(Debug)]
 struct EscapedIterItem {
-    #[allow(dead_code)]
     ch: u8,
     in_escape: bool,
 }
@@ -587,19 +2708,15 @@ impl<'a> Iterator for EscapedIter<'a> {
     type Item = EscapedIterItem;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let Some(ch) = self.chars.get(self.index) else {
-            return None;
-        };
+        let ch = self.chars.get(self.index)?;
         self.index += 1;
 
         let mut in_escape = self.in_escape;
         if *ch == 0x1b {
             self.in_escape = true;
             in_escape = true;
-        } else if self.in_escape && *ch != b'[' {
-            if TerminalReader::is_escape_end(*ch) {
-                self.in_escape = false;
-            }
+        } else if self.in_escape && *ch != b'[' && TerminalReader::is_escape_end(*ch) {
+            self.in_escape = false;
         }
 
         Some(EscapedIterItem { ch: *ch, in_escape })
@@ -615,35 +2732,55 @@ impl EscapedVec {
         Self { unescaped }
     }
 
-    fn len(&self) -> usize {
-        EscapedIter::new(&self.unescaped).fold(
-            0,
-            |acc, curr| {
-                if curr.in_escape { acc } else { acc + 1 }
-            },
-        )
+    // the part that fits into max_rows rows of width columns when hard wrapped like the terminal
+    // does, the rows it takes up, at least one, and the columns taken on the last one.
+    // it stops at the cut, so a huge line costs no more than what is shown
+    fn wrap(&self, width: usize, max_rows: usize) -> (&[u8], usize, usize) {
+        let (fitting, row_starts, column) = self.wrap_rows(width, max_rows);
+        (fitting, row_starts.len() + 1, column)
     }
 
-    fn cap(&self, cap: usize) -> &[u8] {
-        let mut len: usize = 0;
-        let mut real_len: usize = 0;
-
-        for v in EscapedIter::new(&self.unescaped) {
-            real_len += 1;
-            if v.in_escape {
+    // like wrap, with where the rows after the first start in the part that fits.
+    // a line exactly width wide is a single row
+    fn wrap_rows(&self, width: usize, max_rows: usize) -> (&[u8], Vec<usize>, usize) {
+        let (mut row_starts, mut column) = (Vec::new(), 0);
+        for (i, v) in EscapedIter::new(&self.unescaped).enumerate() {
+            // continuation bytes belong to the character before
+            if v.in_escape || v.ch & 0xc0 == 0x80 {
                 continue;
             }
 
-            len += 1;
-            if len >= cap {
-                break;
+            let ch_width = char_width_at(&self.unescaped[i..]);
+            // a wide character that does not fit moves to the next row as a whole
+            if column > 0 && column + ch_width > width {
+                if row_starts.len() + 1 >= max_rows {
+                    return (&self.unescaped[..i], row_starts, column);
+                }
+                row_starts.push(i);
+                column = 0;
             }
+            column += ch_width;
         }
-
-        &self.unescaped[..real_len]
+        (&self.unescaped, row_starts, column)
     }
 }
 
+// of the utf-8 character starting the bytes, invalid and control ones take a column
+fn char_width_at(bytes: &[u8]) -> usize {
+    let len = match bytes[0] {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    };
+    bytes
+        .get(..len)
+        .and_then(|v| str::from_utf8(v).ok())
+        .and_then(|v| v.chars().next())
+        .and_then(unicode_width::UnicodeWidthChar::width)
+        .unwrap_or(1)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -656,12 +2793,854 @@ mod tests {
             0x45, 0x2e,
         ]);
 
-        assert_eq!(escaped_vec.len(), "README.".len());
+        assert_eq!(escaped_vec.wrap(80, 1), (&escaped_vec.unescaped[..], 1, 7));
+        assert_eq!(escaped_vec.wrap(4, 2).1, 2);
+        assert_eq!(
+            escaped_vec.wrap(4, 1),
+            (
+                &[
+                    0x1b, b'[', b'0', b'm', 0x1b, b'[', b'3', b'5', b'm', b'R', b'E', b'A', b'D'
+                ][..],
+                1,
+                4
+            )
+        );
+    }
+
+    #[test]
+    fn escaped_vec_wrap_exact_width() {
+        let escaped_vec = EscapedVec::new(format!("\x1b[1m{}\x1b[0m", "x".repeat(60)).into_bytes());
+        let (fitting, row_starts, column) = escaped_vec.wrap_rows(60, usize::MAX);
+        assert_eq!((fitting.len(), row_starts, column), (68, vec![], 60));
+
+        // one more starts a row, after the escapes
+        let escaped_vec = EscapedVec::new(format!("\x1b[1m{}\x1b[0m", "x".repeat(61)).into_bytes());
+        let (fitting, row_starts, column) = escaped_vec.wrap_rows(60, usize::MAX);
+        assert_eq!((fitting.len(), row_starts, column), (69, vec![64], 1));
+        let (fitting, row_starts, column) = escaped_vec.wrap_rows(60, 1);
+        assert_eq!((fitting.len(), row_starts, column), (64, vec![], 60));
+    }
+
+    #[test]
+    fn escaped_vec_wrap_long_token() {
+        let escaped_vec = EscapedVec::new("x".repeat(100_000).into_bytes());
+        assert_eq!(escaped_vec.wrap(60, usize::MAX).1, 1667);
+        let (fitting, rows, column) = escaped_vec.wrap(60, 5);
+        assert_eq!((fitting.len(), rows, column), (300, 5, 60));
+
+        // wide characters wrap as a whole, a partial one is never cut
+        let escaped_vec = EscapedVec::new("日本語".repeat(10).into_bytes());
+        let (fitting, rows, column) = escaped_vec.wrap(5, 2);
+        assert_eq!(
+            (str::from_utf8(fitting).unwrap(), rows, column),
+            ("日本語日", 2, 4)
+        );
+        assert_eq!(EscapedVec::new(Vec::new()).wrap(5, 2).1, 1);
+    }
+
+    #[test]
+    fn complete_utf8_prefix_holds_back_partial() {
+        let full = "ab日".as_bytes();
+        // one byte into the 3 byte codepoint
+        assert_eq!(complete_utf8_prefix(&full[..3]), b"ab");
+        assert_eq!(complete_utf8_prefix(&full[..4]), b"ab");
+        assert_eq!(complete_utf8_prefix(full), full);
+        assert_eq!(complete_utf8_prefix(b"ab\xff"), b"ab\xff");
+        assert_eq!(complete_utf8_prefix(b""), b"");
+    }
+
+    // the write end is returned so the reader does not see eof
+    fn reader_with_input(input: &[u8]) -> (TerminalReader, fs::File) {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let (read, mut write) = unsafe {
+            use std::os::fd::FromRawFd;
+            (fs::File::from_raw_fd(fds[0]), fs::File::from_raw_fd(fds[1]))
+        };
+        write.write_all(input).unwrap();
+        (TerminalReader { tty: read }, write)
+    }
+
+    #[test]
+    fn read_input_alt() {
+        let (mut reader, _write) = reader_with_input(b"\x1b\x7f\x1bd\x1b[D\x1b[Z");
+        assert!(matches!(
+            reader.read_input().unwrap(),
+            Some(TerminalInput::AltDelete)
+        ));
+        assert!(matches!(
+            reader.read_input().unwrap(),
+            Some(TerminalInput::Alt('d'))
+        ));
+        assert!(matches!(
+            reader.read_input().unwrap(),
+            Some(TerminalInput::Escape(TerminalEscape::LeftArrow))
+        ));
+        assert!(matches!(
+            reader.read_input().unwrap(),
+            Some(TerminalInput::Escape(TerminalEscape::BackTab))
+        ));
+
+        // nothing follows within the timeout, so it is a lone escape
+        let (mut reader, _write) = reader_with_input(b"\x1b");
+        assert!(matches!(
+            reader.read_input().unwrap(),
+            Some(TerminalInput::Escape(TerminalEscape::Timeout))
+        ));
+    }
+
+    #[test]
+    fn read_input_home_end() {
+        let (mut reader, _write) = reader_with_input(
+            b"\x1b[H\x1bOH\x1b[1~\x1b[7~\x1b[F\x1bOF\x1b[4~\x1b[8~\x1bOA\x1bOD\x1b[2~",
+        );
+        let mut read = || match reader.read_input().unwrap() {
+            Some(TerminalInput::Escape(escape)) => escape,
+            input => panic!("{:?}", input),
+        };
+        for _ in 0..4 {
+            assert_eq!(read(), TerminalEscape::Home);
+        }
+        for _ in 0..4 {
+            assert_eq!(read(), TerminalEscape::End);
+        }
+        assert_eq!(read(), TerminalEscape::UpArrow);
+        assert_eq!(read(), TerminalEscape::LeftArrow);
+        assert_eq!(read(), TerminalEscape::Insert);
+
+        // nothing follows within the timeout, so it is alt-O
+        let (mut reader, _write) = reader_with_input(b"\x1bO");
+        assert!(matches!(
+            reader.read_input().unwrap(),
+            Some(TerminalInput::Alt('O'))
+        ));
+    }
+
+    #[test]
+    fn read_input_ctrl_c() {
+        let (mut reader, _write) = reader_with_input(b"\x03");
+        let input = reader.read_input().unwrap().unwrap();
+        assert!(matches!(input, TerminalInput::Ctrl(b'c')));
+        assert_eq!(
+            crate::keymap::Keymap::default().action(&input),
+            Some(crate::keymap::Action::Abort)
+        );
+    }
+
+    #[test]
+    fn read_input_paste() {
+        let (mut reader, _write) =
+            reader_with_input(b"\x1b]52;c;aMSXZGFz\x1b\\\x1b]52;c;eA==\x07\x1b]11;rgb:0/0/0\x07x");
+        assert!(matches!(
+            reader.read_input().unwrap(),
+            Some(TerminalInput::Paste(text)) if text == "hėdas"
+        ));
+        assert!(matches!(
+            reader.read_input().unwrap(),
+            Some(TerminalInput::Paste(text)) if text == "x"
+        ));
+        // other answers are dropped
+        assert!(reader.read_input().unwrap().is_none());
+        assert!(matches!(
+            reader.read_input().unwrap(),
+            Some(TerminalInput::Printable('x'))
+        ));
+    }
+
+    #[test]
+    fn reverse_video_after_highlight() {
+        assert_eq!(
+            reverse_video(b"a\x1b[7mb\x1b[27mc"),
+            b"\x1b[7ma\x1b[7mb\x1b[27m\x1b[7mc"
+        );
+    }
+
+    #[test]
+    fn separator_line_display_width() {
+        let separator = |glyph: &str, width| Separator {
+            glyph: glyph.to_string(),
+            width,
+        };
+
+        assert_eq!(Separator::default().line(5), "─────");
+        assert_eq!(separator("=-", None).line(5), "=-=-=");
+        // a wide glyph that would overshoot an odd width is left out
+        assert_eq!(separator("日", None).line(5), "日日");
+        assert_eq!(separator("—日", None).line(5), "—日—");
+        assert_eq!(separator("—日", None).line(6), "—日—日");
+        assert_eq!(separator("─", Some(3)).line(80), "───");
+        assert_eq!(separator("─", Some(30)).line(4), "────");
+    }
+
+    #[test]
+    fn highlight_smart_case() {
+        let line = b"foo Foo FOO";
+        let matcher = |query: &str| Matcher {
+            query: query.to_string(),
+            case: CaseMode::Smart,
+        };
+
+        assert_eq!(matcher("foo").find_matches(line), vec![0..3, 4..7, 8..11]);
+        assert_eq!(matcher("Foo").find_matches(line), vec![4..7]);
+        assert_eq!(
+            matcher("Foo").highlight(b"\x1b[35mfoo Foo"),
+            b"\x1b[35mfoo \x1b[7mFoo\x1b[27m"
+        );
+
+        // folded past ascii, the ranges are of bytes
+        let line = "žalia Žalia ŽALIA".as_bytes();
+        assert_eq!(
+            matcher("žalia").find_matches(line),
+            vec![0..6, 7..13, 14..20]
+        );
+        assert_eq!(matcher("Žalia").find_matches(line), vec![7..13]);
+        assert!(matcher("ėglė").is_match("EGLĖ ĖGLĖ".as_bytes()));
+        assert!(!matcher("Ėglė").is_match("ėglė".as_bytes()));
+        // bytes that are not utf-8 are skipped over
+        assert_eq!(matcher("a").find_matches(b"\xffA"), vec![1..2]);
+    }
+
+    #[test]
+    fn terminal_size_override() {
+        let winsize = libc::winsize {
+            ws_row: 0,
+            ws_col: 0,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+
+        let size = override_terminal_size(winsize, Some("40"), Some("10"));
+        assert_eq!((size.ws_col, size.ws_row), (40, 10));
+        assert_eq!(TerminalRenderState::new(&size).left_lines, 10);
+
+        let query = "a".repeat(50).chars().collect::<Vec<_>>();
+        let (window, _) = TerminalRenderer::window_str(&query, size.ws_col as usize, 45, 0);
+        assert_eq!(window.len(), 40);
+
+        for invalid in ["0", "-1", "abc", "", "70000"] {
+            let size = override_terminal_size(size, Some(invalid), Some(invalid));
+            assert_eq!((size.ws_col, size.ws_row), (40, 10));
+        }
+        let size = override_terminal_size(size, None, Some("24"));
+        assert_eq!((size.ws_col, size.ws_row), (40, 24));
+    }
+
+    #[test]
+    fn terminal_size_unsignaled_change() {
+        // the environment would override what the terminal says
+        if env::var_os("COLUMNS").is_some() || env::var_os("LINES").is_some() {
+            return;
+        }
+        let winsize = |ws_row, ws_col| libc::winsize {
+            ws_row,
+            ws_col,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let (_master, slave) = crate::child::open_pty(winsize(24, 80)).unwrap();
+        let size = get_terminal_size(slave.as_raw_fd());
+        assert!(same_size(&size, &winsize(24, 80)));
+
+        // resized without anyone being signaled, reading it again picks it up
+        unsafe { libc::ioctl(slave.as_raw_fd(), libc::TIOCSWINSZ, &winsize(30, 100)) };
+        let refreshed = get_terminal_size(slave.as_raw_fd());
+        assert!(!same_size(&size, &refreshed));
+        assert_eq!((refreshed.ws_row, refreshed.ws_col), (30, 100));
+    }
+
+    #[test]
+    fn writer_suspend_restores_terminal() {
+        let winsize = libc::winsize {
+            ws_row: 24,
+            ws_col: 80,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let (mut master, slave) = crate::child::open_pty(winsize).unwrap();
+        let termios = |fd| {
+            let mut termios = mem::MaybeUninit::<libc::termios>::uninit();
+            assert_eq!(unsafe { libc::tcgetattr(fd, termios.as_mut_ptr()) }, 0);
+            unsafe { termios.assume_init() }.c_lflag & (libc::ICANON | libc::ECHO | libc::ISIG)
+        };
+        let fd = slave.as_raw_fd();
+        let original = termios(fd);
+        assert_ne!(original, 0);
+
+        let mut writer = TerminalWriter::from_tty(fs::File::from(slave)).unwrap();
+        assert_eq!(termios(fd), 0);
+        writer.clear().unwrap();
+        writer.suspend(false).unwrap();
+        assert_eq!(termios(fd), original);
+        writer.resume().unwrap();
+        assert_eq!(termios(fd), 0);
+        drop(writer);
+
+        // the alternate screen is left for the command and entered again after it
+        let mut written = vec![0; 4096];
+        let n = master.read(&mut written).unwrap();
+        let written = String::from_utf8_lossy(&written[..n]);
+        if env::var("TIP_DEBUG").as_deref() != Ok("true") {
+            let entered = written.match_indices("\x1b[?1049h").count();
+            let left = written.match_indices("\x1b[?1049l").count();
+            assert_eq!((entered, left), (2, 2), "{:?}", written);
+        }
+    }
+
+    #[test]
+    fn writer_inline_keeps_scrollback() {
+        let winsize = libc::winsize {
+            ws_row: 4,
+            ws_col: 80,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let (mut master, slave) = crate::child::open_pty(winsize).unwrap();
+        let mut writer = TerminalWriter::from_tty(fs::File::from(slave)).unwrap();
+        writer.debug = false;
+        writer.set_cursor_shape(CursorShape::Bar).unwrap();
+        drop(writer);
+
+        // never entered, so leaving writes nothing that clears the screen
+        let mut written = vec![0; 4096];
+        let n = master.read(&mut written).unwrap();
+        let written = String::from_utf8_lossy(&written[..n]);
+        assert!(!written.contains("\x1b[?1049l"), "{:?}", written);
+        assert!(!written.contains("\x1b[J"), "{:?}", written);
+
+        let (mut master, slave) = crate::child::open_pty(winsize).unwrap();
+        let mut writer = TerminalWriter::from_tty(fs::File::from(slave)).unwrap();
+        writer.debug = false;
+        writer.alternate_screen = false;
+        writer.clear().unwrap();
+        writer.write(b"frame").unwrap();
+        drop(writer);
+
+        // the lines on the screen are scrolled out before the first frame,
+        // and only the screen is cleared, never the scrollback
+        let mut written = vec![0; 4096];
+        let n = master.read(&mut written).unwrap();
+        let written = String::from_utf8_lossy(&written[..n]);
+        assert!(
+            written.starts_with("\x1b[4;1H\n\n\n\n\x1b[H\x1b[H\x1b[J"),
+            "{:?}",
+            written
+        );
+        assert!(written.ends_with("\x1b[H\x1b[J"), "{:?}", written);
+        for v in ["\x1b[?1049h", "\x1b[?1049l", "\x1b[2J", "\x1b[3J"] {
+            assert!(!written.contains(v), "{:?}", written);
+        }
+    }
+
+    #[test]
+    fn terminal_errors_are_typed() {
+        let err = open_tty("/tip-does-not-exist/tty").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(TipError::TtyUnavailable(_))
+        ));
+        let err = TerminalReader::open("/tip-does-not-exist/tty")
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(TipError::TtyUnavailable(_))
+        ));
+
+        // not a terminal
+        let file = fs::File::open("/dev/null").unwrap();
+        let err = unsafe { enable_raw_mode(file.as_raw_fd()) }.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(TipError::TermiosError("reading terminal attributes", _))
+        ));
+
+        // the other end of the terminal is gone
+        let winsize = libc::winsize {
+            ws_row: 24,
+            ws_col: 80,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let (master, slave) = crate::child::open_pty(winsize).unwrap();
+        let mut writer = TerminalWriter::from_tty(fs::File::from(slave)).unwrap();
+        drop(master);
+        writer.write(b"frame").unwrap();
+        let err = writer.flush().unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(TipError::RenderError(_))));
+    }
+
+    #[test]
+    fn terminal_size_fallback() {
+        let winsize = libc::winsize {
+            ws_row: 0,
+            ws_col: 0,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let size = fallback_terminal_size(winsize);
+        assert_eq!((size.ws_col, size.ws_row), (80, 24));
+
+        let size = fallback_terminal_size(libc::winsize {
+            ws_col: 120,
+            ..winsize
+        });
+        assert_eq!((size.ws_col, size.ws_row), (120, 24));
+    }
+
+    #[test]
+    fn window_str_display_width() {
+        let query = "[rg]> 日本語".chars().collect::<Vec<_>>();
+        assert_eq!(chars_width(&query), 12);
+
+        // cursor after the wide characters is at column 12, not 9
+        let (window, cursor_col) = TerminalRenderer::window_str(&query, 20, query.len(), 0);
+        assert_eq!(window, 0..query.len());
+        assert_eq!(cursor_col, 12);
+
+        // scrolled so the cursor fits, a wide character never gets split
+        let (window, cursor_col) = TerminalRenderer::window_str(&query, 8, query.len(), 0);
+        assert_eq!(query[window].iter().collect::<String>(), " 日本語");
+        assert_eq!(cursor_col, 7);
+    }
+
+    #[test]
+    fn style_sgr() {
+        assert_eq!("bold,blue".parse::<Style>().unwrap().sgr(), "\x1b[1;34m");
+        assert_eq!(
+            "underline,red"
+                .parse::<Style>()
+                .unwrap()
+                .without_color()
+                .sgr(),
+            "\x1b[4m"
+        );
+        assert_eq!(Style::default().sgr(), "");
+        assert!("blink".parse::<Style>().is_err());
+    }
+
+    #[test]
+    fn listen_signals_degrades() {
+        let (event_tx, event_rx) = sync::mpsc::sync_channel(1);
+        let warning = listen_signals(
+            Err(io::Error::other("not permitted")),
+            event_tx,
+            sync::Arc::default(),
+        );
+        assert!(warning.unwrap().ends_with("not permitted"));
+        assert!(event_rx.recv().is_err());
+
+        let (event_tx, event_rx) = sync::mpsc::sync_channel(1);
+        let signals = signal_hook::iterator::Signals::new([signal_hook::consts::SIGWINCH]);
+        assert_eq!(
+            listen_signals(signals, event_tx, sync::Arc::default()),
+            None
+        );
+        unsafe { libc::raise(libc::SIGWINCH) };
+        assert!(matches!(
+            event_rx.recv_timeout(time::Duration::from_secs(5)),
+            Ok(TerminalRendererEvent::Resize)
+        ));
+    }
+
+    #[test]
+    fn sanitize_controls_modes() {
+        let line = b"ding\x07 \x1b \x1b[1;31mred\x1b[0m\t\x1b[2J";
+        assert_eq!(
+            sanitize_controls(line.to_vec(), ControlMode::Caret),
+            b"ding^G ^[ \x1b[1;31mred\x1b[0m\t^[[2J"
+        );
+        assert_eq!(
+            sanitize_controls(line.to_vec(), ControlMode::Strip),
+            b"ding  \x1b[1;31mred\x1b[0m\t[2J"
+        );
+        assert_eq!(sanitize_controls(line.to_vec(), ControlMode::Pass), line);
+
+        // every caret takes the two columns it shows as
+        let caret = sanitize_controls(b"\x07\x7f\x1b[m".to_vec(), ControlMode::Caret);
+        assert_eq!(caret, b"^G^?\x1b[m");
+        assert_eq!(
+            EscapedVec::new(caret).wrap(80, 1),
+            (&b"^G^?\x1b[m"[..], 1, 4)
+        );
+    }
+
+    #[test]
+    fn color_line_layers() {
+        let rules = ["ERROR=red", "RO=bold,green", r"\d+=underline"]
+            .map(|v| v.parse::<ColorRule>().unwrap());
+        assert_eq!(
+            color_line(&rules, b"ERROR 42"),
+            b"\x1b[31mER\x1b[39m\x1b[1;32mRO\x1b[22;39m\x1b[31mR\x1b[39m \x1b[4m42\x1b[24m"
+        );
+        // escapes of the line are kept and not matched
+        assert_eq!(
+            color_line(&rules[2..], b"\x1b[1mv1\x1b[0m"),
+            b"\x1b[1mv\x1b[4m1\x1b[0m\x1b[24m"
+        );
+        assert_eq!(color_line(&rules, b"fine"), b"fine");
+
+        assert!("a=b=bold".parse::<ColorRule>().is_ok());
+        assert!("nostyle".parse::<ColorRule>().is_err());
+        assert!("(=red".parse::<ColorRule>().is_err());
+    }
+
+    #[test]
+    fn wrap_chars_cursor() {
+        // 25 columns over three rows of 10
+        let query = ('a'..='y').collect::<Vec<_>>();
+        let (rows, cursor) = TerminalRenderer::wrap_chars(&query, 10, 14);
+        assert_eq!(rows, [0..10, 10..20, 20..25]);
+        assert_eq!(cursor, (1, 4));
+        assert_eq!(TerminalRenderer::wrap_chars(&query, 10, 10).1, (1, 0));
+        assert_eq!(TerminalRenderer::wrap_chars(&query, 10, 25).1, (2, 5));
+
+        // past a full row the cursor gets a row of its own
+        let (rows, cursor) = TerminalRenderer::wrap_chars(&query[..20], 10, 20);
+        assert_eq!(rows, [0..10, 10..20, 20..20]);
+        assert_eq!(cursor, (2, 0));
+
+        // a wide character that does not fit starts the next row
+        let query = "abcd日本".chars().collect::<Vec<_>>();
+        let (rows, cursor) = TerminalRenderer::wrap_chars(&query, 5, 5);
+        assert_eq!(rows, [0..4, 4..6]);
+        assert_eq!(cursor, (1, 2));
+    }
+
+    #[test]
+    fn window_str_scroll_margin() {
+        let query = ('a'..='z').collect::<Vec<_>>();
+        let width = 10;
+        let mut start = 0;
+        let mut previous_col = 0;
+        let steps = (0..=query.len()).chain((0..query.len()).rev());
+        for (step, index) in steps.enumerate() {
+            let (window, cursor_col) = TerminalRenderer::window_str(&query, width, index, start);
+            start = window.start;
+
+            assert!(window.contains(&index) || index == window.end);
+            assert!(cursor_col < width);
+            // margins stay visible unless the query ends there
+            if index < query.len() - PROMPT_SCROLL_MARGIN {
+                assert!(cursor_col + PROMPT_SCROLL_MARGIN < width, "{index}");
+            }
+            if index >= PROMPT_SCROLL_MARGIN {
+                assert!(cursor_col >= PROMPT_SCROLL_MARGIN, "{index}");
+            }
+            // the cursor moves a column at a time, the text scrolls under it instead of jumping
+            if step > 0 {
+                assert!(cursor_col.abs_diff(previous_col) <= 1, "{index}");
+            }
+            previous_col = cursor_col;
+        }
+
+        // a shorter query that fits again is shown from its start
+        let (window, _) = TerminalRenderer::window_str(&query[..5], width, 5, start);
+        assert_eq!(window, 0..5);
+    }
+
+    #[test]
+    fn data_pane_lines_max_height() {
+        // a tall terminal with a small cap
+        let preview = Some(PreviewWindow::default());
+        assert_eq!(data_pane_lines(60, 80, None, Some(10)), (10, 0, 0));
+        assert_eq!(data_pane_lines(60, 80, preview, Some(10)), (10, 30, 0));
+        // a terminal shorter than the cap
+        assert_eq!(data_pane_lines(6, 80, None, Some(10)), (6, 0, 0));
+        assert_eq!(data_pane_lines(0, 80, None, Some(10)), (0, 0, 0));
+        assert_eq!(data_pane_lines(60, 80, None, None), (60, 0, 0));
+    }
+
+    #[test]
+    fn preview_window_layout() {
+        let window = |s: &str| s.parse::<PreviewWindow>().unwrap();
+        assert_eq!(window("down:50%"), PreviewWindow::default());
+        assert_eq!(
+            window("right:40%"),
+            PreviewWindow {
+                position: PreviewPosition::Right,
+                size: PreviewSize::Percent(40),
+            }
+        );
+        assert_eq!(window("10").size, PreviewSize::Absolute(10));
+        assert_eq!(window("left").size, PreviewSize::Percent(50));
+        for spec in ["", "middle", "up:", "up:101%", "up:-1", "up:x%"] {
+            assert!(spec.parse::<PreviewWindow>().is_err(), "{}", spec);
+        }
+
+        // ten lines of the preview and its separator
         assert_eq!(
-            escaped_vec.cap("READ".len()),
-            [
-                0x1b, b'[', b'0', b'm', 0x1b, b'[', b'3', b'5', b'm', b'R', b'E', b'A', b'D'
-            ]
+            data_pane_lines(20, 80, Some(window("up:10")), None),
+            (9, 11, 0)
+        );
+        assert_eq!(
+            data_pane_lines(20, 80, Some(window("right:25%")), None),
+            (20, 0, 20)
+        );
+        // too small for the size asked for, and too small for a line of the preview
+        assert_eq!(
+            data_pane_lines(5, 80, Some(window("down:100%")), None),
+            (3, 2, 0)
+        );
+        assert_eq!(
+            data_pane_lines(5, 80, Some(window("down:80%")), None),
+            (1, 4, 0)
+        );
+        assert_eq!(
+            data_pane_lines(2, 80, Some(window("down:10")), None),
+            (2, 0, 0)
+        );
+        assert_eq!(
+            data_pane_lines(20, 2, Some(window("left:10")), None),
+            (20, 0, 0)
+        );
+    }
+
+    #[test]
+    fn lines_below_overflow() {
+        // where drawing a, b and c into three rows ends
+        let end = |offset, left_lines| DataProgress {
+            offset,
+            left_lines,
+            skip: 0,
+            index: 3,
+            above_last: 2,
+        };
+        let shown = |_: &[u8]| false;
+
+        // just under and exactly the height
+        let under = DataProgress {
+            offset: 4,
+            left_lines: 1,
+            skip: 0,
+            index: 2,
+            above_last: 2,
+        };
+        assert_eq!(lines_below(b"a\nb", &under, shown), None);
+        assert_eq!(lines_below(b"a\nb\nc\n", &end(6, 0), shown), None);
+        assert_eq!(lines_below(b"a\nb\nc", &end(6, 0), shown), None);
+        // just over, c is behind the indicator too
+        assert_eq!(lines_below(b"a\nb\nc\nd\n", &end(6, 0), shown), Some(2));
+        assert_eq!(lines_below(b"a\nb\nc\nd\ne", &end(6, 0), shown), Some(3));
+        // c wrapped and was cut off by the last row
+        assert_eq!(lines_below(b"a\nb\nccc", &end(8, -2), shown), Some(1));
+        // hidden lines do not count
+        let hidden = |line: &[u8]| line == b"d";
+        assert_eq!(lines_below(b"a\nb\nc\nd\n", &end(6, 0), hidden), None);
+    }
+
+    #[test]
+    fn ruler_line_columns() {
+        assert_eq!(ruler_line(20), "----+----1----+----2");
+        assert_eq!(ruler_line(0), "");
+        assert_eq!(&ruler_line(120)[94..], "+----0----+----1----+----2");
+
+        // the 10th column is the j, numbered 1 above it
+        let line = "abcdefghijklmnopqrstuvwxyz";
+        let column = ruler_line(20).find('1').unwrap();
+        assert_eq!(line.as_bytes()[column], b'j');
+    }
+
+    #[test]
+    fn frame_edge_width() {
+        for width in [3, 4, 60] {
+            let edge = frame_edge(width, '┌', '┐').chars().collect::<Vec<_>>();
+            assert_eq!(chars_width(&edge), width);
+        }
+        assert_eq!(frame_edge(4, '└', '┘'), "└──┘");
+
+        // a terminal too small goes without
+        assert!(frame_fits(3, 2));
+        assert!(!frame_fits(2, 10));
+        assert!(!frame_fits(60, 1));
+    }
+
+    #[test]
+    fn reflow_scroll_keeps_cursor_line() {
+        let data = (0..100)
+            .map(|v| format!("line {}\n", v))
+            .collect::<String>();
+        let reflow = |scroll, cursor, rows, width| {
+            let lines = data.as_bytes().split(|v| *v == b'\n');
+            reflow_scroll(lines, scroll, cursor, rows, width, ControlMode::Pass)
+        };
+
+        // line 30 selected in a pane of 10 rows, then of 5, one of them for the indicator
+        assert_eq!(reflow(22, 30, 10, 60), 22);
+        assert_eq!(reflow(22, 30, 5, 60), 27);
+        // narrower, every line takes two rows
+        assert_eq!(reflow(22, 30, 5, 4), 29);
+        assert_eq!(reflow(22, 30, 1, 4), 30);
+        // the last line has no indicator below it
+        assert_eq!(reflow(90, 99, 5, 60), 95);
+    }
+
+    #[test]
+    fn matcher_is_match() {
+        let matcher = |query: &str| Matcher {
+            query: query.to_string(),
+            case: CaseMode::Smart,
+        };
+
+        assert!(matcher("").is_match(b"anything"));
+        assert!(matcher("read").is_match(b"\x1b[35mREADME.md"));
+        assert!(!matcher("read").is_match(b"Cargo.toml"));
+        // escapes are not matched against
+        assert!(!matcher("35m").is_match(b"\x1b[35mREADME.md"));
+    }
+
+    // draws to a pty of 8 rows and 40 columns, what it wrote is read from the master
+    fn pty_renderer<'a>(components: Vec<Component<'a>>) -> (TerminalRenderer<'a>, fs::File) {
+        let winsize = libc::winsize {
+            ws_row: 8,
+            ws_col: 40,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let (master, slave) = crate::child::open_pty(winsize).unwrap();
+        let mut writer = TerminalWriter::from_tty(fs::File::from(slave)).unwrap();
+        writer.debug = false;
+        let (_event_tx, event_rx) = sync::mpsc::sync_channel(0);
+        let renderer = TerminalRenderer::from_writer(
+            components,
+            writer,
+            event_rx,
+            sync::Arc::new(HandOff::default()),
+        );
+        (renderer, master)
+    }
+
+    fn read_written(master: &mut fs::File) -> String {
+        let mut written = Vec::new();
+        let mut buf = vec![0; 4096];
+        let mut pollfd = libc::pollfd {
+            fd: master.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        while unsafe { libc::poll(&mut pollfd, 1, 50) } > 0 {
+            let n = master.read(&mut buf).unwrap();
+            written.extend_from_slice(&buf[..n]);
+        }
+        String::from_utf8_lossy(&written).into_owned()
+    }
+
+    struct TestPrompt {
+        read_only: bool,
+    }
+
+    impl ComponentPrompt for TestPrompt {
+        fn input(&mut self, _input: &TerminalInput) -> Result<()> {
+            Ok(())
+        }
+
+        fn render(&self) -> ComponentPromptOut {
+            ComponentPromptOut {
+                query: "> q".chars().collect(),
+                prompt_len: 2,
+                cursor_index: 3,
+                overwrite: false,
+                read_only: self.read_only,
+            }
+        }
+    }
+
+    #[test]
+    fn read_only_prompt_hides_the_cursor() {
+        for read_only in [false, true] {
+            let mut prompt = TestPrompt { read_only };
+            let (mut renderer, mut master) = pty_renderer(vec![Component::Prompt(&mut prompt)]);
+            renderer.rerender().unwrap();
+            let written = read_written(&mut master);
+            assert!(written.contains("\x1b[?25l"), "{:?}", written);
+            assert_eq!(written.contains("\x1b[?25h"), !read_only, "{:?}", written);
+        }
+    }
+
+    #[test]
+    fn hand_off_waits_for_the_input_handled() {
+        let hand_off = sync::Arc::new(HandOff::default());
+        hand_off.sent();
+        let reader = thread::spawn({
+            let hand_off = hand_off.clone();
+            move || hand_off.wait_handled()
+        });
+
+        // the key handed the terminal off, nothing is read until the command is done
+        hand_off.set(true);
+        thread::sleep(time::Duration::from_millis(20));
+        hand_off.set(false);
+        thread::sleep(time::Duration::from_millis(20));
+        assert!(!reader.is_finished());
+
+        hand_off.handled();
+        reader.join().unwrap();
+    }
+
+    // shared with the test, as the renderer holds on to the component
+    struct TestData(sync::Arc<sync::Mutex<(Vec<u8>, Option<u64>)>>);
+
+    impl ComponentData for TestData {
+        fn render(&self) -> ComponentDataOut {
+            let (data, generation) = self.0.lock().unwrap().clone();
+            ComponentDataOut {
+                data: sync::Arc::new(data),
+                matcher: None,
+                highlight: false,
+                filter: None,
+                scroll: 0,
+                cursor_line: None,
+                preview: None,
+                follow: false,
+                generation,
+            }
+        }
+
+        fn content(&self) -> sync::Arc<Vec<u8>> {
+            sync::Arc::new(self.0.lock().unwrap().0.clone())
+        }
+    }
+
+    #[test]
+    fn appended_data_is_drawn_alone() {
+        let shown = sync::Arc::new(sync::Mutex::new((b"one\ntwo\n".to_vec(), Some(1))));
+        let mut data = TestData(shown.clone());
+        let (mut renderer, mut master) = pty_renderer(vec![Component::Data(&mut data)]);
+        let mut frame = |data: &[u8], generation| {
+            *shown.lock().unwrap() = (data.to_vec(), generation);
+            renderer.rerender().unwrap();
+            read_written(&mut master)
+        };
+
+        let written = frame(b"one\ntwo\n", Some(1));
+        assert!(
+            written.contains("one") && written.contains("two"),
+            "{:?}",
+            written
+        );
+
+        // the same generation only grew
+        let written = frame(b"one\ntwo\nthree\n", Some(1));
+        assert!(written.contains("three"), "{:?}", written);
+        assert!(!written.contains("one"), "{:?}", written);
+
+        // another one is drawn whole, as is data without one
+        let written = frame(b"one\ntwo\nthree\nfour\n", Some(2));
+        assert!(
+            written.contains("one") && written.contains("four"),
+            "{:?}",
+            written
+        );
+        let written = frame(b"one\ntwo\nthree\nfour\nfive\n", None);
+        assert!(
+            written.contains("one") && written.contains("five"),
+            "{:?}",
+            written
+        );
+        let written = frame(b"one\ntwo\nthree\nfour\nfive\nsix\n", None);
+        assert!(
+            written.contains("one") && written.contains("six"),
+            "{:?}",
+            written
         );
     }
 }